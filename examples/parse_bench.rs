@@ -0,0 +1,35 @@
+extern crate gammakit;
+use gammakit::*;
+
+// builds a large synthetic program full of nested expressions, which is the worst case for the backtracking parser
+fn synthetic_program(functions : usize) -> String
+{
+    let mut program = String::new();
+    for i in 0..functions
+    {
+        program += &format!("def func_{}(a, b, c)\n{{\n", i);
+        program += "    var arr = [a, b, [c, a * (b + c)], {\"k\": a}];\n";
+        program += "    var x = ((a + b) * (c - (a / (b + 1)))) % 7 + arr[2][1] - arr->len();\n";
+        program += "    if (x > 10 and (a == b or !(c < a)))\n    {\n        x = round(x * a[0].b + (c + 1) * 2);\n    }\n";
+        program += "    for (var j = 0; j < 10; j += 1)\n        x += ((((((((((((j + 1) * 2) - 3) / 4) + a) * b) - c) + arr[0]) * 2) - 1) + 1) * 1);\n";
+        program += "    return x->round();\n}\n";
+    }
+    program
+}
+
+fn main() -> Result<(), String>
+{
+    use std::time::Instant;
+    let functions = std::env::args().nth(1).and_then(|x| x.parse::<usize>().ok()).unwrap_or(1000);
+    let program = synthetic_program(functions);
+    println!("{} lines", program.lines().count());
+
+    let mut interpreter = Interpreter::new(Parser::new_from_default()?);
+    interpreter.insert_default_bindings();
+
+    let start_time = Instant::now();
+    interpreter.compile_program(&program)?;
+    println!("lex, parse, and compile took {:?}", Instant::now().duration_since(start_time));
+
+    Ok(())
+}
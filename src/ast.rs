@@ -18,6 +18,28 @@ pub struct ASTNode {
 }
 
 impl ASTNode {
+    /// The name of the grammar point this node was parsed as, or the text of the token if it's a leaf.
+    pub fn text(&self) -> &str
+    {
+        &self.text
+    }
+    pub fn line(&self) -> usize
+    {
+        self.line
+    }
+    /// In codepoints, starting at 1.
+    pub fn position(&self) -> usize
+    {
+        self.position
+    }
+    pub fn children(&self) -> &[ASTNode]
+    {
+        &self.children
+    }
+    pub fn is_parent(&self) -> bool
+    {
+        self.isparent
+    }
     pub (crate) fn last_child(&'_ self) -> Result<&'_ ASTNode, String>
     {
         self.child(self.children.len()-1)
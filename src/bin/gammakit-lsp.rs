@@ -0,0 +1,26 @@
+extern crate gammakit;
+use gammakit::*;
+
+// speaks the language server protocol over stdin and stdout; stdout is reserved for protocol messages
+fn main()
+{
+    let parser = match Parser::new_from_default()
+    {
+        Ok(parser) => parser,
+        Err(err) =>
+        {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let mut interpreter = Interpreter::new(parser);
+    interpreter.insert_default_bindings();
+    
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    if let Err(err) = LanguageServer::new(interpreter).run(stdin.lock(), stdout.lock())
+    {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
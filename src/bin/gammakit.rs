@@ -0,0 +1,85 @@
+extern crate gammakit;
+use gammakit::*;
+
+use std::io::{Read, Write};
+
+const USAGE : &str = "usage: gammakit fmt [files...]
+       gammakit check [files...]
+  fmt    rewrites the given files in the canonical style, or formats standard input to standard output if no files are given
+  check  reports likely mistakes in the given files, or in standard input if no files are given, and fails if any of them are errors";
+
+fn format_files(parser : &mut Parser, files : &[String]) -> Result<(), String>
+{
+    if files.is_empty()
+    {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text).map_err(|err| format!("error: failed to read standard input: {}", err))?;
+        let formatted = parser.format(&text)?;
+        return std::io::stdout().write_all(formatted.as_bytes()).map_err(|err| format!("error: failed to write standard output: {}", err));
+    }
+    for file in files
+    {
+        let text = std::fs::read_to_string(file).map_err(|err| format!("error: failed to read {}: {}", file, err))?;
+        let formatted = parser.format(&text).map_err(|err| format!("{}\nin {}", err, file))?;
+        // leave files that are already formatted alone, so their modification times don't change
+        if formatted != text
+        {
+            std::fs::write(file, formatted).map_err(|err| format!("error: failed to write {}: {}", file, err))?;
+        }
+    }
+    Ok(())
+}
+
+fn check_files(interpreter : &mut Interpreter, files : &[String]) -> Result<(), String>
+{
+    let mut inputs = Vec::new();
+    if files.is_empty()
+    {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text).map_err(|err| format!("error: failed to read standard input: {}", err))?;
+        inputs.push(("<stdin>".to_string(), text));
+    }
+    for file in files
+    {
+        let text = std::fs::read_to_string(file).map_err(|err| format!("error: failed to read {}: {}", file, err))?;
+        inputs.push((file.clone(), text));
+    }
+    let mut errors = 0;
+    for (name, text) in inputs
+    {
+        for diagnostic in interpreter.check_program(&text)?
+        {
+            println!("{}:{}:{}: {}", name, diagnostic.line, diagnostic.position, diagnostic.message);
+            if diagnostic.severity == Severity::Error
+            {
+                errors += 1;
+            }
+        }
+    }
+    if errors > 0
+    {
+        return Err(format!("error: found {} error{}", errors, if errors == 1 { "" } else { "s" }));
+    }
+    Ok(())
+}
+
+fn main()
+{
+    let args : Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(|x| x.as_str())
+    {
+        Some("fmt") => Parser::new_from_default().and_then(|mut parser| format_files(&mut parser, &args[1..])),
+        Some("check") => Parser::new_from_default().and_then(|parser|
+        {
+            let mut interpreter = Interpreter::new(parser);
+            interpreter.insert_default_bindings();
+            check_files(&mut interpreter, &args[1..])
+        }),
+        _ => Err(USAGE.to_string())
+    };
+    if let Err(err) = result
+    {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
@@ -59,6 +59,7 @@ pub (crate) const BINSTATEMUL : u64 = 0x43;
 pub (crate) const BINSTATEDIV : u64 = 0x44;
 pub (crate) const UNSTATEINCR : u64 = 0x48;
 pub (crate) const UNSTATEDECR : u64 = 0x49;
+pub (crate) const SETOBJCONST : u64 = 0x4E;
 pub (crate) const SETBAREGLOBAL : u64 = 0x4F;
 
 pub (crate) const EVALUATEVAR : u64 = 0x50;
@@ -72,6 +73,8 @@ pub (crate) const SWITCHEXIT : u64 = 0x62;
 pub (crate) const COLLECTARRAY : u64 = 0x70;
 pub (crate) const COLLECTDICT : u64 = 0x71;
 pub (crate) const COLLECTSET : u64 = 0x72;
+pub (crate) const COLLECTSTRING : u64 = 0x73;
+pub (crate) const FORMATVAL : u64 = 0x74;
 
 pub (crate) const IF : u64 = 0x80;
 pub (crate) const WHILE : u64 = 0x82;
@@ -80,6 +83,7 @@ pub (crate) const WITH : u64 = 0x84;
 pub (crate) const WITHAS : u64 = 0x85;
 pub (crate) const FOREACH : u64 = 0x86;
 pub (crate) const SWITCH : u64 = 0x87;
+pub (crate) const WITHEACH : u64 = 0x88;
 
 pub (crate) const BREAK : u64 = 0x90;
 pub (crate) const CONTINUE : u64 = 0x91;
@@ -164,6 +168,7 @@ pub (crate) fn op_to_name(op : u8) -> &'static str
         0x44 => "BINSTATEDIV",
         0x48 => "UNSTATEINCR",
         0x49 => "UNSTATEDECR",
+        0x4E => "SETOBJCONST",
         0x4F => "SETBAREGLOBAL",
         
         0x50 => "EVALUATEVAR",
@@ -177,6 +182,8 @@ pub (crate) fn op_to_name(op : u8) -> &'static str
         0x70 => "COLLECTARRAY",
         0x71 => "COLLECTDICT",
         0x72 => "COLLECTSET",
+        0x73 => "COLLECTSTRING",
+        0x74 => "FORMATVAL",
         
         0x80 => "IF",
         0x82 => "WHILE",
@@ -185,6 +192,7 @@ pub (crate) fn op_to_name(op : u8) -> &'static str
         0x85 => "WITHAS",
         0x86 => "FOREACH",
         0x87 => "SWITCH",
+        0x88 => "WITHEACH",
         
         0x90 => "BREAK",
         0x91 => "CONTINUE",
@@ -1,12 +1,34 @@
 #![allow(clippy::len_zero)]
 #![allow(clippy::ptr_arg)]
 
-use super::{strings::*, ast::*, bytecode::*};
-use std::rc::Rc;
+use super::{strings::*, ast::*, bytecode::*, parser::{Diagnostic, Severity}};
+use std::sync::Arc;
 use std::collections::{HashMap, BTreeMap, BTreeSet};
-use super::interpreter::GlobalState;
+use super::interpreter::{GlobalState, BindingKind};
 use super::interpreter::types::{FuncSpec, ObjSpec};
 
+mod checker;
+
+// points at the exact line and position of an invalid escape sequence in a string token
+fn escape_error(token : &ASTNode, (offset, sequence) : (usize, String)) -> String
+{
+    let mut line = token.line;
+    let mut position = token.position;
+    for c in token.text.chars().take(offset)
+    {
+        if c == '\n'
+        {
+            line += 1;
+            position = 1;
+        }
+        else
+        {
+            position += 1;
+        }
+    }
+    format!("error: invalid escape sequence `{}` on line {}, position {}", sequence, line, position)
+}
+
 pub (crate) struct DebugInfo
 {
     pub (crate) last_line : usize,
@@ -23,9 +45,9 @@ impl std::fmt::Debug for DebugInfo {
 }
 
 pub struct Code {
-    pub (crate) code : Rc<Vec<u64>>,
-    pub (crate) debug : Rc<BTreeMap<usize, DebugInfo>>,
-    pub (crate) booklet : Rc<Vec<usize>>,
+    pub (crate) code : Arc<Vec<u64>>,
+    pub (crate) debug : Arc<BTreeMap<usize, DebugInfo>>,
+    pub (crate) booklet : Arc<Vec<usize>>,
     pub (crate) cached : bool
 }
 
@@ -33,7 +55,7 @@ impl std::clone::Clone for Code
 {
     fn clone(&self) -> Code
     {
-        Code{code : Rc::clone(&self.code), debug : Rc::clone(&self.debug), booklet : self.booklet.clone(), cached : self.cached}
+        Code{code : Arc::clone(&self.code), debug : Arc::clone(&self.debug), booklet : self.booklet.clone(), cached : self.cached}
     }
 }
 
@@ -41,7 +63,7 @@ impl std::cmp::PartialEq for Code
 {
     fn eq(&self, other : &Code) -> bool
     {
-        Rc::ptr_eq(&self.code, &other.code)
+        Arc::ptr_eq(&self.code, &other.code)
     }
 }
 impl Eq for Code {}
@@ -58,21 +80,21 @@ impl Code
 {
     pub (crate) fn new() -> Code
     {
-        Code{code : Rc::new(Vec::new()), debug : Rc::new(BTreeMap::new()), booklet : Rc::new(Vec::new()), cached : false}
+        Code{code : Arc::new(Vec::new()), debug : Arc::new(BTreeMap::new()), booklet : Arc::new(Vec::new()), cached : false}
     }
     fn push_op(&mut self, val : u64)
     {
-        Rc::get_mut(&mut self.booklet).unwrap().push(self.code.len());
+        Arc::get_mut(&mut self.booklet).unwrap().push(self.code.len());
         self.push(val)
     }
     fn push(&mut self, val : u64)
     {
-        Rc::get_mut(&mut self.code).unwrap().push(val);
+        Arc::get_mut(&mut self.code).unwrap().push(val);
     }
     pub (crate) fn push_for_nop_thing_only(&mut self, val : u64)
     {
-        Rc::get_mut(&mut self.booklet).unwrap().push(self.code.len());
-        Rc::get_mut(&mut self.code).unwrap().push(val);
+        Arc::get_mut(&mut self.booklet).unwrap().push(self.code.len());
+        Arc::get_mut(&mut self.code).unwrap().push(val);
     }
     pub (crate) fn len(&self) -> usize
     {
@@ -89,7 +111,7 @@ impl Code
     }
     fn add_debug_info(&mut self, pc : usize, last_line : usize, last_index : usize, last_type : &str)
     {
-        Rc::get_mut(&mut self.debug).unwrap().insert(pc, DebugInfo{last_line, last_index, last_type : last_type.to_string()});
+        Arc::get_mut(&mut self.debug).unwrap().insert(pc, DebugInfo{last_line, last_index, last_type : last_type.to_string()});
     }
     pub (crate) fn get_debug_info(&self, pc : usize) -> Option<&DebugInfo>
     {
@@ -110,7 +132,7 @@ impl<I: std::slice::SliceIndex<[u64]>> std::ops::IndexMut<I> for Code
 {
     fn index_mut(&mut self, index: I) -> &mut Self::Output
     {
-        &mut Rc::get_mut(&mut self.code).unwrap()[index]
+        &mut Arc::get_mut(&mut self.code).unwrap()[index]
     }
 }
 
@@ -131,16 +153,68 @@ enum Context {
     Lvar,
 }
 
+/// What kind of thing a name in a program refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SymbolKind {
+    Local, // var declarations and function arguments
+    Function, // def and generator
+    InstanceVar,
+    Method, // object functions, including static ones
+    ObjectConst, // const inside of an obj
+    State, // state inside of an obj
+    BareGlobal, // const
+    GlobalVar, // globalvar, accessed as global.varname
+    GlobalFunc, // globaldef
+    Binding,
+    Object,
+}
+
+/// A place in a program where a name is defined or used, as found by Interpreter::analyze_program().
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name : String,
+    pub kind : SymbolKind,
+    pub line : usize,
+    pub position : usize,
+    /// Where the name is defined, if it's defined in the program. Definitions point at themselves.
+    pub definition : Option<(usize, usize)>,
+}
+
+/// The result of Interpreter::analyze_program().
+#[derive(Debug, Clone)]
+pub struct ProgramAnalysis {
+    /// May contain "error" nodes; see Parser::parse_with_recovery().
+    pub ast : ASTNode,
+    pub diagnostics : Vec<Diagnostic>,
+    /// In the order the compiler found them.
+    pub symbols : Vec<Symbol>,
+}
+
+// what the compiler keeps track of when it's analyzing a program for tooling instead of just compiling it
+#[derive(Default)]
+struct Analysis {
+    symbols : Vec<Symbol>,
+    diagnostics : Vec<Diagnostic>,
+    globals : HashMap<(SymbolKind, usize), (usize, usize)>, // definitions by kind and name
+    members : HashMap<(usize, usize), (SymbolKind, (usize, usize))>, // instance variables and methods by object and name
+    global_arities : HashMap<usize, usize>, // argument counts of global functions by name
+}
+
 struct Scope {
     parent_size : usize,
     size : usize,
     identifiers : HashMap<usize, (usize, bool)>,
+    locations : HashMap<usize, (usize, usize)>, // where identifiers were defined; only kept track of when analyzing
+    arities : HashMap<usize, usize>, // argument counts of functions; only kept track of when analyzing
+    unread : HashMap<usize, (usize, usize)>, // variables that haven't been read yet, and where they were defined; only kept track of when analyzing
 }
 
 enum IdenLocation {
     Lexical(usize), // lexical scoped variable
     Function(usize), // user-defined function, declarations of which are subject to lexical scoping
-    InstanceVar(usize), // instance variable as found from within method body or with() body
+    InstanceVar(usize), // slot of an instance variable as found from within method body or with() body
+    InstanceFunc(usize), // name of an object function as found from within method body or with() body
+    ObjectMember(usize, usize), // object and name of a static function or object constant as found from within an object's code
     BareGlobal(usize), // bare global variable
     GlobalFunc(usize), // global function
     Binding(usize), // binding
@@ -152,11 +226,11 @@ enum IdenLocation {
 impl Scope {
     fn new(parent_size : usize) -> Scope
     {
-        Scope { parent_size, size : 0, identifiers : HashMap::new() }
+        Scope { parent_size, size : 0, identifiers : HashMap::new(), locations : HashMap::new(), arities : HashMap::new(), unread : HashMap::new() }
     }
     fn add_identifier(&mut self, name : usize, isfunction : bool) -> Option<usize>
     {
-        if self.identifiers.get(&name).is_some()
+        if self.identifiers.contains_key(&name)
         {
             None
         }
@@ -185,15 +259,25 @@ impl Scope {
     }
 }
 
+#[derive(Clone, Copy, PartialEq)]
+enum FrameKind {
+    Program,
+    Function,
+    Generator,
+}
+
 struct Frame {
+    kind : FrameKind,
     scopes : Vec<Scope>,
     objects : Vec<ObjSpec>,
+    static_function : bool, // the body of a static object function, which has no instance until a with() gives it one
+    loops : usize, // how many while and for loops deep the code being compiled is; break and continue don't work in with or foreach
 }
 
 impl Frame {
-    fn new() -> Frame
+    fn new(kind : FrameKind) -> Frame
     {
-        Frame { scopes : vec!(Scope::new(0)), objects : vec!() }
+        Frame { kind, scopes : vec!(Scope::new(0)), objects : vec!(), static_function : false, loops : 0 }
     }
     fn total_size(&self) -> usize
     {
@@ -224,13 +308,17 @@ impl Frame {
         }
         if let Some(myobj) = &self.objects.last()
         {
-            if myobj.variables.contains_key(&name)
+            if let Some(slot) = myobj.variables.get(&name)
+            {
+                return Some(IdenLocation::InstanceVar(*slot));
+            }
+            if myobj.functions.contains_key(&name) || myobj.states.values().any(|functions| functions.contains_key(&name))
             {
-                return Some(IdenLocation::InstanceVar(name)); // FIXME make this use exact index
+                return Some(IdenLocation::InstanceFunc(name));
             }
-            if myobj.functions.contains_key(&name)
+            if myobj.static_functions.contains_key(&name) || myobj.constants.contains(&name) || myobj.states.contains_key(&name)
             {
-                return Some(IdenLocation::InstanceVar(name)); // FIXME make this use exact index
+                return Some(IdenLocation::ObjectMember(myobj.ident, name));
             }
         }
         None
@@ -250,6 +338,8 @@ struct CompilerState<'a> {
     globalstate : &'a mut GlobalState,
     
     frames : Vec<Frame>,
+    
+    analysis : Option<Analysis>,
 }
 
 
@@ -267,7 +357,9 @@ impl<'a> CompilerState<'a> {
             
             globalstate,
             
-            frames : vec!(Frame::new()),
+            frames : vec!(Frame::new(FrameKind::Program)),
+            
+            analysis : None,
         };
         ret.insert_default_hooks();
         ret
@@ -302,6 +394,7 @@ impl<'a> CompilerState<'a> {
     }
     fn pop_scope(&mut self) -> usize
     {
+        self.note_unused_variables();
         self.frames.last_mut().unwrap().pop_scope()
     }
     fn add_variable(&mut self, name : &String) -> Option<usize>
@@ -327,10 +420,7 @@ impl<'a> CompilerState<'a> {
         {
             return Some(var);
         }
-        if self.globalstate.bindings.contains_key(&index)
-        || self.globalstate.trivial_bindings.contains_key(&index)
-        || self.globalstate.simple_bindings.contains_key(&index)
-        || self.globalstate.trivial_simple_bindings.contains_key(&index)
+        if self.globalstate.bindings.contains(BindingKind::Function, index)
         {
             return Some(IdenLocation::Binding(index));
         }
@@ -348,13 +438,114 @@ impl<'a> CompilerState<'a> {
         }
         None
     }
-    fn open_frame(&mut self)
+    fn note_symbol(&mut self, name : &str, kind : SymbolKind, line : usize, position : usize, definition : Option<(usize, usize)>)
+    {
+        if let Some(analysis) = self.analysis.as_mut()
+        {
+            analysis.symbols.push(Symbol { name : name.to_string(), kind, line, position, definition });
+        }
+    }
+    // records where a name is defined, when analyzing; lexical names are remembered by the scope they're in
+    fn note_definition(&mut self, name : &ASTNode, kind : SymbolKind)
+    {
+        if self.analysis.is_none()
+        {
+            return;
+        }
+        let index = self.get_string_index(&name.text);
+        let location = (name.line, name.position);
+        if matches!(kind, SymbolKind::Local | SymbolKind::Function)
+        {
+            self.lint_shadowing(name);
+            self.note_lexical_location(name);
+        }
+        else if let Some(analysis) = self.analysis.as_mut()
+        {
+            analysis.globals.insert((kind, index), location);
+        }
+        self.note_symbol(&name.text, kind, name.line, name.position, Some(location));
+    }
+    // for names that are defined in more than one scope at once, like a function's name inside of its own body
+    fn note_lexical_location(&mut self, name : &ASTNode)
+    {
+        if self.analysis.is_some()
+        {
+            let index = self.get_string_index(&name.text);
+            self.frames.last_mut().unwrap().scopes.last_mut().unwrap().locations.insert(index, (name.line, name.position));
+        }
+    }
+    fn note_member_definition(&mut self, object : usize, name : &ASTNode, kind : SymbolKind)
+    {
+        if self.analysis.is_none()
+        {
+            return;
+        }
+        let index = self.get_string_index(&name.text);
+        let location = (name.line, name.position);
+        if let Some(analysis) = self.analysis.as_mut()
+        {
+            analysis.members.insert((object, index), (kind, location));
+        }
+        self.note_symbol(&name.text, kind, name.line, name.position, Some(location));
+    }
+    // records a use of a name, when analyzing, along with where it was defined
+    fn note_reference(&mut self, name : &ASTNode)
+    {
+        if self.analysis.is_none()
+        {
+            return;
+        }
+        let index = self.get_string_index(&name.text);
+        let (kind, definition) = match self.find_identifier(&name.text)
+        {
+            Some(IdenLocation::Lexical(_)) | Some(IdenLocation::Function(_)) =>
+            {
+                let frame = self.frames.last().unwrap();
+                let scope = frame.scopes.iter().rev().find(|scope| scope.identifiers.contains_key(&index));
+                let kind = match scope.and_then(|scope| scope.identifiers.get(&index))
+                {
+                    Some((_, true)) => SymbolKind::Function,
+                    _ => SymbolKind::Local
+                };
+                let definition = scope.and_then(|scope| scope.locations.get(&index).cloned());
+                self.note_read(index);
+                (kind, definition)
+            }
+            Some(IdenLocation::InstanceVar(_)) | Some(IdenLocation::InstanceFunc(_)) | Some(IdenLocation::ObjectMember(_, _)) =>
+            {
+                let object = self.frames.last().unwrap().objects.last().map(|object| object.ident).unwrap_or(0);
+                match self.analysis.as_ref().and_then(|analysis| analysis.members.get(&(object, index)).cloned())
+                {
+                    Some((kind, location)) => (kind, Some(location)),
+                    None => (SymbolKind::InstanceVar, None)
+                }
+            }
+            Some(IdenLocation::BareGlobal(_)) => return self.note_global_reference(name, SymbolKind::BareGlobal),
+            Some(IdenLocation::GlobalFunc(_)) => return self.note_global_reference(name, SymbolKind::GlobalFunc),
+            Some(IdenLocation::Object(_)) => return self.note_global_reference(name, SymbolKind::Object),
+            Some(IdenLocation::Binding(_)) => (SymbolKind::Binding, None),
+            _ => return
+        };
+        self.note_symbol(&name.text, kind, name.line, name.position, definition);
+    }
+    fn note_global_reference(&mut self, name : &ASTNode, kind : SymbolKind)
+    {
+        if self.analysis.is_none()
+        {
+            return;
+        }
+        let index = self.get_string_index(&name.text);
+        let definition = self.analysis.as_ref().and_then(|analysis| analysis.globals.get(&(kind, index)).cloned());
+        self.note_symbol(&name.text, kind, name.line, name.position, definition);
+    }
+    fn open_frame(&mut self, kind : FrameKind)
     {
         assert!(self.frames.len() >= 1);
-        self.frames.push(Frame::new());
+        self.frames.push(Frame::new(kind));
     }
     fn close_frame(&mut self)
     {
+        self.note_unused_variables_in_frame();
         self.frames.pop();
         assert!(self.frames.len() >= 1);
     }
@@ -377,6 +568,7 @@ impl<'a> CompilerState<'a> {
         self.add_hook(&"simplexpr", CompilerState::compile_children);
         self.add_hook(&"supersimplexpr", CompilerState::compile_children);
         self.add_hook(&"string", CompilerState::compile_push_string);
+        self.add_hook(&"interpstring", CompilerState::compile_interpstring);
         self.add_hook(&"condition", CompilerState::compile_children);
         self.add_hook(&"barestatement", CompilerState::compile_children);
         self.add_hook(&"block", CompilerState::compile_block);
@@ -494,7 +686,7 @@ impl<'a> CompilerState<'a> {
     }
     fn compile_program(&mut self, ast : &ASTNode) -> Result<(), String>
     {
-        self.compile_children(ast)?;
+        self.compile_statements(&ast.children)?;
         self.code.push_op(EXIT);
         Ok(())
     }
@@ -502,7 +694,24 @@ impl<'a> CompilerState<'a> {
     {
         for child in &ast.children
         {
-            self.compile_any(&child)?;
+            self.compile_any(child)?;
+        }
+        Ok(())
+    }
+    // only the first unreachable statement in a list is reported
+    fn compile_statements(&mut self, statements : &[ASTNode]) -> Result<(), String>
+    {
+        let mut ended = false;
+        let mut reported = false;
+        for statement in statements
+        {
+            if ended && !reported && !statement.child(0).map(|child| child.text == "blankstatement").unwrap_or(true)
+            {
+                self.lint(statement, Severity::Warning, "warning: unreachable code".to_string());
+                reported = true;
+            }
+            self.compile_any(statement)?;
+            ended |= self.ends_control_flow(statement);
         }
         Ok(())
     }
@@ -515,6 +724,31 @@ impl<'a> CompilerState<'a> {
         self.compile_any(ast.last_child()?)
     }
     fn compile_statement(&mut self, ast : &ASTNode) -> Result<(), String>
+    {
+        if self.analysis.is_none()
+        {
+            return self.compile_statement_inner(ast);
+        }
+        // when analyzing, statements that fail to compile are reported and skipped, after undoing whatever they left half done
+        let frames = self.frames.len();
+        let scopes = self.frames.last().unwrap().scopes.len();
+        let objects = self.frames.last().unwrap().objects.len();
+        let loops = self.frames.last().unwrap().loops;
+        let context = self.context;
+        if let Err(message) = self.compile_statement_inner(ast)
+        {
+            let diagnostic = Diagnostic { line : self.last_line, position : self.last_index, severity : Severity::Error, message };
+            self.analysis.as_mut().unwrap().diagnostics.push(diagnostic);
+            self.frames.truncate(frames);
+            let frame = self.frames.last_mut().unwrap();
+            frame.scopes.truncate(scopes);
+            frame.objects.truncate(objects);
+            frame.loops = loops;
+            self.context = context;
+        }
+        Ok(())
+    }
+    fn compile_statement_inner(&mut self, ast : &ASTNode) -> Result<(), String>
     {
         if ast.child(0)?.text == "funcargs_head"
         {
@@ -528,6 +762,7 @@ impl<'a> CompilerState<'a> {
     
     fn compile_funcargs_head(&mut self, ast : &ASTNode) -> Result<(), String>
     {
+        self.lint_call(ast)?;
         self.compile_context_wrapped(Context::Unknown, &|x| x.compile_nth_child(ast, 0))?;
         self.compile_nth_child(ast, 1)
     }
@@ -535,6 +770,7 @@ impl<'a> CompilerState<'a> {
     {
         if ast.child(0)?.text == "name" && ast.child(0)?.child(0)?.text == "global"
         {
+            self.note_global_reference(ast.child(1)?.child(1)?.child(0)?, SymbolKind::GlobalVar);
             if !matches!(self.context, Context::Lvar)
             {
                 self.compile_pushglobalval(&ast.child(1)?.child(1)?.child(0)?.text)
@@ -544,12 +780,56 @@ impl<'a> CompilerState<'a> {
                 self.compile_pushglobal(&ast.child(1)?.child(1)?.child(0)?.text)
             }
         }
+        else if let Some(index) = self.find_namespaced_binding(ast)
+        {
+            if self.analysis.is_some()
+            {
+                let name = self.namespaced_name(ast).unwrap_or_default();
+                self.note_symbol(&name, SymbolKind::Binding, ast.line, ast.position, None);
+            }
+            self.code.push_op(PUSHBIND);
+            self.compile_u64(index as u64);
+            Ok(())
+        }
         else
         {
+            self.lint_object_member(ast)?;
             self.compile_context_wrapped(Context::Unknown, &|x| x.compile_nth_child(ast, 0))?;
             self.compile_nth_child(ast, 1)
         }
     }
+    /// Spells out chains of bare names like audio.play as "audio.play", as long as the leftmost name doesn't refer to anything in scope.
+    fn namespaced_name(&mut self, ast : &ASTNode) -> Option<String>
+    {
+        match ast.text.as_str()
+        {
+            "name" =>
+            {
+                let name = &ast.child(0).ok()?.text;
+                if name == "global" || self.find_identifier(name).is_some()
+                {
+                    return None;
+                }
+                Some(name.clone())
+            }
+            "indirection_head" =>
+            {
+                let left = self.namespaced_name(ast.child(0).ok()?)?;
+                Some(format!("{}.{}", left, ast.child(1).ok()?.child(1).ok()?.child(0).ok()?.text))
+            }
+            _ => None
+        }
+    }
+    fn find_namespaced_binding(&mut self, ast : &ASTNode) -> Option<usize>
+    {
+        let name = self.namespaced_name(ast)?;
+        let index = self.get_string_index(&name);
+        if self.globalstate.bindings.contains(BindingKind::Function, index)
+        {
+            return Some(index);
+        }
+        None
+    }
     fn compile_dismember_head(&mut self, ast : &ASTNode) -> Result<(), String>
     {
         self.compile_context_wrapped(Context::Lvar, &|x| x.compile_nth_child(ast, 0))?;
@@ -641,6 +921,11 @@ impl<'a> CompilerState<'a> {
         }
         if let Some(var) = self.find_identifier(string)
         {
+            let frame = self.frames.last().unwrap();
+            if matches!(var, IdenLocation::Selfref | IdenLocation::Other) && frame.static_function && frame.objects.len() == 1
+            {
+                return Err(format!("error: `{}` used in a static function, which isn't run in the context of an instance", string));
+            }
             match var
             {
                 IdenLocation::Lexical(index) |
@@ -657,18 +942,48 @@ impl<'a> CompilerState<'a> {
                         self.compile_u64(index as u64);
                     }
                 }
-                IdenLocation::InstanceVar(index) =>
+                // the object type is known here, so instance variables are accessed by slot instead of by name
+                IdenLocation::InstanceVar(slot) =>
                 {
                     if !matches!(self.context, Context::Lvar)
                     {
                         self.code.push_op(EVALUATEINSTVAR);
-                        self.compile_u64(index as u64);
+                        self.compile_u64(slot as u64);
                     }
                     else
                     {
                         self.code.push_op(PUSHINSTVAR);
-                        self.compile_u64(index as u64);
+                        self.compile_u64(slot as u64);
+                    }
+                }
+                // object functions aren't stored in instances, so they're looked up like self.name
+                IdenLocation::InstanceFunc(index) =>
+                {
+                    self.code.push_op(PUSHSELF);
+                    if !matches!(self.context, Context::Lvar)
+                    {
+                        self.code.push_op(EVALUATEINDIRECTION);
+                    }
+                    else
+                    {
+                        self.code.push_op(INDIRECTION);
+                    }
+                    self.compile_u64(index as u64);
+                }
+                // read-only, so assigning to these is caught by check_assignable() before it gets here
+                IdenLocation::ObjectMember(object, index) =>
+                {
+                    self.code.push_op(PUSHOBJ);
+                    self.compile_u64(object as u64);
+                    if !matches!(self.context, Context::Lvar)
+                    {
+                        self.code.push_op(EVALUATEINDIRECTION);
+                    }
+                    else
+                    {
+                        self.code.push_op(INDIRECTION);
                     }
+                    self.compile_u64(index as u64);
                 }
                 IdenLocation::Binding(index) =>
                 {
@@ -723,6 +1038,41 @@ impl<'a> CompilerState<'a> {
         self.compile_string_index_with_op(PUSHSTR, &string.to_string());
         Ok(())
     }
+    // each piece is turned into a string (with FORMATVAL for interpolated expressions) and then they're all joined
+    fn compile_interpstring(&mut self, ast : &ASTNode) -> Result<(), String>
+    {
+        let mut piececount = 0;
+        for part in ast.child_slice(1, -1)?
+        {
+            let part = part.child(0)?;
+            if part.text == "interptext"
+            {
+                let text = part.child(0)?;
+                self.compile_pushstr(&unescape(&text.text).map_err(|error| escape_error(text, error))?)?;
+            }
+            else
+            {
+                self.compile_any(part.child(1)?)?;
+                let mut spec = String::new();
+                if part.children.len() == 5
+                {
+                    for piece in &part.child(3)?.children
+                    {
+                        spec.push_str(&piece.child(0)?.text);
+                    }
+                    if FormatSpec::parse(&spec).is_none()
+                    {
+                        return Err(format!("error: invalid format specifier `{}`", spec));
+                    }
+                }
+                self.compile_string_index_with_op(FORMATVAL, &spec);
+            }
+            piececount += 1;
+        }
+        self.code.push_op(COLLECTSTRING);
+        self.compile_u64(piececount as u64);
+        Ok(())
+    }
     fn compile_null(&mut self, _ast : &ASTNode) -> Result<(), String>
     {
         self.code.push_op(PUSHNULL);
@@ -730,7 +1080,16 @@ impl<'a> CompilerState<'a> {
     }
     fn compile_name(&mut self, ast : &ASTNode) -> Result<(), String>
     {
-        self.compile_pushname(&ast.child(0)?.text)
+        let name = ast.child(0)?;
+        // when analyzing, unknown identifiers don't stop the rest of the statement from being looked at
+        if self.analysis.is_some() && name.text != "global" && self.find_identifier(&name.text).is_none()
+        {
+            self.lint(ast, Severity::Error, format!("error: unknown identifier `{}`", name.text));
+            self.code.push_op(PUSHNULL);
+            return Ok(());
+        }
+        self.note_reference(name);
+        self.compile_pushname(&name.text)
     }
     fn rewrite_code_word(&mut self, location : usize, word : u64) -> Result<(), String>
     {
@@ -778,7 +1137,8 @@ impl<'a> CompilerState<'a> {
     }
     fn compile_push_string(&mut self, ast : &ASTNode) -> Result<(), String>
     {
-        self.compile_pushstr(&unescape(&slice(&ast.child(0)?.text, 1, -1)))?;
+        let text = ast.child(0)?;
+        self.compile_pushstr(&string_literal_value(&text.text).map_err(|error| escape_error(text, error))?)?;
         Ok(())
     }
     fn compile_whilecondition(&mut self, ast : &ASTNode) -> Result<(), String>
@@ -794,7 +1154,9 @@ impl<'a> CompilerState<'a> {
         
         let point_2 = self.code.len();
         
+        self.frames.last_mut().unwrap().loops += 1;
         self.compile_nth_child(ast, 2)?;
+        self.frames.last_mut().unwrap().loops -= 1;
         self.code.push_op(WHILELOOP);
         
         let point_3 = self.code.len();
@@ -833,14 +1195,7 @@ impl<'a> CompilerState<'a> {
         {
             return plainerr("internal error: unhandled form of statement list");
         }
-        self.compile_scope_wrapped(&|x|
-        {
-            for child in ast.child_slice(1, -1)?
-            {
-                x.compile_any(child)?;
-            }
-            Ok(())
-        })
+        self.compile_scope_wrapped(&|x| x.compile_statements(ast.child_slice(1, -1)?))
     }
     fn compile_block(&mut self, ast : &ASTNode) -> Result<(), String>
     {
@@ -858,17 +1213,11 @@ impl<'a> CompilerState<'a> {
 
     fn compile_nakedblock(&mut self, ast : &ASTNode) -> Result<(), String>
     {
-        self.compile_scope_wrapped(&|x|
-        {
-            for child in &ast.children
-            {
-                x.compile_any(child)?;
-            }
-            Ok(())
-        })
+        self.compile_scope_wrapped(&|x| x.compile_statements(&ast.children))
     }
     fn compile_instruction(&mut self, ast : &ASTNode) -> Result<(), String>
     {
+        self.lint_instruction(ast.child(0)?);
         // FIXME move to function
         match ast.child(0)?.text.as_str()
         {
@@ -896,6 +1245,11 @@ impl<'a> CompilerState<'a> {
     fn compile_objdef(&mut self, ast : &ASTNode) -> Result<(), String>
     {
         let nameindex = self.get_string_index(&ast.child(1)?.child(0)?.text);
+        if self.globalstate.objects.contains_key(&nameindex)
+        {
+            self.lint(ast.child(1)?, Severity::Error, format!("error: redeclared object `{}`", ast.child(1)?.child(0)?.text));
+        }
+        self.note_definition(ast.child(1)?.child(0)?, SymbolKind::Object);
         
         let parts = &ast.child(3)?;
         
@@ -905,7 +1259,7 @@ impl<'a> CompilerState<'a> {
         variables.insert(self.get_string_index(&"id".to_string()), var_index);
         var_index += 1;
         
-        let mut incomplete_object = ObjSpec { ident : nameindex, variables, functions : BTreeMap::new() };
+        let mut incomplete_object = ObjSpec { ident : nameindex, variables, functions : BTreeMap::new(), static_functions : BTreeMap::new(), constants : BTreeSet::new(), states : BTreeMap::new() };
         
         for part in &parts.children
         {
@@ -914,8 +1268,9 @@ impl<'a> CompilerState<'a> {
                 #[allow(clippy::map_entry)] // this is simpler and expresses the intent better the "stupid" way
                 for varname in part.child(0)?.child_slice(1, -1)?
                 {
+                    self.note_member_definition(nameindex, varname.child(0)?, SymbolKind::InstanceVar);
                     let varname = &varname.child(0)?.text;
-                    let varnameindex = self.get_string_index(&varname);
+                    let varnameindex = self.get_string_index(varname);
                     if !incomplete_object.variables.contains_key(&varnameindex)
                     {
                         incomplete_object.variables.insert(varnameindex, var_index);
@@ -929,10 +1284,13 @@ impl<'a> CompilerState<'a> {
             }
         }
         
-        self.globalstate.objects.insert(nameindex, incomplete_object.clone());
-        self.globalstate.instances_by_type.insert(nameindex, BTreeSet::new());
+        Arc::make_mut(&mut self.globalstate.objects).insert(nameindex, incomplete_object.clone());
+        self.globalstate.instances_by_type.insert(nameindex, BTreeMap::new());
         
         let mut dummy_functions = BTreeMap::new();
+        let mut dummy_static_functions = BTreeMap::new();
+        let mut constants = BTreeSet::new();
+        let mut dummy_states = BTreeMap::new();
         for part in &parts.children
         {
             if part.child(0)?.text == "objfuncdef"
@@ -940,6 +1298,13 @@ impl<'a> CompilerState<'a> {
                 let def = &part.child(0)?;
                 let funcname = &def.child(1)?.child(0)?.text;
                 let argcount = def.child(3)?.children.len();
+                self.note_member_definition(nameindex, def.child(1)?.child(0)?, SymbolKind::Method);
+                
+                let funcnameindex = self.get_string_index(funcname);
+                if dummy_static_functions.contains_key(&funcnameindex) || constants.contains(&funcnameindex)
+                {
+                    return Err(format!("error: redeclared identifier `{}`", funcname));
+                }
                 
                 let func = FuncSpec {
                     startaddr : 0,
@@ -954,43 +1319,150 @@ impl<'a> CompilerState<'a> {
                 
                 dummy_functions.insert(self.get_string_index(funcname), func);
             }
+            else if part.child(0)?.text == "objstaticdef" || part.child(0)?.text == "objconstdef"
+            {
+                let def = &part.child(0)?;
+                let is_static = def.text == "objstaticdef";
+                let name = if is_static { def.child(2)?.child(0)? } else { def.child(1)?.child(0)? };
+                self.note_member_definition(nameindex, name, if is_static { SymbolKind::Method } else { SymbolKind::ObjectConst });
+                
+                let memberindex = self.get_string_index(&name.text);
+                if incomplete_object.variables.contains_key(&memberindex) || dummy_functions.contains_key(&memberindex) || dummy_static_functions.contains_key(&memberindex) || constants.contains(&memberindex)
+                {
+                    return Err(format!("error: redeclared identifier `{}`", name.text));
+                }
+                
+                if is_static
+                {
+                    let func = FuncSpec {
+                        startaddr : 0,
+                        endaddr : 0,
+                        code : Code::new(),
+                        argcount : def.child(4)?.children.len(),
+                        parentobj : nameindex,
+                        forcecontext : 0,
+                        fromobj : false,
+                        generator : false,
+                    };
+                    dummy_static_functions.insert(memberindex, func);
+                }
+                else
+                {
+                    constants.insert(memberindex);
+                }
+            }
+            else if part.child(0)?.text == "objstatedef"
+            {
+                let def = &part.child(0)?;
+                let statename = def.child(1)?.child(0)?;
+                self.note_member_definition(nameindex, statename, SymbolKind::State);
+                
+                let mut state_functions = BTreeMap::new();
+                for funcdef in &def.child(3)?.children
+                {
+                    let funcname = funcdef.child(1)?.child(0)?;
+                    self.note_member_definition(nameindex, funcname, SymbolKind::Method);
+                    if funcname.text == "create"
+                    {
+                        return plainerr("error: `create` function can't be defined inside of a state, because instances aren't in a state until they're created");
+                    }
+                    
+                    let func = FuncSpec {
+                        startaddr : 0,
+                        endaddr : 0,
+                        code : Code::new(),
+                        argcount : funcdef.child(3)?.children.len(),
+                        parentobj : nameindex,
+                        forcecontext : 0,
+                        fromobj : true,
+                        generator : false,
+                    };
+                    if state_functions.insert(self.get_string_index(&funcname.text), func).is_some()
+                    {
+                        return Err(format!("error: redeclared identifier `{}`", funcname.text));
+                    }
+                }
+                if dummy_states.insert(self.get_string_index(&statename.text), state_functions).is_some()
+                {
+                    return Err(format!("error: redeclared identifier `{}`", statename.text));
+                }
+            }
+        }
+        
+        // states share a namespace with the object's other members, and functions inside of them can only override the object's own functions
+        for (statename, state_functions) in &dummy_states
+        {
+            let taken = |name : &usize| incomplete_object.variables.contains_key(name) || dummy_static_functions.contains_key(name) || constants.contains(name);
+            if taken(statename) || dummy_functions.contains_key(statename) || dummy_states.values().any(|functions| functions.contains_key(statename))
+            {
+                return Err(format!("error: redeclared identifier `{}`", self.globalstate.get_string(*statename)));
+            }
+            if let Some(name) = state_functions.keys().find(|name| taken(name))
+            {
+                return Err(format!("error: redeclared identifier `{}`", self.globalstate.get_string(*name)));
+            }
         }
         
         incomplete_object.functions = dummy_functions;
-        self.globalstate.objects.insert(nameindex, incomplete_object.clone());
+        incomplete_object.static_functions = dummy_static_functions;
+        incomplete_object.constants = constants;
+        incomplete_object.states = dummy_states;
+        Arc::make_mut(&mut self.globalstate.objects).insert(nameindex, incomplete_object.clone());
         
         let mut functions = BTreeMap::new();
+        let mut states = BTreeMap::new();
         for part in &parts.children
         {
             if part.child(0)?.text == "objfuncdef"
             {
                 let def = &part.child(0)?;
-                let funcname = &def.child(1)?.child(0)?.text;
+                let func = self.compile_objfuncdef(def, &incomplete_object, &["create", "destroy"])?;
+                functions.insert(self.get_string_index(&def.child(1)?.child(0)?.text), func);
+            }
+            else if part.child(0)?.text == "objstatedef"
+            {
+                let def = &part.child(0)?;
+                let mut state_functions = BTreeMap::new();
+                for funcdef in &def.child(3)?.children
+                {
+                    let func = self.compile_objfuncdef(funcdef, &incomplete_object, &["enter", "exit", "destroy"])?;
+                    state_functions.insert(self.get_string_index(&funcdef.child(1)?.child(0)?.text), func);
+                }
+                states.insert(self.get_string_index(&def.child(1)?.child(0)?.text), state_functions);
+            }
+        }
+        
+        // static functions don't run in the context of an instance, so only the object's static functions and constants can be used as bare names in them
+        let mut static_view = incomplete_object.clone();
+        static_view.variables = BTreeMap::new();
+        static_view.functions = BTreeMap::new();
+        static_view.states = incomplete_object.states.keys().map(|state| (*state, BTreeMap::new())).collect();
+        
+        let mut static_functions = BTreeMap::new();
+        for part in &parts.children
+        {
+            if part.child(0)?.text == "objstaticdef"
+            {
+                let def = &part.child(0)?;
+                let funcname = &def.child(2)?.child(0)?.text;
                 
                 let oldcode = self.code.clone();
                 self.code = Code::new();
-                self.open_frame();
-                self.frames.last_mut().unwrap().objects.push(incomplete_object.clone());
+                self.open_frame(FrameKind::Function);
+                self.frames.last_mut().unwrap().objects.push(static_view.clone());
+                self.frames.last_mut().unwrap().static_function = true;
                 
-                let argcount = def.child(3)?.children.len();
+                let argcount = def.child(4)?.children.len();
                 self.add_function(funcname).ok_or_else(|| format!("error: redeclared identifier `{}`", funcname))?;
-                for arg in &def.child(3)?.children
+                self.note_lexical_location(def.child(2)?.child(0)?);
+                self.note_arity(funcname, argcount);
+                for arg in &def.child(4)?.children
                 {
                     let name = &arg.child(0)?.text;
                     self.add_variable(name).ok_or_else(|| format!("error: redeclared identifier `{}`", name))?;
+                    self.note_definition(arg.child(0)?, SymbolKind::Local);
                 }
-                if funcname == "create" && argcount != 0
-                {
-                    return Err("error: `create` function must have 0 arguments".to_string());
-                }
-                if funcname == "destroy" && argcount != 0
-                {
-                    return Err("error: `destroy` function must have 0 arguments".to_string());
-                }
-                for statement in &def.child(6)?.children
-                {
-                    self.compile_any(&statement)?;
-                }
+                self.compile_statements(&def.child(7)?.children)?;
                 self.code.push_op(EXIT);
                 
                 self.close_frame();
@@ -1004,19 +1476,75 @@ impl<'a> CompilerState<'a> {
                     argcount,
                     parentobj : nameindex,
                     forcecontext : 0,
-                    fromobj : true,
+                    fromobj : false,
                     generator : false,
                 };
                 
-                functions.insert(self.get_string_index(funcname), func);
+                static_functions.insert(self.get_string_index(funcname), func);
             }
         }
         
         incomplete_object.functions = functions;
+        incomplete_object.static_functions = static_functions;
+        incomplete_object.states = states;
         let complete_object = incomplete_object;
-        self.globalstate.objects.insert(nameindex, complete_object);
+        Arc::make_mut(&mut self.globalstate.objects).insert(nameindex, complete_object);
         
-        Ok(())
+        // like bare globals, object constants get their values when their definition is reached at runtime
+        self.frames.last_mut().unwrap().objects.push(static_view);
+        let result = parts.children.iter().filter(|part| part.child(0).map(|def| def.text == "objconstdef").unwrap_or(false)).try_for_each(|part|
+        {
+            let def = &part.child(0)?;
+            self.compile_nth_child(def, 3)?;
+            self.code.push_op(SETOBJCONST);
+            self.compile_u64(nameindex as u64);
+            self.compile_string_index(&def.child(1)?.child(0)?.text);
+            Ok(())
+        });
+        self.frames.last_mut().unwrap().objects.pop();
+        result
+    }
+    // hooks are functions that the interpreter calls on its own, like create, so they can't take arguments
+    fn compile_objfuncdef(&mut self, def : &ASTNode, object : &ObjSpec, hooks : &[&str]) -> Result<FuncSpec, String>
+    {
+        let funcname = &def.child(1)?.child(0)?.text;
+        
+        let oldcode = self.code.clone();
+        self.code = Code::new();
+        self.open_frame(FrameKind::Function);
+        self.frames.last_mut().unwrap().objects.push(object.clone());
+        
+        let argcount = def.child(3)?.children.len();
+        self.add_function(funcname).ok_or_else(|| format!("error: redeclared identifier `{}`", funcname))?;
+        self.note_lexical_location(def.child(1)?.child(0)?);
+        self.note_arity(funcname, argcount);
+        for arg in &def.child(3)?.children
+        {
+            let name = &arg.child(0)?.text;
+            self.add_variable(name).ok_or_else(|| format!("error: redeclared identifier `{}`", name))?;
+            self.note_definition(arg.child(0)?, SymbolKind::Local);
+        }
+        if hooks.contains(&funcname.as_str()) && argcount != 0
+        {
+            return Err(format!("error: `{}` function must have 0 arguments", funcname));
+        }
+        self.compile_statements(&def.child(6)?.children)?;
+        self.code.push_op(EXIT);
+        
+        self.close_frame();
+        let funccode = self.code.clone();
+        self.code = oldcode;
+        
+        Ok(FuncSpec {
+            startaddr : 0,
+            endaddr : funccode.code.len(),
+            code : funccode,
+            argcount,
+            parentobj : object.ident,
+            forcecontext : 0,
+            fromobj : true,
+            generator : false,
+        })
     }
     fn compile_funcdef(&mut self, ast : &ASTNode) -> Result<(), String>
     {
@@ -1038,27 +1566,29 @@ impl<'a> CompilerState<'a> {
             }
             _ => return plainerr("error: first token of funcdef must be \"def\" | \"generator\"")
         }
+        self.note_definition(ast.child(1)?.child(0)?, SymbolKind::Function);
+        self.note_arity(name, ast.child(3)?.children.len());
         
         self.code.push_op(prefix);
         
         self.compile_u64(ast.child(3)?.children.len() as u64);
         
-        let body_len_position = self.compile_u64(0 as u64);
+        let body_len_position = self.compile_u64(0_u64);
         
         let position_1 = self.code.len();
         
-        self.open_frame();
+        self.open_frame(if prefix == GENERATORDEF { FrameKind::Generator } else { FrameKind::Function });
         self.add_function(name).ok_or_else(|| format!("error: redeclared identifier `{}`", name))?;
+        self.note_lexical_location(ast.child(1)?.child(0)?);
+        self.note_arity(name, ast.child(3)?.children.len());
         for child in &ast.child(3)?.children
         {
             let name = &child.child(0)?.text;
             self.add_variable(name).ok_or_else(|| format!("error: redeclared identifier `{}`", name))?;
+            self.note_definition(child.child(0)?, SymbolKind::Local);
         }
         
-        for statement in &ast.child(6)?.children
-        {
-            self.compile_any(&statement)?;
-        }
+        self.compile_statements(&ast.child(6)?.children)?;
         self.code.push_op(EXIT);
         
         self.close_frame();
@@ -1081,21 +1611,27 @@ impl<'a> CompilerState<'a> {
             return Err(format!("error: redeclared global function `{}`", name));
         }
         
+        self.note_definition(ast.child(1)?.child(0)?, SymbolKind::GlobalFunc);
+        if let Some(analysis) = self.analysis.as_mut()
+        {
+            analysis.global_arities.insert(nameindex, ast.child(3)?.children.len());
+        }
+        
         let oldcode = self.code.clone();
         self.code = Code::new();
-        self.open_frame();
+        self.open_frame(FrameKind::Function);
         self.add_function(name).ok_or_else(|| format!("error: redeclared identifier `{}`", name))?;
+        self.note_lexical_location(ast.child(1)?.child(0)?);
+        self.note_arity(name, ast.child(3)?.children.len());
         let mut argcount = 0;
         for arg in &ast.child(3)?.children
         {
             let name = &arg.child(0)?.text;
             self.add_variable(name).ok_or_else(|| format!("error: redeclared identifier `{}`", name))?;
+            self.note_definition(arg.child(0)?, SymbolKind::Local);
             argcount += 1;
         }
-        for statement in &ast.child(6)?.children
-        {
-            self.compile_any(&statement)?;
-        }
+        self.compile_statements(&ast.child(6)?.children)?;
         self.code.push_op(EXIT);
         
         self.close_frame();
@@ -1120,8 +1656,18 @@ impl<'a> CompilerState<'a> {
     
     fn compile_with(&mut self, ast : &ASTNode) -> Result<(), String>
     {
+        if ast.child(2)?.text != "name"
+        {
+            return self.compile_with_each(ast);
+        }
         let name = &ast.child(2)?.child(0)?.text;
         let index = self.get_string_index(name);
+        // with(name) runs over an object type if there's one with that name, and otherwise over whatever the name holds
+        if !self.globalstate.objects.contains_key(&index) && self.find_identifier(name).is_some()
+        {
+            return self.compile_with_each(ast);
+        }
+        self.note_reference(ast.child(2)?.child(0)?);
         self.code.push_op(WITH);
         self.compile_u64(index as u64);
         
@@ -1130,12 +1676,7 @@ impl<'a> CompilerState<'a> {
         let position_1 = self.code.len();
         let myobj = self.globalstate.objects.get(&index).ok_or_else(|| format!("error: unknown object type `{}`", name))?;
         self.frames.last_mut().unwrap().objects.push(myobj.clone());
-        self.compile_scope_wrapped(&|x|
-        {
-            x.compile_nth_child(ast, 4)?;
-            x.code.push_op(WITHLOOP);
-            Ok(())
-        })?;
+        self.compile_scope_wrapped(&|x| x.compile_with_body(ast))?;
         self.frames.last_mut().unwrap().objects.pop();
         let position_2 = self.code.len();
         let block_len = position_2 - position_1;
@@ -1143,6 +1684,44 @@ impl<'a> CompilerState<'a> {
         
         Ok(())
     }
+    fn compile_with_each(&mut self, ast : &ASTNode) -> Result<(), String>
+    {
+        self.compile_nth_child(ast, 2)?;
+        self.code.push_op(WITHEACH);
+        
+        let len_position = self.compile_u64(0);
+        
+        let position_1 = self.code.len();
+        // the instances can be of any object type, so their variables and functions can only be reached through self, like self.hp
+        self.frames.last_mut().unwrap().objects.push(ObjSpec::default());
+        self.compile_scope_wrapped(&|x| x.compile_with_body(ast))?;
+        self.frames.last_mut().unwrap().objects.pop();
+        let position_2 = self.code.len();
+        let block_len = position_2 - position_1;
+        self.rewrite_code_word(len_position, pack_u64(block_len as u64))?;
+        
+        Ok(())
+    }
+    // with(... where condition) tests the condition in the context of each instance, and skips the block for the ones it's false for
+    fn compile_with_body(&mut self, ast : &ASTNode) -> Result<(), String>
+    {
+        if ast.child(3)?.text == "where"
+        {
+            self.compile_nth_child(ast, 4)?;
+            self.code.push_op(IF);
+            let body_len_position = self.compile_u64(0);
+            let position_1 = self.code.len();
+            self.compile_nth_child(ast, 6)?;
+            let position_2 = self.code.len();
+            self.rewrite_code_word(body_len_position, pack_u64((position_2 - position_1) as u64))?;
+        }
+        else
+        {
+            self.compile_nth_child(ast, 4)?;
+        }
+        self.code.push_op(WITHLOOP);
+        Ok(())
+    }
     fn compile_withas(&mut self, ast : &ASTNode) -> Result<(), String>
     {
         self.compile_nth_child(ast, 2)?;
@@ -1150,6 +1729,9 @@ impl<'a> CompilerState<'a> {
         
         let obj_name = &ast.child(4)?.child(0)?.text;
         let obj_index = self.get_string_index(obj_name);
+        self.note_reference(ast.child(4)?.child(0)?);
+        // the block accesses instance variables by their slots in this object type, so the instance has to be checked against it
+        self.compile_u64(obj_index as u64);
         
         let len_position = self.compile_u64(0);
         
@@ -1186,6 +1768,8 @@ impl<'a> CompilerState<'a> {
                         self.compile_nth_child(child, 2)?;
                         
                         self.add_variable(name).ok_or_else(|| format!("error: redeclared identifier `{}`", name))?;
+                        self.note_definition(child.child(0)?.child(0)?, SymbolKind::Local);
+                        self.note_unread(child.child(0)?.child(0)?);
                         self.code.push_op(NEWVAR);
                         
                         self.compile_context_wrapped(Context::Lvar, &|x| x.compile_pushname(&child.child(0)?.child(0)?.text))?;
@@ -1199,6 +1783,7 @@ impl<'a> CompilerState<'a> {
                             return Err(format!("error: redeclared bare global variable `{}`", name));
                         }
                         self.globalstate.insert_global(nameindex);
+                        self.note_definition(child.child(0)?.child(0)?, SymbolKind::GlobalVar);
                         
                         self.compile_nth_child(child, 2)?;
                         self.compile_pushglobal(&child.child(0)?.child(0)?.text)?;
@@ -1215,6 +1800,8 @@ impl<'a> CompilerState<'a> {
                     "var" =>
                     {
                         self.add_variable(name).ok_or_else(|| format!("error: redeclared identifier `{}`", name))?;
+                        self.note_definition(child.child(0)?.child(0)?, SymbolKind::Local);
+                        self.note_unread(child.child(0)?.child(0)?);
                         self.code.push_op(NEWVAR);
                     }
                     "globalvar" =>
@@ -1225,6 +1812,7 @@ impl<'a> CompilerState<'a> {
                             return Err(format!("error: redeclared bare global variable `{}`", name));
                         }
                         self.globalstate.insert_global(nameindex);
+                        self.note_definition(child.child(0)?.child(0)?, SymbolKind::GlobalVar);
                     }
                     _ => return plainerr("internal error: unknown prefix to variable declaration")
                 }
@@ -1242,6 +1830,7 @@ impl<'a> CompilerState<'a> {
             return Err(format!("error: redeclared bare global variable `{}`", name));
         }
         self.globalstate.insert_bare_global(nameindex);
+        self.note_definition(ast.child(1)?.child(0)?, SymbolKind::BareGlobal);
         
         self.compile_nth_child(ast, 3)?;
         self.code.push_op(SETBAREGLOBAL);
@@ -1253,8 +1842,12 @@ impl<'a> CompilerState<'a> {
         let operator = &ast.child(1)?.child(0)?.text;
         let op = get_assignment_type(operator).ok_or_else(|| minierr(&format!("internal error: unhandled or unsupported type of binary statement {}", operator)))?;
         
+        self.check_assignable(ast.child(0)?)?;
         self.compile_nth_child(ast, 2)?;
+        // plain assignments to a variable don't count as reading it
+        let unread = if operator == "=" { self.unread_target(ast.child(0)?)? } else { None };
         self.compile_nth_child(ast, 0)?;
+        self.restore_unread(unread);
         self.code.push_op(op as u64);
         
         Ok(())
@@ -1263,6 +1856,7 @@ impl<'a> CompilerState<'a> {
     {
         let operator = &ast.child(1)?.child(0)?.text;
         
+        self.check_assignable(ast.child(0)?)?;
         self.compile_nth_child(ast, 0)?;
         match operator.as_str()
         {
@@ -1273,6 +1867,30 @@ impl<'a> CompilerState<'a> {
         
         Ok(())
     }
+    // object constants and static functions are read-only; at runtime, assigning to one would only give an unhelpful error about the stack
+    fn check_assignable(&mut self, lvar : &ASTNode) -> Result<(), String>
+    {
+        let target = lvar.child(0)?;
+        let member = match target.text.as_str()
+        {
+            "name" => match self.find_identifier(&target.child(0)?.text)
+            {
+                Some(IdenLocation::ObjectMember(object, _)) => Some((self.globalstate.get_string(object), &target.child(0)?.text)),
+                _ => None
+            }
+            "indirection_head" if target.child(0)?.text == "name" => match self.find_identifier(&target.child(0)?.child(0)?.text)
+            {
+                Some(IdenLocation::Object(_)) => Some((target.child(0)?.child(0)?.text.clone(), &target.child(1)?.child(1)?.child(0)?.text)),
+                _ => None
+            }
+            _ => None
+        };
+        if let Some((object, name)) = member
+        {
+            return Err(format!("error: tried to assign to `{}.{}`; object constants and static functions are read-only", object, name));
+        }
+        Ok(())
+    }
     fn compile_lvar(&mut self, ast : &ASTNode) -> Result<(), String>
     {
         self.compile_context_wrapped(Context::Lvar, &|x| x.compile_nth_child(ast, 0))
@@ -1309,9 +1927,10 @@ impl<'a> CompilerState<'a> {
             self.compile_nth_child(capture, 2)?;
         }
         
-        self.open_frame();
+        self.open_frame(FrameKind::Function);
         
         self.add_function(&"lambda_self".to_string()).ok_or_else(|| minierr("error: redeclared identifier `lambda_self`"))?;
+        self.note_arity("lambda_self", args.len());
         
         self.code.push_op(LAMBDA);
         self.compile_u64(captures.len() as u64);
@@ -1319,22 +1938,25 @@ impl<'a> CompilerState<'a> {
         {
             self.add_variable(capture_name).ok_or_else(|| format!("error: redeclared identifier `{}`", capture_name))?;
         }
+        for capture in captures
+        {
+            self.note_definition(capture.child(0)?.child(0)?, SymbolKind::Local);
+            self.note_unread(capture.child(0)?.child(0)?);
+        }
         self.compile_u64(args.len() as u64);
-        let len_position = self.compile_u64(0 as u64);
+        let len_position = self.compile_u64(0_u64);
           
         for arg in args
         {
             let name = &arg.child(0)?.text;
             self.add_variable(name).ok_or_else(|| format!("error: redeclared identifier `{}`", name))?;
+            self.note_definition(arg.child(0)?, SymbolKind::Local);
         }
         
         let position_1 = self.code.len();
         
-        for statement in statements
-        {
-            self.compile_any(statement)?;
-        }
-                
+        self.compile_statements(statements)?;
+        
         self.code.push_op(EXIT);
         
         self.close_frame();
@@ -1476,7 +2098,9 @@ impl<'a> CompilerState<'a> {
         self.code.push_op(WHILETEST);
         
         let position_3 = self.code.len();
+        self.frames.last_mut().unwrap().loops += 1;
         self.compile_last_child(ast)?;
+        self.frames.last_mut().unwrap().loops -= 1;
         self.code.push_op(WHILELOOP);
         
         let position_4 = self.code.len();
@@ -1540,6 +2164,7 @@ impl<'a> CompilerState<'a> {
             
             x.code.push_op(FOREACHHEAD);
             x.add_variable(&ast.child(2)?.child(0)?.text);
+            x.note_definition(ast.child(2)?.child(0)?, SymbolKind::Local);
             
             x.compile_nth_child(ast, 6)?;
             
@@ -1654,10 +2279,27 @@ impl<'a> CompilerState<'a> {
     }
 }
 
-pub fn compile_bytecode<'a>(ast : &ASTNode, global : &'a mut GlobalState) -> Result<Code, String>
+pub fn compile_bytecode(ast : &ASTNode, global : &mut GlobalState) -> Result<Code, String>
 {
     let mut state = CompilerState::new(global);
     let signal = state.compile_any(ast);
     state.trap_error(signal)?;
     Ok(state.code)
 }
+
+// compiles an AST for tooling, finding every name it defines and uses and every statement that fails to compile; error nodes from parse_with_recovery are skipped
+pub (crate) fn analyze_bytecode(ast : &ASTNode, global : &mut GlobalState) -> (Vec<Symbol>, Vec<Diagnostic>)
+{
+    let mut state = CompilerState::new(global);
+    state.analysis = Some(Analysis::default());
+    state.add_hook(&"error", CompilerState::compile_nop);
+    if let Err(message) = state.compile_any(ast)
+    {
+        let diagnostic = Diagnostic { line : state.last_line, position : state.last_index, severity : Severity::Error, message };
+        state.analysis.as_mut().unwrap().diagnostics.push(diagnostic);
+    }
+    state.note_unused_variables_in_frame();
+    let mut analysis = state.analysis.take().unwrap_or_default();
+    analysis.diagnostics.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.position));
+    (analysis.symbols, analysis.diagnostics)
+}
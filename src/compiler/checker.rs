@@ -0,0 +1,260 @@
+// checks for likely mistakes that only run when the compiler is analyzing a program for tooling, e.g. Interpreter::check_program()
+// they're done while compiling, so names are looked up exactly the way the compiled code will look them up
+
+use super::*;
+
+// a variable that hasn't been read yet: which scope of the current frame it's in, its name, and where it was defined
+type UnreadVariable = (usize, usize, (usize, usize));
+
+impl<'a> CompilerState<'a> {
+    pub (super) fn lint_at(&mut self, line : usize, position : usize, severity : Severity, message : String)
+    {
+        if let Some(analysis) = self.analysis.as_mut()
+        {
+            analysis.diagnostics.push(Diagnostic { line, position, severity, message });
+        }
+    }
+    pub (super) fn lint(&mut self, node : &ASTNode, severity : Severity, message : String)
+    {
+        self.lint_at(node.line, node.position, severity, message);
+    }
+    // remembers how many arguments a function in the current scope takes
+    pub (super) fn note_arity(&mut self, name : &str, argcount : usize)
+    {
+        if self.analysis.is_some()
+        {
+            let index = self.get_string_index(&name.to_string());
+            self.frames.last_mut().unwrap().scopes.last_mut().unwrap().arities.insert(index, argcount);
+        }
+    }
+    // var declarations and lambda captures are reported if they're never read; arguments aren't
+    pub (super) fn note_unread(&mut self, name : &ASTNode)
+    {
+        if self.analysis.is_some()
+        {
+            let index = self.get_string_index(&name.text);
+            self.frames.last_mut().unwrap().scopes.last_mut().unwrap().unread.insert(index, (name.line, name.position));
+        }
+    }
+    pub (super) fn note_read(&mut self, index : usize)
+    {
+        if let Some(scope) = self.frames.last_mut().unwrap().scopes.iter_mut().rev().find(|scope| scope.identifiers.contains_key(&index))
+        {
+            scope.unread.remove(&index);
+        }
+    }
+    // for `name = value`: which scope the variable is unread in, if it is, so that assigning to it doesn't count as reading it
+    pub (super) fn unread_target(&mut self, lvar : &ASTNode) -> Result<Option<UnreadVariable>, String>
+    {
+        let target = lvar.child(0)?;
+        if self.analysis.is_none() || target.text != "name"
+        {
+            return Ok(None);
+        }
+        let index = self.get_string_index(&target.child(0)?.text);
+        let scopes = &self.frames.last().unwrap().scopes;
+        Ok(scopes.iter().enumerate().rev().find(|(_, scope)| scope.identifiers.contains_key(&index)).and_then(|(depth, scope)|
+            scope.unread.get(&index).map(|location| (depth, index, *location))
+        ))
+    }
+    pub (super) fn restore_unread(&mut self, unread : Option<UnreadVariable>)
+    {
+        if let Some((depth, index, location)) = unread
+        {
+            if let Some(scope) = self.frames.last_mut().unwrap().scopes.get_mut(depth)
+            {
+                scope.unread.insert(index, location);
+            }
+        }
+    }
+    fn note_unused_variables_in(&mut self, unread : Vec<(usize, (usize, usize))>)
+    {
+        for (index, (line, position)) in unread
+        {
+            let name = self.get_string(index);
+            if !name.starts_with('_')
+            {
+                self.lint_at(line, position, Severity::Warning, format!("warning: unused variable `{}`", name));
+            }
+        }
+    }
+    // for the innermost scope, right before it's closed
+    pub (super) fn note_unused_variables(&mut self)
+    {
+        let unread = self.frames.last_mut().unwrap().scopes.last_mut().map(|scope| scope.unread.drain().collect()).unwrap_or_default();
+        self.note_unused_variables_in(unread);
+    }
+    pub (super) fn note_unused_variables_in_frame(&mut self)
+    {
+        let unread = self.frames.last_mut().unwrap().scopes.iter_mut().flat_map(|scope| scope.unread.drain()).collect();
+        self.note_unused_variables_in(unread);
+    }
+    // called when a local variable or function has just been added to the current scope
+    pub (super) fn lint_shadowing(&mut self, name : &ASTNode)
+    {
+        let index = self.get_string_index(&name.text);
+        let frame = self.frames.last().unwrap();
+        let outer = &frame.scopes[..frame.scopes.len() - 1];
+        let object = frame.objects.last();
+        let what = if outer.iter().any(|scope| scope.identifiers.contains_key(&index))
+        {
+            "a variable from an outer scope"
+        }
+        else if object.map(|object| object.variables.contains_key(&index)).unwrap_or(false)
+        {
+            "an instance variable"
+        }
+        else if object.map(|object| object.functions.contains_key(&index) || object.states.values().any(|functions| functions.contains_key(&index))).unwrap_or(false)
+        {
+            "an object function"
+        }
+        else if self.globalstate.bindings.contains(BindingKind::Function, index)
+        {
+            "a binding"
+        }
+        else if self.globalstate.barevariables.contains_key(&index)
+        {
+            "a global constant"
+        }
+        else if self.globalstate.objects.contains_key(&index)
+        {
+            "an object type"
+        }
+        else if self.globalstate.functions.contains_key(&index)
+        {
+            "a global function"
+        }
+        else
+        {
+            return;
+        };
+        self.lint(name, Severity::Warning, format!("warning: `{}` shadows {}", name.text, what));
+    }
+    // whether nothing after this statement in the same list can run
+    pub (super) fn ends_control_flow(&self, statement : &ASTNode) -> bool
+    {
+        let keyword = match statement.child(0)
+        {
+            Ok(child) if child.text == "instruction" => child.child(0),
+            _ => return false
+        };
+        let keyword = match keyword
+        {
+            Ok(keyword) => keyword,
+            Err(_) => return false
+        };
+        // a break or continue outside of a loop is already an error, so what comes after it isn't reported as well
+        match keyword.text.as_str()
+        {
+            "return" => true,
+            "break" | "continue" => self.frames.last().unwrap().loops > 0,
+            _ => false
+        }
+    }
+    pub (super) fn lint_instruction(&mut self, keyword : &ASTNode)
+    {
+        let frame = self.frames.last().unwrap();
+        match keyword.text.as_str()
+        {
+            "break" | "continue" if frame.loops == 0 =>
+                self.lint(keyword, Severity::Error, format!("error: `{}` outside of a while or for loop", keyword.text)),
+            "yield" if frame.kind == FrameKind::Function =>
+                self.lint(keyword, Severity::Error, "error: `yield` inside of a function that isn't a generator; use return instead".to_string()),
+            _ => {}
+        }
+    }
+    // for Objname.member, if Objname refers to an object type: the indexes of the object and the member's name
+    fn object_member(&mut self, ast : &ASTNode) -> Result<Option<(usize, usize)>, String>
+    {
+        let left = ast.child(0)?;
+        if left.text != "name"
+        {
+            return Ok(None);
+        }
+        match self.find_identifier(&left.child(0)?.text)
+        {
+            Some(IdenLocation::Object(object)) => Ok(Some((object, self.get_string_index(&ast.child(1)?.child(1)?.child(0)?.text)))),
+            _ => Ok(None)
+        }
+    }
+    pub (super) fn lint_object_member(&mut self, ast : &ASTNode) -> Result<(), String>
+    {
+        if self.analysis.is_none()
+        {
+            return Ok(());
+        }
+        if let Some((object, member)) = self.object_member(ast)?
+        {
+            let known = self.globalstate.objects.get(&object).map(|spec| spec.static_functions.contains_key(&member) || spec.constants.contains(&member) || spec.states.contains_key(&member)).unwrap_or(true);
+            if !known
+            {
+                let member = ast.child(1)?.child(1)?;
+                self.lint(member, Severity::Error, format!("error: object type `{}` has no static function or constant named `{}`", ast.child(0)?.child(0)?.text, member.child(0)?.text));
+            }
+        }
+        Ok(())
+    }
+    // the name that a call is reported with and the number of arguments that the callee takes, if it's known
+    fn known_arity(&mut self, callee : &ASTNode) -> Result<Option<(String, usize)>, String>
+    {
+        Ok(match callee.text.as_str()
+        {
+            "name" =>
+            {
+                let name = &callee.child(0)?.text;
+                let location = self.find_identifier(name);
+                let nameindex = self.get_string_index(name);
+                let frame = self.frames.last().unwrap();
+                let object = frame.objects.last();
+                let arity = match location
+                {
+                    Some(IdenLocation::Function(_)) => frame.scopes.iter().rev().find(|scope| scope.identifiers.contains_key(&nameindex)).and_then(|scope| scope.arities.get(&nameindex).cloned()),
+                    Some(IdenLocation::InstanceFunc(index)) => object.and_then(|object|
+                        object.functions.get(&index).or_else(|| object.states.values().find_map(|functions| functions.get(&index)))
+                    ).map(|func| func.argcount),
+                    Some(IdenLocation::ObjectMember(_, index)) => object.and_then(|object| object.static_functions.get(&index)).map(|func| func.argcount),
+                    Some(IdenLocation::Binding(index)) => self.globalstate.bindings.arity(BindingKind::Function, index),
+                    Some(IdenLocation::GlobalFunc(index)) => self.analysis.as_ref().and_then(|analysis| analysis.global_arities.get(&index).cloned()),
+                    _ => None
+                };
+                arity.map(|arity| (name.clone(), arity))
+            }
+            "indirection_head" => match self.object_member(callee)?
+            {
+                Some((object, member)) =>
+                {
+                    let name = format!("{}.{}", callee.child(0)?.child(0)?.text, self.get_string(member));
+                    self.globalstate.objects.get(&object).and_then(|spec| spec.static_functions.get(&member)).map(|func| (name, func.argcount))
+                }
+                None => match self.find_namespaced_binding(callee)
+                {
+                    Some(index) => self.globalstate.bindings.arity(BindingKind::Function, index).map(|arity| (self.get_string(index), arity)),
+                    None => None
+                }
+            }
+            "dismember_head" =>
+            {
+                let name = &callee.child(1)?.child(1)?.child(0)?.text;
+                let index = self.get_string_index(name);
+                self.globalstate.bindings.arity(BindingKind::Arrow, index).map(|arity| (name.clone(), arity))
+            }
+            _ => None
+        })
+    }
+    pub (super) fn lint_call(&mut self, ast : &ASTNode) -> Result<(), String>
+    {
+        if self.analysis.is_none()
+        {
+            return Ok(());
+        }
+        let argcount = ast.child(1)?.child(1)?.children.len();
+        if let Some((name, arity)) = self.known_arity(ast.child(0)?)?
+        {
+            if arity != argcount
+            {
+                self.lint(ast, Severity::Error, format!("error: wrong number of arguments to {}(); expected {}, got {}", name, arity, argcount));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,438 @@
+// pretty-prints program text in a canonical style, using the raw parse tree to know what each token is doing
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{ast::*, parser::*};
+
+const INDENT : &str = "    ";
+
+// grammar points whose braces hold statements, written on their own lines (Allman style)
+const BLOCK_BRACE_PARENTS : [&str; 9] = ["statementlist", "funcdef", "globalfuncdef", "objfuncdef", "objstaticdef", "objstatedef", "objdef", "lambda", "switch"];
+// grammar points whose children each start on a new line
+const LINE_LISTS : [&str; 8] = ["program", "statementlist", "funcdefstatements", "lambdabody", "nakedblock", "objparts", "objstatefuncs", "switchcases"];
+// keywords whose header in parentheses directly follows them
+const HEADER_KEYWORDS : [&str; 5] = ["if", "while", "for", "with", "switch"];
+// grammar points whose brackets hug their contents
+const TIGHT_BRACE_PARENTS : [&str; 4] = ["dictbody", "setbody", "dictindex", "lambda"];
+// grammar points that are written one element per line if they were written across several lines
+const COLLECTIONS : [&str; 3] = ["arraybody", "dictbody", "setbody"];
+
+// a grammar point that a token is inside of
+struct Ancestor<'a> {
+    id : usize,
+    name : &'a str,
+    // whether the token is the first or last one inside of it
+    first : bool,
+    last : bool,
+}
+
+// a token along with the grammar points it's inside of, outermost first
+struct Token<'a> {
+    text : &'a str,
+    line : usize,
+    position : usize,
+    path : Vec<Ancestor<'a>>,
+}
+
+impl<'a> Token<'a> {
+    fn parent(&self) -> &'a str
+    {
+        self.path.last().map(|x| x.name).unwrap_or("")
+    }
+    // pieces of interpolated strings are written exactly as they were, because their text (and lack of whitespace) matters
+    fn is_interp_literal(&self) -> bool
+    {
+        let parent = self.parent();
+        parent == "interpstart" || parent == "interptext" || parent == "interpopen" || parent == "interpend"
+        || (parent == "interpexpr" && (self.text == "}" || self.text == ":"))
+        || self.path.iter().any(|x| x.name == "formatspec")
+    }
+    fn is_block_brace(&self) -> bool
+    {
+        (self.text == "{" || self.text == "}") && BLOCK_BRACE_PARENTS.contains(&self.parent())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum Gap {
+    None,
+    Space,
+    Newline,
+}
+
+// punctuation that goes right up against whatever comes before it, even a block comment
+fn hugs_left(text : &str) -> bool
+{
+    text == "," || text == ";" || text == ")" || text == "]"
+}
+
+// whether two tokens on the same line go right up against each other; the lexer gets the final say in Formatter::can_touch
+fn spacing(prev : &Token, cur : &Token) -> Gap
+{
+    let (p, c) = (prev.text, cur.text);
+    let (pp, cp) = (prev.parent(), cur.parent());
+    let tight =
+        (prev.is_interp_literal() && pp != "interpend") || (cur.is_interp_literal() && cp != "interpstart")
+        || hugs_left(c) || p == "(" || p == "["
+        || p == "." || c == "." || p == "->" || c == "->"
+        || pp == "unop" || cp == "unstateop"
+        || (c == "(" && (HEADER_KEYWORDS.contains(&p) || cp == "funcargs" || cp == "lambda" || cp == "funcdef" || cp == "globalfuncdef" || cp == "objfuncdef" || cp == "objstaticdef"))
+        || (c == "[" && cp == "arrayindex")
+        || (c == "{" && (cp == "dictindex" || cp == "lambda"))
+        || (c == ":" && (cp == "dictval" || cp == "switchcase" || cp == "switchdefault"))
+        || (p == "{" && TIGHT_BRACE_PARENTS.contains(&pp))
+        || (c == "}" && TIGHT_BRACE_PARENTS.contains(&cp));
+    if tight { Gap::None } else { Gap::Space }
+}
+
+struct Formatter<'a> {
+    parser : &'a mut Parser,
+    comments : Vec<LexToken>,
+    next_comment : usize,
+    out : String,
+    indent : usize,
+    gap : Gap,
+    // source line that whatever was written last ended on, for keeping blank lines and trailing comments
+    last_line : usize,
+    // whether the last thing written was the opening brace of a block, which never gets a blank line after it
+    after_open : bool,
+    // whether the last thing written was the opening brace of an empty lambda body, written as {}
+    inline_open : bool,
+    // whether the last thing written was a comment
+    after_comment : bool,
+    // whether a newline was asked for since the last token was written, as opposed to one that only follows a comment
+    line_requested : bool,
+    // whether a comment ended the line partway through a statement, so the rest of it is indented one more level
+    continued : bool,
+    // for each block being written, whether its body is a single indented statement without braces
+    blocks : Vec<bool>,
+    // collections that were written across several lines
+    multiline : HashSet<usize>,
+    touch_cache : HashMap<(String, String), bool>,
+}
+
+impl<'a> Formatter<'a> {
+    fn write(&mut self, text : &str, line : usize, blank_allowed : bool)
+    {
+        match self.gap
+        {
+            Gap::Newline if !self.out.is_empty() =>
+            {
+                self.out.push('\n');
+                if blank_allowed && !self.after_open && line > self.last_line + 1
+                {
+                    self.out.push('\n');
+                }
+                for _ in 0..self.indent + self.continued as usize
+                {
+                    self.out.push_str(INDENT);
+                }
+            }
+            Gap::Space => self.out.push(' '),
+            _ => {}
+        }
+        self.out.push_str(text);
+        self.gap = Gap::None;
+        self.last_line = line + text.matches('\n').count();
+        self.after_open = false;
+        self.inline_open = false;
+        self.after_comment = false;
+    }
+    fn comment_before(&self, line : usize, position : usize) -> Option<&LexToken>
+    {
+        self.comments.get(self.next_comment).filter(|comment| (comment.line, comment.position) < (line, position))
+    }
+    // comments on the same line as what was written last stay there
+    fn write_trailing_comments(&mut self, line : usize, position : usize)
+    {
+        while let Some(comment) = self.comment_before(line, position).filter(|comment| comment.line == self.last_line && !self.out.is_empty()).cloned()
+        {
+            let gap = self.gap;
+            self.gap = Gap::Space;
+            self.write(&comment.text, comment.line, false);
+            self.after_comment = true;
+            self.gap = if comment.text.starts_with("//") { Gap::Newline } else if gap > Gap::Space { gap } else { Gap::Space };
+            self.next_comment += 1;
+        }
+    }
+    // the rest go on their own lines, except block comments that were followed by code on the same line
+    fn write_own_line_comments(&mut self, line : usize, position : usize)
+    {
+        while let Some(comment) = self.comment_before(line, position).cloned()
+        {
+            self.gap = Gap::Newline;
+            self.write(&comment.text, comment.line, true);
+            self.after_comment = true;
+            self.next_comment += 1;
+            let next_line = self.comment_before(line, position).map(|next| next.line).unwrap_or(line);
+            self.gap = if comment.text.starts_with("//") || next_line > self.last_line { Gap::Newline } else { Gap::Space };
+        }
+    }
+    fn newline(&mut self)
+    {
+        self.gap = Gap::Newline;
+        self.line_requested = true;
+    }
+    // whether writing two tokens without a space between them still lexes as the same two tokens
+    fn can_touch(&mut self, a : &str, b : &str) -> bool
+    {
+        let key = (a.to_string(), b.to_string());
+        if let Some(answer) = self.touch_cache.get(&key)
+        {
+            return *answer;
+        }
+        let mut diagnostics = Vec::new();
+        let answer = match self.parser.tokenize_with_recovery(&format!("{}{}", a, b), &mut diagnostics)
+        {
+            Ok(tokens) => tokens.len() >= 2 && tokens[0].text == a && tokens[1].text == b,
+            Err(_) => false
+        };
+        self.touch_cache.insert(key, answer);
+        answer
+    }
+    fn format_token(&mut self, tokens : &[Token], i : usize) -> Result<(), String>
+    {
+        let cur = &tokens[i];
+        let prev = if i > 0 { tokens.get(i-1) } else { None };
+        
+        self.write_trailing_comments(cur.line, cur.position);
+        
+        let is_block_brace = cur.is_block_brace();
+        let opening = is_block_brace && cur.text == "{";
+        let closing = is_block_brace && cur.text == "}";
+        let in_switch = cur.parent() == "switch";
+        // lambdas with empty bodies are written as [](){}
+        let inline_open = opening && cur.parent() == "lambda" && tokens.get(i+1).map(|next| next.text == "}" && self.comment_before(next.line, next.position).is_none()).unwrap_or(false);
+        let inline_close = closing && self.inline_open;
+        
+        if closing && !inline_close
+        {
+            self.write_own_line_comments(cur.line, cur.position);
+            if !in_switch
+            {
+                self.indent = self.indent.checked_sub(1).ok_or_else(|| format!("internal error: unbalanced block braces while formatting line {}", cur.line))?;
+            }
+            self.newline();
+        }
+        
+        // the brackets of collections written one element per line
+        let multiline = cur.path.last().filter(|parent| COLLECTIONS.contains(&parent.name) && self.multiline.contains(&parent.id)).map(|parent| (parent.first, parent.last));
+        if let Some((false, true)) = multiline
+        {
+            self.write_own_line_comments(cur.line, cur.position);
+            self.indent = self.indent.checked_sub(1).ok_or_else(|| format!("internal error: unbalanced brackets while formatting line {}", cur.line))?;
+            self.newline();
+        }
+        
+        for (k, ancestor) in cur.path.iter().enumerate()
+        {
+            if !ancestor.first
+            {
+                continue;
+            }
+            if k > 0 && LINE_LISTS.contains(&cur.path[k-1].name)
+            {
+                self.newline();
+            }
+            if ancestor.name == "nakedblock"
+            {
+                self.indent += 1;
+            }
+            else if ancestor.name == "block"
+            {
+                let body = cur.path.get(k+2).map(|x| x.name).unwrap_or("");
+                let else_if = body == "condition" && prev.map(|prev| prev.text == "else").unwrap_or(false);
+                let bare = body != "statementlist" && !else_if;
+                if bare
+                {
+                    self.indent += 1;
+                    self.newline();
+                }
+                self.blocks.push(bare);
+            }
+        }
+        if (cur.text == "else" && cur.parent() == "ifcondition") || (opening && !inline_open)
+        {
+            self.newline();
+        }
+        
+        self.continued = !self.line_requested && (self.gap == Gap::Newline || self.comment_before(cur.line, cur.position).is_some());
+        self.write_own_line_comments(cur.line, cur.position);
+        
+        if self.after_comment && self.gap == Gap::Space && hugs_left(cur.text)
+        {
+            self.gap = Gap::None;
+        }
+        else if self.gap == Gap::None
+        {
+            if let Some(prev) = prev
+            {
+                let touching = spacing(prev, cur) == Gap::None;
+                let literal = prev.is_interp_literal() || cur.is_interp_literal();
+                if !touching || (!literal && !self.can_touch(prev.text, cur.text))
+                {
+                    self.gap = Gap::Space;
+                }
+            }
+        }
+        self.write(cur.text, cur.line, !closing);
+        self.line_requested = false;
+        self.continued = false;
+        
+        if opening && !inline_open
+        {
+            if !in_switch
+            {
+                self.indent += 1;
+            }
+            self.after_open = true;
+            self.newline();
+        }
+        self.inline_open = inline_open;
+        match multiline
+        {
+            Some((true, false)) =>
+            {
+                self.indent += 1;
+                self.after_open = true;
+                self.newline();
+            }
+            // list separators, which aren't the first or last token of anything
+            Some((false, false)) => self.newline(),
+            _ => {}
+        }
+        // trailing commas
+        if cur.parent() == "unusedcomma" && cur.path.iter().rev().nth(1).map(|collection| self.multiline.contains(&collection.id)).unwrap_or(false)
+        {
+            self.newline();
+        }
+        
+        for ancestor in cur.path.iter().rev()
+        {
+            if !ancestor.last
+            {
+                continue;
+            }
+            let bare = match ancestor.name
+            {
+                "nakedblock" => true,
+                "block" => self.blocks.pop().ok_or_else(|| format!("internal error: unbalanced blocks while formatting line {}", cur.line))?,
+                _ => false
+            };
+            if bare
+            {
+                self.indent = self.indent.checked_sub(1).ok_or_else(|| format!("internal error: unbalanced blocks while formatting line {}", cur.line))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// lists the leaves of a parse tree, each with the ids and names of the nodes it's inside of
+fn collect_leaves<'a>(ast : &'a ASTNode, path : &mut Vec<(usize, &'a str)>, next_id : &mut usize, leaves : &mut Vec<(&'a ASTNode, Vec<(usize, &'a str)>)>)
+{
+    if ast.isparent
+    {
+        path.push((*next_id, &ast.text));
+        *next_id += 1;
+        for child in &ast.children
+        {
+            collect_leaves(child, path, next_id, leaves);
+        }
+        path.pop();
+    }
+    else
+    {
+        leaves.push((ast, path.clone()));
+    }
+}
+
+impl Parser {
+    /// Rewrites program text in the canonical style: Allman braces, four-space indentation, and consistent spacing around operators.
+    ///
+    /// Comments and single blank lines between statements are kept. Formatting is idempotent, and the formatted program parses to the same AST as the original.
+    ///
+    /// Returns Err if the program has syntax errors, listing all of them.
+    pub fn format(&mut self, text : &str) -> Result<String, String>
+    {
+        let mut comments = Vec::new();
+        let lex_tokens = self.tokenize_with_comments(text, &mut comments)?;
+        let ast = match self.parse_raw(&lex_tokens)?
+        {
+            Some(ast) => ast,
+            None =>
+            {
+                let (_, diagnostics) = self.parse_with_recovery(text)?;
+                let messages : Vec<String> = diagnostics.iter().map(|diagnostic| format!("{} on line {}, position {}", diagnostic.message, diagnostic.line, diagnostic.position)).collect();
+                return Err(messages.join("\n"));
+            }
+        };
+        
+        let mut leaves = Vec::new();
+        collect_leaves(&ast, &mut Vec::new(), &mut 0, &mut leaves);
+        
+        // match leaves up with tokens; tokens without a leaf are list separators, which belong to the innermost node around both of their neighbors
+        let mut paths : Vec<Option<Vec<(usize, &str)>>> = Vec::new();
+        let mut leaf_iter = leaves.iter().peekable();
+        for token in &lex_tokens
+        {
+            match leaf_iter.peek()
+            {
+                Some((leaf, path)) if leaf.line == token.line && leaf.position == token.position =>
+                {
+                    paths.push(Some(path.clone()));
+                    leaf_iter.next();
+                }
+                _ => paths.push(None)
+            }
+        }
+        if leaf_iter.next().is_some()
+        {
+            return Err("internal error: parse tree does not match the program's tokens".to_string());
+        }
+        let mut first_leaf = HashMap::new();
+        let mut last_leaf = HashMap::new();
+        for (i, path) in paths.iter().enumerate()
+        {
+            for (id, _) in path.iter().flatten()
+            {
+                first_leaf.entry(*id).or_insert(i);
+                last_leaf.insert(*id, i);
+            }
+        }
+        let mut tokens = Vec::new();
+        for (i, token) in lex_tokens.iter().enumerate()
+        {
+            let path = match &paths[i]
+            {
+                Some(path) => path.iter().map(|(id, name)| Ancestor{id : *id, name, first : first_leaf.get(id) == Some(&i), last : last_leaf.get(id) == Some(&i)}).collect(),
+                None =>
+                {
+                    let before = paths[..i].iter().rev().flatten().next();
+                    let after = paths[i+1..].iter().flatten().next();
+                    match (before, after)
+                    {
+                        (Some(before), Some(after)) => before.iter().zip(after.iter()).take_while(|(a, b)| a.0 == b.0).map(|(a, _)| Ancestor{id : a.0, name : a.1, first : false, last : false}).collect(),
+                        _ => Vec::new()
+                    }
+                }
+            };
+            tokens.push(Token{text : &token.text, line : token.line, position : token.position, path});
+        }
+        
+        let multiline = first_leaf.iter().filter(|(id, first)| lex_tokens[**first].line != lex_tokens[last_leaf[*id]].line).map(|(id, _)| *id).collect();
+        
+        let mut formatter = Formatter{parser : self, comments, next_comment : 0, out : String::new(), indent : 0, gap : Gap::None, last_line : 1, after_open : false, inline_open : false, after_comment : false, line_requested : false, continued : false, blocks : Vec::new(), multiline, touch_cache : HashMap::new()};
+        for i in 0..tokens.len()
+        {
+            formatter.format_token(&tokens, i)?;
+        }
+        formatter.write_trailing_comments(usize::MAX, 0);
+        formatter.write_own_line_comments(usize::MAX, 0);
+        if !formatter.out.is_empty()
+        {
+            formatter.out.push('\n');
+        }
+        Ok(formatter.out)
+    }
+}
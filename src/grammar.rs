@@ -43,7 +43,7 @@ impl GrammarForm
         let tokens : Vec<&str> = line.split(' ').collect();
         for token in &tokens
         {
-            if *token == ""
+            if token.is_empty()
             {
                 continue;
             }
@@ -54,10 +54,9 @@ impl GrammarForm
             else if re.is_exact(r"%.+%$", token)
             {
                 let bare = slice(token, 1, -1);
-                if intoken && !parser.regex_set.contains(&bare)
+                if intoken
                 {
                     parser.regex_set.insert(bare.clone());
-                    parser.regex_list.push(bare.clone());
                 }
                 ret.tokens.push(GrammarToken::Regex(bare));
             }
@@ -1,8 +1,11 @@
-use std::collections::{HashMap, HashSet, BTreeMap, BTreeSet};
-use std::rc::Rc;
-use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, BTreeMap, BTreeSet, VecDeque};
+use std::sync::Arc;
+#[cfg(not(feature = "thread_safe"))]
+use std::{rc::Rc, cell::RefCell};
+#[cfg(feature = "thread_safe")]
+use std::sync::Mutex;
 
-use super::{strings::*, ast::*, parser::*, bytecode::*, compiler::*};
+use super::{strings::*, ast::*, parser::*, bytecode::*, compiler::*, program::*};
 
 mod bindings;
 mod internal;
@@ -11,8 +14,23 @@ mod manipulation;
 mod jumping;
 pub (crate) mod types;
 mod variableaccess;
+mod convert;
+mod native;
+mod registry;
+mod reflection;
+mod queries;
+mod lifecycle;
+mod messages;
+mod timers;
+mod states;
 
 pub use self::types::*;
+pub use self::convert::*;
+pub use self::native::*;
+pub use self::registry::{BindingKind, BindingInfo};
+use self::registry::*;
+pub use self::bindings::VecHelpers;
+pub use self::lifecycle::InstanceSnapshot;
 use variableaccess::ValueLoc;
 
 /// Returned by the step() method of an interpreter.
@@ -22,18 +40,65 @@ pub fn default_step_result() -> StepResult
     Ok(())
 }
 /// Type signature of functions to be registered as bindings.
+#[cfg(not(feature = "thread_safe"))]
 pub type Binding = dyn FnMut(&mut Interpreter, Vec<Value>) -> Result<Value, String>;
+/// Type signature of functions to be registered as bindings.
+#[cfg(feature = "thread_safe")]
+pub type Binding = dyn FnMut(&mut Interpreter, Vec<Value>) -> Result<Value, String> + Send;
 /// For trivial bindings.
 pub type TrivialBinding = fn(&mut Interpreter, Vec<Value>) -> Result<Value, String>;
 /// For simple bindings.
+#[cfg(not(feature = "thread_safe"))]
 pub type SimpleBinding = dyn FnMut(Vec<Value>) -> Result<Value, String>;
+/// For simple bindings.
+#[cfg(feature = "thread_safe")]
+pub type SimpleBinding = dyn FnMut(Vec<Value>) -> Result<Value, String> + Send;
 /// For trivial simple bindings.
 pub type TrivialSimpleBinding = fn(Vec<Value>) -> Result<Value, String>;
 /// For arrow bindings.
+#[cfg(not(feature = "thread_safe"))]
 pub type ArrowBinding = dyn FnMut(ValueLoc, Vec<Value>) -> Result<Value, String>;
+/// For arrow bindings.
+#[cfg(feature = "thread_safe")]
+pub type ArrowBinding = dyn FnMut(ValueLoc, Vec<Value>) -> Result<Value, String> + Send;
 /// For trivial arrow bindings.
 pub type TrivialArrowBinding = fn(ValueLoc, Vec<Value>) -> Result<Value, String>;
 
+/// Shared, mutable handle that non-trivial bindings are passed in as.
+///
+/// This is Rc<RefCell<...>> by default. With the thread_safe feature, it's Arc<Mutex<...>> instead, bindings must be Send, and the interpreter itself is Send.
+#[cfg(not(feature = "thread_safe"))]
+pub type BindingCell<T> = Rc<RefCell<T>>;
+/// Shared, mutable handle that non-trivial bindings are passed in as.
+///
+/// This is Rc<RefCell<...>> by default. With the thread_safe feature, it's Arc<Mutex<...>> instead, bindings must be Send, and the interpreter itself is Send.
+#[cfg(feature = "thread_safe")]
+pub type BindingCell<T> = Arc<Mutex<T>>;
+
+/// Wraps a binding closure up for insert_binding(), insert_simple_binding(), or insert_arrow_binding().
+#[cfg(not(feature = "thread_safe"))]
+pub fn binding_cell<T>(binding : T) -> BindingCell<T>
+{
+    Rc::new(RefCell::new(binding))
+}
+/// Wraps a binding closure up for insert_binding(), insert_simple_binding(), or insert_arrow_binding().
+#[cfg(feature = "thread_safe")]
+pub fn binding_cell<T>(binding : T) -> BindingCell<T>
+{
+    Arc::new(Mutex::new(binding))
+}
+
+#[cfg(not(feature = "thread_safe"))]
+fn borrow_binding<T : ?Sized>(binding : &BindingCell<T>) -> Result<std::cell::RefMut<'_, T>, String>
+{
+    binding.try_borrow_mut().or_else(|_| plainerr("error: tried to borrow internal function while it was borrowed elsewhere"))
+}
+#[cfg(feature = "thread_safe")]
+fn borrow_binding<T : ?Sized>(binding : &BindingCell<T>) -> Result<std::sync::MutexGuard<'_, T>, String>
+{
+    binding.try_lock().or_else(|_| plainerr("error: tried to borrow internal function while it was borrowed elsewhere"))
+}
+
 fn minierr(mystr : &'static str) -> String
 {
     mystr.to_string()
@@ -49,28 +114,33 @@ fn fat_vec<T>() -> Vec<T>
 
 // global interpreter data
 pub struct GlobalState {
-    instance_id: usize,
-    pub (crate) instances: BTreeMap<usize, Instance>,
-    pub (crate) instances_by_type: Box<BTreeMap<usize, BTreeSet<usize>>>,
+    instance_generations: Vec<u32>, // of each instance slot; see instance_handle()
+    free_instance_slots: VecDeque<usize>, // slots of removed instances, reused oldest first
+    pub (crate) instances_created: u64, // counts up, to order instances by when they were created
+    pub (crate) instances: BTreeMap<u64, Instance>,
+    pub (crate) instances_by_type: Box<BTreeMap<usize, BTreeMap<u64, u64>>>, // keyed by when each instance was created
+    pub (crate) instances_by_tag: BTreeMap<usize, BTreeMap<u64, u64>>, // tag name to the instances that have it, keyed the same way
+    pub (crate) dead_instances: Vec<u64>, // killed, but not removed yet; see Interpreter::collect_dead_instances()
+    pub (crate) messages: VecDeque<Message>, // see Interpreter::pump_messages()
+    pub (crate) timers: BTreeMap<(u64, u64), Timer>, // keyed by the tick they fire on, then by the order they were scheduled in; see Interpreter::tick()
+    pub (crate) current_tick: u64,
+    pub (crate) changing_state: bool, // see Interpreter::instance_goto_state()
+    pub (crate) pending_state_changes: VecDeque<(u64, usize)>, // instance and state
+    pub (crate) timers_scheduled: u64,
     
-    pub (crate) objects: Box<BTreeMap<usize, ObjSpec>>,
+    pub (crate) objects: Arc<BTreeMap<usize, ObjSpec>>,
     pub (crate) variables: BTreeMap<usize, Value>, // accessed as global.varname
     pub (crate) barevariables: BTreeMap<usize, Value>, // accessed as varname
     pub (crate) functions: BTreeMap<usize, Value>, // accessed as funcname
+    pub (crate) object_constants: BTreeMap<(usize, usize), Value>, // accessed as Objname.CONSTNAME, keyed by object and name
     
-    // TODO: same map
-    pub (crate) bindings: Box<BTreeMap<usize, Rc<RefCell<Binding>>>>,
-    pub (crate) trivial_bindings: Box<BTreeMap<usize, TrivialBinding>>,
-    pub (crate) simple_bindings: Box<BTreeMap<usize, Rc<RefCell<SimpleBinding>>>>,
-    pub (crate) trivial_simple_bindings: Box<BTreeMap<usize, TrivialSimpleBinding>>,
+    pub (crate) bindings: Box<BindingRegistry>,
     
-    // TODO: same map
-    pub (crate) arrow_bindings: Box<BTreeMap<usize, Rc<RefCell<ArrowBinding>>>>,
-    pub (crate) trivial_arrow_bindings: Box<BTreeMap<usize, TrivialArrowBinding>>,
+    pub (crate) native_classes: Box<BTreeMap<u64, NativeClass>>, // by Custom discriminator
     
-    string_index: usize,
-    string_table : Box<HashMap<String, usize>>,
-    string_table_reverse : Box<BTreeMap<usize, String>>,
+    pub (crate) dynamic_instance_variables: bool,
+    
+    strings : StringTable,
     
     parser: Box<Parser>,
 }
@@ -79,48 +149,68 @@ impl GlobalState {
     fn new(parser : Parser) -> GlobalState
     {
         GlobalState {
-            instance_id : 1,
+            // slot 0 is never used, so that no handle is 0
+            instance_generations : vec!(0),
+            free_instance_slots : VecDeque::new(),
+            instances_created : 0,
             instances : BTreeMap::new(),
             instances_by_type : Box::new(BTreeMap::new()),
+            instances_by_tag : BTreeMap::new(),
+            dead_instances : Vec::new(),
+            messages : VecDeque::new(),
+            timers : BTreeMap::new(),
+            current_tick : 0,
+            changing_state : false,
+            pending_state_changes : VecDeque::new(),
+            timers_scheduled : 0,
             
-            objects : Box::new(BTreeMap::new()),
+            objects : Arc::new(BTreeMap::new()),
             variables : BTreeMap::new(),
             barevariables : BTreeMap::new(),
             functions : BTreeMap::new(),
+            object_constants : BTreeMap::new(),
+            
+            bindings : Box::new(BindingRegistry::default()),
+            native_classes : Box::new(BTreeMap::new()),
             
-            bindings : Box::new(BTreeMap::new()),
-            trivial_bindings : Box::new(BTreeMap::new()),
-            simple_bindings : Box::new(BTreeMap::new()),
-            trivial_simple_bindings : Box::new(BTreeMap::new()),
-            arrow_bindings : Box::new(BTreeMap::new()),
-            trivial_arrow_bindings : Box::new(BTreeMap::new()),
+            dynamic_instance_variables : false,
             
             parser : Box::new(parser),
             
-            string_index : 1,
-            string_table : Box::new(HashMap::new()),
-            string_table_reverse : Box::new(BTreeMap::new()),
+            strings : StringTable::new(),
         }
     }
-    #[allow(clippy::ptr_arg)]
-    pub (crate) fn get_string_index(&mut self, string : &String) -> usize
+    /// Makes a global state for compiling a standalone program. It knows about the same bindings and strings as this one, but nothing else.
+    fn new_for_compilation(&self) -> GlobalState
     {
-        if let Some(index) = self.string_table.get(string)
-        {
-            *index
-        }
-        else
+        let mut global = GlobalState::new(Parser::default());
+        global.bindings = self.bindings.clone();
+        global.strings = self.strings.clone();
+        global
+    }
+    /// Switches over to the given string table, moving bindings to the indexes their names have in it.
+    fn adopt_string_table(&mut self, mut strings : StringTable)
+    {
+        let old = std::mem::take(&mut self.bindings.entries);
+        for ((kind, index), entry) in old
         {
-            let index = self.string_index;
-            self.string_index += 1;
-            self.string_table.insert(string.clone(), index);
-            self.string_table_reverse.insert(index, string.clone());
-            index
+            let name = self.get_string(index);
+            self.bindings.entries.insert((kind, strings.get_index(&name)), entry);
         }
+        self.strings = strings;
+    }
+    #[allow(clippy::ptr_arg)]
+    pub (crate) fn get_string_index(&mut self, string : &String) -> usize
+    {
+        self.strings.get_index(string)
+    }
+    pub (crate) fn find_string_index(&self, string : &str) -> Option<usize>
+    {
+        self.strings.find_index(string)
     }
     pub (crate) fn get_string(&self, index : usize) -> String
     {
-        if let Some(string) = self.string_table_reverse.get(&index)
+        if let Some(string) = self.strings.get_string(index)
         {
             return string.clone();
         }
@@ -165,16 +255,6 @@ impl Interpreter {
     /// Creates a new interpreter 
     pub fn new(parser : Parser) -> Interpreter
     {
-        println!("- sizeof Value {}", std::mem::size_of::<Value>());
-        println!("- sizeof Variable {}", std::mem::size_of::<Variable>());
-        println!("- sizeof FuncSpec {}", std::mem::size_of::<FuncSpec>());
-        println!("- sizeof Frame {}", std::mem::size_of::<Frame>());
-        println!("- sizeof Vec<StackValue> {}", std::mem::size_of::<Vec<StackValue>>());
-        println!("- sizeof HashMap<HashableValue, Value> {}", std::mem::size_of::<HashMap<HashableValue, Value>>());
-        println!("- sizeof NonArrayVariable {}", std::mem::size_of::<NonArrayVariable>());
-        println!("- sizeof Interpreter {}", std::mem::size_of::<Interpreter>());
-        println!("- sizeof GlobalState {}", std::mem::size_of::<GlobalState>());
-        simulation::build_opfunc_table();
         Interpreter {
             top_frame : Frame::new_root(&Code::new()),
             frames : fat_vec(),
@@ -217,7 +297,7 @@ impl Interpreter {
     {
         let program_lines : Vec<String> = text.lines().map(|x| x.to_string()).collect();
         
-        let tokens = self.global.parser.tokenize(&program_lines, false)?;
+        let tokens = self.global.parser.tokenize(text, false)?;
         
         let ast = self.global.parser.parse_program(&tokens, &program_lines, false)?.ok_or_else(|| "failed to parse program".to_string())?;
         
@@ -225,33 +305,118 @@ impl Interpreter {
         self.restart(&code);
         Ok(code)
     }
+    /// Compiles program text into a Program that can be loaded into any number of interpreters with load_program().
+    ///
+    /// The program is compiled against this interpreter's bindings, but not its objects or global variables, and compiling it does not modify them.
+    pub fn compile_program(&mut self, text: &str) -> Result<Program, String>
+    {
+        let program_lines : Vec<String> = text.lines().map(|x| x.to_string()).collect();
+        
+        let tokens = self.global.parser.tokenize(text, false)?;
+        
+        let ast = self.global.parser.parse_program(&tokens, &program_lines, false)?.ok_or_else(|| "failed to parse program".to_string())?;
+        
+        let mut global = self.global.new_for_compilation();
+        let code = compile_bytecode(&ast, &mut global)?;
+        Ok(Program {
+            code,
+            strings : global.strings.freeze(),
+            objects : global.objects,
+            functions : Arc::new(global.functions),
+            variables : Arc::new(global.variables.keys().cloned().collect()),
+            barevariables : Arc::new(global.barevariables.keys().cloned().collect()),
+        })
+    }
+    /// Analyzes program text for tooling like editor integrations, without running it or changing this interpreter's state.
+    ///
+    /// Unlike compile_program(), this doesn't stop at the first error: syntax errors are recovered from like in Parser::parse_with_recovery(), and statements that fail to compile are skipped.
+    /// Compile errors are only reported if there aren't any syntax errors, because skipped statements would cause a lot of spurious ones.
+    /// Along with them, the compiler reports the likely mistakes described in check_program(), including warnings.
+    pub fn analyze_program(&mut self, text : &str) -> Result<ProgramAnalysis, String>
+    {
+        let (ast, mut diagnostics) = self.global.parser.parse_with_recovery(text)?;
+        
+        let mut global = self.global.new_for_compilation();
+        let (symbols, compile_diagnostics) = analyze_bytecode(&ast, &mut global);
+        if diagnostics.is_empty()
+        {
+            diagnostics = compile_diagnostics;
+        }
+        
+        Ok(ProgramAnalysis { ast, diagnostics, symbols })
+    }
+    /// Checks program text for likely mistakes without running it, and without changing this interpreter's state. Returns the same diagnostics as analyze_program(), sorted by where they are.
+    ///
+    /// Besides compile errors, reports calls with the wrong number of arguments (to functions defined in the program, and to bindings whose arity is known; see set_binding_arity()),
+    /// break and continue outside of loops, yield in functions that aren't generators, unused and shadowed variables, and unreachable code after return, break, or continue.
+    ///
+    /// Like compile_program(), only the interpreter's bindings are taken into account, not its objects or global variables.
+    /// If there are syntax errors, those are reported instead.
+    pub fn check_program(&mut self, text : &str) -> Result<Vec<Diagnostic>, String>
+    {
+        Ok(self.analyze_program(text)?.diagnostics)
+    }
+    /// Loads a compiled Program into the interpreter and restarts it at the start of the program.
+    ///
+    /// Replaces all objects, instances, global variables, and global functions with the program's, even if code was still running.
+    ///
+    /// Does not unload the parser that was loaded into the interpreter upon creation.
+    ///
+    /// Does not unload internal function bindings.
+    pub fn load_program(&mut self, program : &Program)
+    {
+        self.clear_global_state();
+        self.global.adopt_string_table(StringTable::from_shared(&program.strings));
+        
+        self.global.objects = Arc::clone(&program.objects);
+        for object_id in program.objects.keys()
+        {
+            self.global.instances_by_type.insert(*object_id, BTreeMap::new());
+        }
+        self.global.functions = (*program.functions).clone();
+        for index in program.variables.iter()
+        {
+            self.global.insert_global(*index);
+        }
+        for index in program.barevariables.iter()
+        {
+            self.global.insert_bare_global(*index);
+        }
+        
+        self.restart(&program.code);
+    }
     /// Clears global state (objects/instances).
     /// 
     /// This GRACELESSLY deletes all objects and instances, even if they contained code that has not yet finished running or needs special destruction.
     /// 
     /// Does not unload the parser that was loaded into the interpreter upon creation.
     /// 
-    /// Does not unload internal function bindings.
+    /// Does not unload internal function bindings or native classes.
     /// 
     /// Does not reset global state (objects/instances).
     pub fn clear_global_state(&mut self)
     {
         let mut parser = Parser::default();
         std::mem::swap(&mut parser, &mut self.global.parser);
-        self.global = GlobalState::new(parser);
+        let mut global = GlobalState::new(parser);
+        std::mem::swap(&mut global.bindings, &mut self.global.bindings);
+        std::mem::swap(&mut global.native_classes, &mut self.global.native_classes);
+        std::mem::swap(&mut global.strings, &mut self.global.strings);
+        global.dynamic_instance_variables = self.global.dynamic_instance_variables;
+        self.global = global;
     }
     #[inline]
     fn step_internal(&mut self) -> StepResult
     {
         #[cfg(not(feature = "track_op_performance"))]
         {
-            unsafe { simulation::OPTABLE[self.pull_single_from_code() as usize](self) }
+            simulation::OPTABLE[self.pull_single_from_code() as usize](self)
         }
         #[cfg(feature = "track_op_performance")]
         {
             let op = self.pull_single_from_code();
             
-            let ret = unsafe { simulation::OPTABLE[op as usize](self) };
+            let ret = simulation::OPTABLE[op as usize](self);
             
             unsafe
             {
@@ -327,7 +492,7 @@ impl Interpreter {
             {
                 self.last_error = Some(format!("{}\n(unknown or missing context - code probably desynced - location {} - map {:?})", err, pc, self.top_frame.code.debug));
             }
-            return Err(err);
+            Err(err)
         }
         else
         {
@@ -343,12 +508,12 @@ impl Interpreter {
         }
         code.cached = true;
         
-        let code_data = Rc::get_mut(&mut code.code).unwrap();
+        let code_data = Arc::make_mut(&mut code.code);
         
         for addr in code.booklet.iter()
         {
             let op = &mut code_data[*addr];
-            let opfunc = unsafe { simulation::OPTABLE[*op as usize] };
+            let opfunc = simulation::OPTABLE[*op as usize];
             let ptr = opfunc as *const OpFunc;
             *op = ptr as u64;
         }
@@ -356,7 +521,7 @@ impl Interpreter {
     
     pub fn step_cached(&mut self) -> StepResult
     {
-        let f : OpFunc = unsafe { std::mem::transmute(self.top_frame.code[self.top_frame.pc] as *const OpFunc) };
+        let f : OpFunc = unsafe { std::mem::transmute::<usize, OpFunc>(self.top_frame.code[self.top_frame.pc] as usize) };
         self.top_frame.pc += 1;
         
         #[cfg(not(feature = "track_op_performance"))]
@@ -366,7 +531,7 @@ impl Interpreter {
         #[cfg(feature = "track_op_performance")]
         {
             let op = f as *const OpFunc as usize;
-            let op = * unsafe { simulation::REVERSE_OPTABLE.as_ref().unwrap().get(&op).unwrap() };
+            let op = *simulation::reverse_optable().get(&op).unwrap();
             
             let ret = f(self);
             
@@ -387,9 +552,9 @@ impl Interpreter {
         if !self.top_frame.code.cached
         {
             Interpreter::prepare_cache(&mut self.top_frame.code);
-            for spec in self.global.objects.iter_mut()
+            for spec in Arc::make_mut(&mut self.global.objects).iter_mut()
             {
-                for func in spec.1.functions.iter_mut()
+                for func in spec.1.functions.iter_mut().chain(spec.1.states.values_mut().flat_map(|functions| functions.iter_mut()))
                 {
                     Interpreter::prepare_cache(&mut func.1.code);
                 }
@@ -430,7 +595,7 @@ impl Interpreter {
             {
                 self.last_error = Some(format!("{}\n(unknown or missing context - code probably desynced - location {} - map {:?})", err, pc, self.top_frame.code.debug));
             }
-            return Err(err);
+            Err(err)
         }
         else
         {
@@ -6,8 +6,6 @@ use super::variableaccess::ValueLoc;
 use super::types::ops::{float_booly, bool_floaty};
 
 pub trait VecHelpers<Value> {
-    /// Slow; use extract() instead
-    fn pop_front(&mut self) -> Option<Value>;
     /// If the given element exists, extracts it by value, replacing what was there with Value::default()
     /// Otherwise returns None
     fn extract(&mut self, index : usize) -> Option<Value>;
@@ -15,13 +13,13 @@ pub trait VecHelpers<Value> {
     fn extract_num(&mut self, index : usize) -> Result<f64, String>;
     /// Same as extract(), but returns Err(...message that the error should be unreachable...) on out-of-range.
     fn expect_extract(&mut self, index : usize) -> Result<Value, String>;
+    /// Returns Err(...wrong number of arguments to funcname()...) unless there are exactly count elements.
+    fn expect_count(&self, funcname : &str, count : usize) -> Result<(), String>;
+    /// Extracts and converts an argument, returning Err(...argument to funcname() must be...) if it's missing or of the wrong type.
+    fn extract_arg<T : FromValue>(&mut self, funcname : &str, index : usize) -> Result<T, String>;
 }
 
 impl VecHelpers<Value> for Vec<Value> {
-    fn pop_front(&mut self) -> Option<Value>
-    {
-        if self.is_empty() { None } else { Some(self.remove(0)) }
-    }
     fn extract(&mut self, index : usize) -> Option<Value>
     {
         if index < self.len()
@@ -44,6 +42,29 @@ impl VecHelpers<Value> for Vec<Value> {
     {
         self.extract(index).ok_or_else(|| minierr("internal error: error that should be unreachable in expect_extract"))
     }
+    fn expect_count(&self, funcname : &str, count : usize) -> Result<(), String>
+    {
+        if self.len() != count
+        {
+            return Err(format!("error: wrong number of arguments to {}(); expected {}, got {}", funcname, count, self.len()));
+        }
+        Ok(())
+    }
+    fn extract_arg<T : FromValue>(&mut self, funcname : &str, index : usize) -> Result<T, String>
+    {
+        let val = self.extract(index).ok_or_else(|| format!("error: wrong number of arguments to {}(); expected at least {}, got {}", funcname, index+1, self.len()))?;
+        T::from_value(val).ok_or_else(|| format!("error: {} argument to {}() must be {}", ordinal(index), funcname, T::expected()))
+    }
+}
+
+fn extract_ticks(args : &mut Vec<Value>, funcname : &str) -> Result<u64, String>
+{
+    let ticks : f64 = args.extract_arg(funcname, 2)?;
+    if ticks < 1.0 || ticks.fract() != 0.0 || ticks > u32::MAX as f64
+    {
+        return Err(format!("error: third argument to {}() must be a positive whole number of ticks", funcname));
+    }
+    Ok(ticks as u64)
 }
 
 pub (crate) fn ast_to_dict(ast : &ASTNode) -> Value
@@ -104,64 +125,16 @@ pub (crate) fn dict_to_ast(dict : &HashMap<HashableValue, Value>) -> Result<ASTN
 
 impl Interpreter
 {
-    /// Insert a normal binding that needs access to the interpreter.
-    ///
-    /// The reference-counter wrapping is required to pass functions.
-    ///
-    /// The reference cell wrapping is required to support lambdas that have closure over mutable references, because that closure may indirectly include the interpreter itself. See magmakit for examples.
-    pub fn insert_binding(&mut self, funcname : String, func : Rc<RefCell<Binding>>)
-    {
-        let index = self.get_string_index(&funcname);
-        self.global.trivial_bindings.remove(&index);
-        self.global.simple_bindings.remove(&index);
-        self.global.trivial_simple_bindings.remove(&index);
-        self.global.bindings.insert(index, func);
-    }
-    /// Insert a normal binding that does not need access to the interpreter or indeed any other external state.
-    pub fn insert_trivial_binding(&mut self, funcname : String, func : TrivialBinding)
-    {
-        let index = self.get_string_index(&funcname);
-        self.global.bindings.remove(&index);
-        self.global.simple_bindings.remove(&index);
-        self.global.trivial_simple_bindings.remove(&index);
-        self.global.trivial_bindings.insert(index, func);
-    }
-    /// Insert a normal binding that does not need access to the interpreter.
-    pub fn insert_simple_binding(&mut self, funcname : String, func : Rc<RefCell<SimpleBinding>>)
-    {
-        let index = self.get_string_index(&funcname);
-        self.global.bindings.remove(&index);
-        self.global.trivial_bindings.remove(&index);
-        self.global.trivial_simple_bindings.remove(&index);
-        self.global.simple_bindings.insert(index, func);
-    }
-    /// Insert a normal binding that does not need access to the interpreter or indeed any other external state.
-    pub fn insert_trivial_simple_binding(&mut self, funcname : String, func : TrivialSimpleBinding)
-    {
-        let index = self.get_string_index(&funcname);
-        self.global.bindings.remove(&index);
-        self.global.trivial_bindings.remove(&index);
-        self.global.simple_bindings.remove(&index);
-        self.global.trivial_simple_bindings.insert(index, func);
-    }
-    /// Insert an associated function ("arrow" function) binding.
-    pub fn insert_arrow_binding(&mut self, funcname : String, func : Rc<RefCell<ArrowBinding>>)
-    {
-        let index = self.get_string_index(&funcname);
-        self.global.arrow_bindings.insert(index, func);
-    }
-    pub fn insert_trivial_arrow_binding(&mut self, funcname : String, func : TrivialArrowBinding)
-    {
-        let index = self.get_string_index(&funcname);
-        self.global.trivial_arrow_bindings.insert(index, func);
-    }
     /// Inserts or reinserts the default bindings. These SHOULD be safe, but if you're paranoid or you're making a very restrictive implementation of gammakit, you can feel free not to call this after initializing the interpreter.
+    ///
+    /// print() and printraw() are tagged with the "print" capability, and the parsing and compiling functions with "metaprogramming".
     pub fn insert_default_bindings(&mut self)
     {
         macro_rules! insert { ( $x:expr, $y:ident ) => { self.insert_trivial_binding($x.to_string(), Interpreter::$y); } }
         
         insert!("parse_text"             , sim_func_parse_text              );
         insert!("parse_text_with_grammar", sim_func_parse_text_with_grammar );
+        insert!("unparse_ast"            , sim_func_unparse_ast             );
         
         insert!("compile_text"           , sim_func_compile_text            );
         insert!("compile_ast"            , sim_func_compile_ast             );
@@ -173,10 +146,28 @@ impl Interpreter
         insert!("instance_object"        , sim_func_instance_object         );
         insert!("instance_has_variable"  , sim_func_instance_has_variable   );
         insert!("instance_has_function"  , sim_func_instance_has_function   );
+        insert!("instance_get"           , sim_func_instance_get            );
+        insert!("instance_set"           , sim_func_instance_set            );
+        insert!("instance_variables"     , sim_func_instance_variables      );
+        insert!("instance_find"          , sim_func_instance_find           );
+        insert!("instance_tag"           , sim_func_instance_tag            );
+        insert!("instance_untag"         , sim_func_instance_untag          );
+        insert!("instance_has_tag"       , sim_func_instance_has_tag        );
+        insert!("instances_of"           , sim_func_instances_of            );
+        insert!("instances_tagged"       , sim_func_instances_tagged        );
+        insert!("send"                   , sim_func_send                    );
+        insert!("broadcast"              , sim_func_broadcast               );
+        insert!("alarm_set"              , sim_func_alarm_set               );
+        insert!("alarm_repeat"           , sim_func_alarm_repeat            );
+        insert!("alarm_cancel"           , sim_func_alarm_cancel            );
+        insert!("alarm_get"              , sim_func_alarm_get               );
+        insert!("goto_state"             , sim_func_goto_state              );
+        insert!("instance_state"         , sim_func_instance_state          );
         
         insert!("object_count"           , sim_func_object_count            );
         insert!("object_has_variable"    , sim_func_object_has_variable     );
         insert!("object_has_function"    , sim_func_object_has_function     );
+        insert!("object_functions"       , sim_func_object_functions        );
         
         macro_rules! insert_simple { ( $x:expr, $y:ident ) => { self.insert_trivial_simple_binding($x.to_string(), Interpreter::$y); } }
         
@@ -211,30 +202,31 @@ impl Interpreter
         insert_arrow!("typeof_str"      , sim_subfunc_typeof_str        );
         insert_arrow!("typeof_num"      , sim_subfunc_typeof_num        );
         insert_arrow!("discriminator"   , sim_subfunc_discriminator     );
-    }
-    pub (crate) fn get_binding(&self, name : usize) -> Option<Rc<RefCell<Binding>>>
-    {
-        match_or_none!(self.global.bindings.get(&name), Some(f) => Rc::clone(f))
-    }
-    pub (crate) fn get_trivial_binding(&self, name : usize) -> Option<TrivialBinding>
-    {
-        self.global.trivial_bindings.get(&name).copied()
-    }
-    pub (crate) fn get_simple_binding(&self, name : usize) -> Option<Rc<RefCell<SimpleBinding>>>
-    {
-        match_or_none!(self.global.simple_bindings.get(&name), Some(f) => Rc::clone(f))
-    }
-    pub (crate) fn get_trivial_simple_binding(&self, name : usize) -> Option<TrivialSimpleBinding>
-    {
-        self.global.trivial_simple_bindings.get(&name).copied()
-    }
-    pub (crate) fn get_arrow_binding(&self, name : usize) -> Option<Rc<RefCell<ArrowBinding>>>
-    {
-        match_or_none!(self.global.arrow_bindings.get(&name), Some(f) => Rc::clone(f))
-    }
-    pub (crate) fn get_trivial_arrow_binding(&self, name : usize) -> Option<TrivialArrowBinding>
-    {
-        self.global.trivial_arrow_bindings.get(&name).copied()
+        
+        for name in ["print", "printraw"]
+        {
+            self.set_binding_capability(BindingKind::Function, name, "print");
+        }
+        for name in ["parse_text", "parse_text_with_grammar", "unparse_ast", "compile_text", "compile_ast", "compile_ast_generator"]
+        {
+            self.set_binding_capability(BindingKind::Function, name, "metaprogramming");
+        }
+        // for the static checker; bindings that take a variable number of arguments are left out
+        for (name, arity) in [("parse_text", 1), ("parse_text_with_grammar", 2), ("unparse_ast", 1), ("compile_text", 1), ("compile_ast", 1), ("compile_ast_generator", 1),
+                              ("instance_create", 1), ("instance_exists", 1), ("instance_kill", 1), ("instance_object", 1), ("instance_has_variable", 2), ("instance_has_function", 2),
+                              ("instance_get", 2), ("instance_set", 3), ("instance_variables", 1), ("object_functions", 1),
+                              ("instance_find", 2), ("instance_tag", 2), ("instance_untag", 2), ("instance_has_tag", 2), ("instances_of", 1), ("instances_tagged", 1),
+                              ("send", 3), ("broadcast", 3), ("alarm_set", 3), ("alarm_repeat", 3), ("alarm_cancel", 2), ("alarm_get", 2),
+                              ("goto_state", 1), ("instance_state", 1),
+                              ("object_count", 1), ("object_has_variable", 2), ("object_has_function", 2),
+                              ("string", 1), ("round", 1), ("floor", 1), ("ceil", 1), ("sqrt", 1), ("pow", 2), ("log", 2), ("ln", 1)]
+        {
+            self.set_binding_arity(BindingKind::Function, name, arity);
+        }
+        for (name, arity) in [("len", 0), ("keys", 0), ("slice", 2), ("contains", 1), ("remove", 1), ("push", 1), ("pop", 0), ("replace_char", 2)]
+        {
+            self.set_binding_arity(BindingKind::Arrow, name, arity);
+        }
     }
     pub (crate) fn sim_func_print(mut args : Vec<Value>) -> Result<Value, String>
     {
@@ -254,154 +246,106 @@ impl Interpreter
     }
     pub (crate) fn sim_func_string(args : Vec<Value>) -> Result<Value, String>
     {
-        if args.len() != 1
-        {
-            return Err(format!("error: wrong number of arguments to string(); expected 1, got {}", args.len()));
-        }
+        args.expect_count("string", 1)?;
         Ok(Value::Text(format_val(&args[0]).ok_or_else(|| minierr("error: tried to stringify an unprintable value"))?))
     }
     pub (crate) fn sim_func_round(mut args : Vec<Value>) -> Result<Value, String>
     {
-        let num = args.extract_num(0)?;
+        args.expect_count("round", 1)?;
+        let num : f64 = args.extract_arg("round", 0)?;
         Ok(Value::Number(num.round()))
     }
     pub (crate) fn sim_func_ceil(mut args : Vec<Value>) -> Result<Value, String>
     {
-        let num = args.extract_num(0)?;
+        args.expect_count("ceil", 1)?;
+        let num : f64 = args.extract_arg("ceil", 0)?;
         Ok(Value::Number(num.ceil()))
     }
     pub (crate) fn sim_func_floor(mut args : Vec<Value>) -> Result<Value, String>
     {
-        let num = args.extract_num(0)?;
+        args.expect_count("floor", 1)?;
+        let num : f64 = args.extract_arg("floor", 0)?;
         Ok(Value::Number(num.floor()))
     }
     pub (crate) fn sim_func_sqrt(mut args : Vec<Value>) -> Result<Value, String>
     {
-        let num = args.extract_num(0)?;
+        args.expect_count("sqrt", 1)?;
+        let num : f64 = args.extract_arg("sqrt", 0)?;
         Ok(Value::Number(num.sqrt()))
     }
     pub (crate) fn sim_func_pow(mut args : Vec<Value>) -> Result<Value, String>
     {
-        let num1 = args.extract_num(0)?;
-        let num2 = args.extract_num(1)?;
+        args.expect_count("pow", 2)?;
+        let num1 : f64 = args.extract_arg("pow", 0)?;
+        let num2 : f64 = args.extract_arg("pow", 1)?;
         Ok(Value::Number(num1.powf(num2)))
     }
     pub (crate) fn sim_func_log(mut args : Vec<Value>) -> Result<Value, String>
     {
-        let num1 = args.extract_num(0)?;
-        let num2 = args.extract_num(1)?;
+        args.expect_count("log", 2)?;
+        let num1 : f64 = args.extract_arg("log", 0)?;
+        let num2 : f64 = args.extract_arg("log", 1)?;
         Ok(Value::Number(num1.log(num2)))
     }
     pub (crate) fn sim_func_ln(mut args : Vec<Value>) -> Result<Value, String>
     {
-        let num = args.extract_num(0)?;
+        args.expect_count("ln", 1)?;
+        let num : f64 = args.extract_arg("ln", 0)?;
         Ok(Value::Number(num.ln()))
     }
     pub (crate) fn sim_func_instance_create(&mut self, mut args : Vec<Value>) -> Result<Value, String>
     {
-        if args.len() != 1
-        {
-            return Err(format!("error: wrong number of arguments to instance_create(); expected 1, got {}", args.len()));
-        }
+        args.expect_count("instance_create", 1)?;
         let create_index = self.get_string_index(&"create".to_string());
         let id_index = self.get_string_index(&"id".to_string());
         
-        let object_id = self.vec_pop_front_object(&mut args).ok_or_else(|| minierr("error: first argument to instance_create() must be an object"))?;
+        let ObjectId(object_id) = args.extract_arg("instance_create", 0)?;
         
-        let instance_id = self.global.instance_id as usize;
-        if self.global.instances.len() == !0usize
-        {
-            return plainerr("error: ran out of instance id space");
-        }
         let object = self.global.objects.get(&object_id).ok_or_else(|| format!("error: tried to create instance of non-extant object type {}", object_id))?;
+        let create = object.functions.get(&create_index).cloned();
+        let mut variables = vec!(Value::default(); object.variables.len());
+        let id_slot = *object.variables.get(&id_index).ok_or_else(|| minierr("internal error: object type has no `id` variable"))?;
         
-        let mut variables = BTreeMap::new();
-        variables.insert(id_index, Value::Instance(instance_id));
-        for var in object.variables.keys() // FIXME make this stuff use exact index somehow
-        {
-            if *var != id_index
-            {
-                variables.insert(*var, Value::default());
-            }
-        }
-        self.global.instances.insert(instance_id, Instance { objtype : object_id, ident : instance_id, variables });
+        let instance_id = self.allocate_instance_handle()?;
+        let created = self.global.instances_created;
+        self.global.instances_created += 1;
         
-        if let Some(ref mut instance_list) = self.global.instances_by_type.get_mut(&object_id)
-        {
-            instance_list.insert(instance_id);
-        }
-        else
-        {
-            let mut instance_list = BTreeSet::new();
-            instance_list.insert(instance_id);
-            self.global.instances_by_type.insert(object_id, instance_list);
-        }
+        variables[id_slot] = Value::Instance(instance_id);
+        self.global.instances.insert(instance_id, Instance { objtype : object_id, ident : instance_id, created, variables, dynamic_variables : BTreeMap::new(), tags : BTreeSet::new(), state : None, alarms : BTreeMap::new(), dead : false });
+        self.global.instances_by_type.entry(object_id).or_default().insert(created, instance_id);
         
-        if let Some(function) = object.functions.get(&create_index)
+        if let Some(function) = create
         {
-            let mut mydata = function.clone();
+            let mut mydata = function;
             mydata.forcecontext = instance_id;
             let pseudo_funcvar = Box::new(FuncVal{predefined : None, userdefdata : mydata});
             self.call_function(pseudo_funcvar, Vec::new(), false)?;
         }
         
-        while self.global.instances.contains_key(&self.global.instance_id)
-        {
-            self.global.instance_id += 1;
-        }
-        
         Ok(Value::Instance(instance_id))
     }
     pub (crate) fn sim_func_instance_exists(&mut self, mut args : Vec<Value>) -> Result<Value, String>
     {
-        if args.len() != 1
-        {
-            return Err(format!("error: wrong number of arguments to instance_create(); expected 1, got {}", args.len()));
-        }
+        args.expect_count("instance_exists", 1)?;
         
-        let instance_id = self.vec_pop_front_instance(&mut args).ok_or_else(|| minierr("error: first argument to instance_exists() must be an instance"))?;
+        let InstanceId(instance_id) = args.extract_arg("instance_exists", 0)?;
         
-        Ok(Value::Number(bool_floaty(self.global.instances.contains_key(&instance_id))))
+        Ok(Value::Number(bool_floaty(self.instance_is_alive(instance_id))))
     }
     pub (crate) fn sim_func_instance_kill(&mut self, mut args : Vec<Value>) -> Result<Value, String>
     {
-        if args.len() != 1
-        {
-            return Err(format!("error: wrong number of arguments to instance_create(); expected 1, got {}", args.len()));
-        }
+        args.expect_count("instance_kill", 1)?;
         
-        let destroy_index = self.get_string_index(&"destroy".to_string());
-        let instance_id = self.vec_pop_front_instance(&mut args).ok_or_else(|| minierr("error: first argument to instance_kill() must be an instance"))?;
-        
-        if let Some(inst) = self.global.instances.get(&instance_id)
-        {
-            let object = self.global.objects.get(&inst.objtype).ok_or_else(|| format!("error: tried to kill instance of non-extant object type {}", inst.objtype))?;
-            if let Some(function) = object.functions.get(&destroy_index)
-            {
-                let mut mydata = function.clone();
-                mydata.forcecontext = instance_id;
-                let pseudo_funcvar = Box::new(FuncVal{predefined : None, userdefdata : mydata});
-                self.call_function(pseudo_funcvar, Vec::new(), false)?;
-            }
-        }
-        if let Some(inst) = self.global.instances.remove(&instance_id)
-        {
-            if let Some(ref mut instance_list) = self.global.instances_by_type.get_mut(&inst.objtype)
-            {
-                instance_list.remove(&instance_id);
-            }
-        }
+        let InstanceId(instance_id) = args.extract_arg("instance_kill", 0)?;
+        self.kill_instance(instance_id)?;
         
         Ok(Value::default())
     }
     pub (crate) fn sim_func_instance_object(&mut self, mut args : Vec<Value>) -> Result<Value, String>
     {
-        if args.len() != 1
-        {
-            return Err(format!("error: wrong number of arguments to instance_object(); expected 1, got {}", args.len()));
-        }
+        args.expect_count("instance_object", 1)?;
         
-        let instance_id = self.vec_pop_front_instance(&mut args).ok_or_else(|| minierr("error: first argument to instance_kill() must be an instance"))?;
+        let InstanceId(instance_id) = args.extract_arg("instance_object", 0)?;
         
         if let Some(inst) = self.global.instances.get(&instance_id)
         {
@@ -412,62 +356,192 @@ impl Interpreter
     }
     pub (crate) fn sim_func_instance_has_variable(&mut self, mut args : Vec<Value>) -> Result<Value, String>
     {
-        if args.len() != 2
-        {
-            return Err(format!("error: wrong number of arguments to instance_has_variable(); expected 2, got {}", args.len()));
-        }
+        args.expect_count("instance_has_variable", 2)?;
         
-        let instance_id = self.vec_pop_front_instance(&mut args).ok_or_else(|| minierr("error: first argument to instance_has_variable() must be an instance"))?;
-        let text = self.vec_pop_front_text(&mut args).ok_or_else(|| minierr("error: second argument to instance_has_variable() must be a string"))?;
+        let InstanceId(instance_id) = args.extract_arg("instance_has_variable", 0)?;
+        let text : String = args.extract_arg("instance_has_variable", 1)?;
         let text_id = self.get_string_index(&text);
         
         if let Some(inst) = self.global.instances.get(&instance_id)
         {
-            return Ok(Value::Number(bool_floaty(inst.variables.contains_key(&text_id))));
+            let object = self.global.objects.get(&inst.objtype).ok_or_else(|| format!("error: tried to use instance of non-extant object type {}", inst.objtype))?;
+            return Ok(Value::Number(bool_floaty(object.variables.contains_key(&text_id) || inst.dynamic_variables.contains_key(&text_id))));
         }
         
         Ok(Value::default())
     }
     pub (crate) fn sim_func_instance_has_function(&mut self, mut args : Vec<Value>) -> Result<Value, String>
     {
-        if args.len() != 2
-        {
-            return Err(format!("error: wrong number of arguments to instance_has_function(); expected 2, got {}", args.len()));
-        }
+        args.expect_count("instance_has_function", 2)?;
         
-        let instance_id = self.vec_pop_front_instance(&mut args).ok_or_else(|| minierr("error: first argument to instance_has_function() must be an instance"))?;
-        let text = self.vec_pop_front_text(&mut args).ok_or_else(|| minierr("error: second argument to instance_has_function() must be a string"))?;
+        let InstanceId(instance_id) = args.extract_arg("instance_has_function", 0)?;
+        let text : String = args.extract_arg("instance_has_function", 1)?;
         let text_id = self.get_string_index(&text);
         
         if let Some(inst) = self.global.instances.get(&instance_id)
         {
             let object = self.global.objects.get(&inst.objtype).ok_or_else(|| format!("error: tried to use instance of non-extant object type {}", inst.objtype))?;
-            return Ok(Value::Number(bool_floaty(object.functions.contains_key(&text_id))));
+            return Ok(Value::Number(bool_floaty(object.method(inst.state, text_id).is_some())));
         }
         
         Ok(Value::default())
     }
-    pub (crate) fn sim_func_object_count(&mut self, mut args : Vec<Value>) -> Result<Value, String>
+    pub (crate) fn sim_func_instance_get(&mut self, mut args : Vec<Value>) -> Result<Value, String>
+    {
+        args.expect_count("instance_get", 2)?;
+        let instance : InstanceId = args.extract_arg("instance_get", 0)?;
+        let name : String = args.extract_arg("instance_get", 1)?;
+        self.instance_get(instance, &name)
+    }
+    pub (crate) fn sim_func_instance_set(&mut self, mut args : Vec<Value>) -> Result<Value, String>
+    {
+        args.expect_count("instance_set", 3)?;
+        let instance : InstanceId = args.extract_arg("instance_set", 0)?;
+        let name : String = args.extract_arg("instance_set", 1)?;
+        let value : Value = args.extract_arg("instance_set", 2)?;
+        self.instance_set(instance, &name, value)?;
+        Ok(Value::default())
+    }
+    pub (crate) fn sim_func_instance_variables(&mut self, mut args : Vec<Value>) -> Result<Value, String>
+    {
+        args.expect_count("instance_variables", 1)?;
+        let instance : InstanceId = args.extract_arg("instance_variables", 0)?;
+        Ok(self.instance_variables(instance)?.into_value())
+    }
+    pub (crate) fn sim_func_instance_find(&mut self, mut args : Vec<Value>) -> Result<Value, String>
     {
-        if args.len() != 1
+        args.expect_count("instance_find", 2)?;
+        let object : ObjectId = args.extract_arg("instance_find", 0)?;
+        let n : f64 = args.extract_arg("instance_find", 1)?;
+        if n < 0.0 || n.fract() != 0.0
         {
-            return Err(format!("error: wrong number of arguments to object_count(); expected 1, got {}", args.len()));
+            return plainerr("error: second argument to instance_find() must be a non-negative whole number");
         }
+        // null if there aren't that many instances
+        Ok(self.instances_of(object)?.get(n as usize).map(|id| id.into_value()).unwrap_or_default())
+    }
+    pub (crate) fn sim_func_instance_tag(&mut self, mut args : Vec<Value>) -> Result<Value, String>
+    {
+        args.expect_count("instance_tag", 2)?;
+        let instance : InstanceId = args.extract_arg("instance_tag", 0)?;
+        let tag : String = args.extract_arg("instance_tag", 1)?;
+        self.instance_tag(instance, &tag)?;
+        Ok(Value::default())
+    }
+    pub (crate) fn sim_func_instance_untag(&mut self, mut args : Vec<Value>) -> Result<Value, String>
+    {
+        args.expect_count("instance_untag", 2)?;
+        let instance : InstanceId = args.extract_arg("instance_untag", 0)?;
+        let tag : String = args.extract_arg("instance_untag", 1)?;
+        self.instance_untag(instance, &tag)?;
+        Ok(Value::default())
+    }
+    pub (crate) fn sim_func_instance_has_tag(&mut self, mut args : Vec<Value>) -> Result<Value, String>
+    {
+        args.expect_count("instance_has_tag", 2)?;
+        let instance : InstanceId = args.extract_arg("instance_has_tag", 0)?;
+        let tag : String = args.extract_arg("instance_has_tag", 1)?;
+        Ok(self.instance_has_tag(instance, &tag)?.into_value())
+    }
+    pub (crate) fn sim_func_instances_of(&mut self, mut args : Vec<Value>) -> Result<Value, String>
+    {
+        args.expect_count("instances_of", 1)?;
+        let object : ObjectId = args.extract_arg("instances_of", 0)?;
+        Ok(self.instances_of(object)?.into_value())
+    }
+    pub (crate) fn sim_func_instances_tagged(&mut self, mut args : Vec<Value>) -> Result<Value, String>
+    {
+        args.expect_count("instances_tagged", 1)?;
+        let tag : String = args.extract_arg("instances_tagged", 0)?;
+        Ok(self.instances_tagged(&tag).into_value())
+    }
+    pub (crate) fn sim_func_send(&mut self, mut args : Vec<Value>) -> Result<Value, String>
+    {
+        args.expect_count("send", 3)?;
+        let instance : InstanceId = args.extract_arg("send", 0)?;
+        let name : String = args.extract_arg("send", 1)?;
+        let payload : Value = args.extract_arg("send", 2)?;
+        self.send_message(instance, &name, payload);
+        Ok(Value::default())
+    }
+    pub (crate) fn sim_func_broadcast(&mut self, mut args : Vec<Value>) -> Result<Value, String>
+    {
+        args.expect_count("broadcast", 3)?;
+        let object : ObjectId = args.extract_arg("broadcast", 0)?;
+        let name : String = args.extract_arg("broadcast", 1)?;
+        let payload : Value = args.extract_arg("broadcast", 2)?;
+        self.broadcast_message(object, &name, payload)?;
+        Ok(Value::default())
+    }
+    pub (crate) fn sim_func_alarm_set(&mut self, mut args : Vec<Value>) -> Result<Value, String>
+    {
+        args.expect_count("alarm_set", 3)?;
+        let instance : InstanceId = args.extract_arg("alarm_set", 0)?;
+        let method : String = args.extract_arg("alarm_set", 1)?;
+        let ticks = extract_ticks(&mut args, "alarm_set")?;
+        self.alarm_set(instance, &method, ticks)?;
+        Ok(Value::default())
+    }
+    pub (crate) fn sim_func_alarm_repeat(&mut self, mut args : Vec<Value>) -> Result<Value, String>
+    {
+        args.expect_count("alarm_repeat", 3)?;
+        let instance : InstanceId = args.extract_arg("alarm_repeat", 0)?;
+        let method : String = args.extract_arg("alarm_repeat", 1)?;
+        let ticks = extract_ticks(&mut args, "alarm_repeat")?;
+        self.alarm_repeat(instance, &method, ticks)?;
+        Ok(Value::default())
+    }
+    pub (crate) fn sim_func_alarm_cancel(&mut self, mut args : Vec<Value>) -> Result<Value, String>
+    {
+        args.expect_count("alarm_cancel", 2)?;
+        let instance : InstanceId = args.extract_arg("alarm_cancel", 0)?;
+        let method : String = args.extract_arg("alarm_cancel", 1)?;
+        Ok(self.alarm_cancel(instance, &method)?.into_value())
+    }
+    pub (crate) fn sim_func_alarm_get(&mut self, mut args : Vec<Value>) -> Result<Value, String>
+    {
+        args.expect_count("alarm_get", 2)?;
+        let instance : InstanceId = args.extract_arg("alarm_get", 0)?;
+        let method : String = args.extract_arg("alarm_get", 1)?;
+        // null if the alarm isn't set
+        Ok(self.alarm_remaining(instance, &method)?.map(|ticks| Value::Number(ticks as f64)).unwrap_or_default())
+    }
+    pub (crate) fn sim_func_goto_state(&mut self, mut args : Vec<Value>) -> Result<Value, String>
+    {
+        args.expect_count("goto_state", 1)?;
+        let state : String = args.extract_arg("goto_state", 0)?;
+        let instance_id = *self.top_frame.instancestack.last().ok_or_else(|| minierr("error: goto_state() used outside of an instance"))?;
+        self.instance_goto_state(InstanceId(instance_id), &state)?;
+        Ok(Value::default())
+    }
+    pub (crate) fn sim_func_instance_state(&mut self, mut args : Vec<Value>) -> Result<Value, String>
+    {
+        args.expect_count("instance_state", 1)?;
+        let instance : InstanceId = args.extract_arg("instance_state", 0)?;
+        // null if the instance hasn't entered a state
+        Ok(self.instance_state(instance)?.map(|state| Value::Text(state)).unwrap_or_default())
+    }
+    pub (crate) fn sim_func_object_functions(&mut self, mut args : Vec<Value>) -> Result<Value, String>
+    {
+        args.expect_count("object_functions", 1)?;
+        let object : ObjectId = args.extract_arg("object_functions", 0)?;
+        Ok(self.object_functions(object)?.into_value())
+    }
+    pub (crate) fn sim_func_object_count(&mut self, mut args : Vec<Value>) -> Result<Value, String>
+    {
+        args.expect_count("object_count", 1)?;
         
-        let object_id = self.vec_pop_front_object(&mut args).ok_or_else(|| minierr("error: first argument to object_count() must be an object"))?;
+        let ObjectId(object_id) = args.extract_arg("object_count", 0)?;
         
         let instance_list = self.global.instances_by_type.get(&object_id).ok_or_else(|| format!("error: tried to use non-extant object type {}", object_id))?;
         Ok(Value::Number(instance_list.len() as f64))
     }
     pub (crate) fn sim_func_object_has_variable(&mut self, mut args : Vec<Value>) -> Result<Value, String>
     {
-        if args.len() != 2
-        {
-            return Err(format!("error: wrong number of arguments to object_has_variable(); expected 2, got {}", args.len()));
-        }
+        args.expect_count("object_has_variable", 2)?;
         
-        let object_id = self.vec_pop_front_object(&mut args).ok_or_else(|| minierr("error: first argument to object_has_variable() must be an object"))?;
-        let text = self.vec_pop_front_text(&mut args).ok_or_else(|| minierr("error: second argument to object_has_variable() must be a string"))?;
+        let ObjectId(object_id) = args.extract_arg("object_has_variable", 0)?;
+        let text : String = args.extract_arg("object_has_variable", 1)?;
         let text_id = self.get_string_index(&text);
         
         let object = self.global.objects.get(&object_id).ok_or_else(|| format!("error: tried to use non-extant object type {}", object_id))?;
@@ -475,30 +549,24 @@ impl Interpreter
     }
     pub (crate) fn sim_func_object_has_function(&mut self, mut args : Vec<Value>) -> Result<Value, String>
     {
-        if args.len() != 2
-        {
-            return Err(format!("error: wrong number of arguments to object_has_function(); expected 2, got {}", args.len()));
-        }
+        args.expect_count("object_has_function", 2)?;
         
-        let object_id = self.vec_pop_front_object(&mut args).ok_or_else(|| minierr("error: first argument to object_has_function() must be an object"))?;
-        let text = self.vec_pop_front_text(&mut args).ok_or_else(|| minierr("error: second argument to object_has_function() must be a string"))?;
+        let ObjectId(object_id) = args.extract_arg("object_has_function", 0)?;
+        let text : String = args.extract_arg("object_has_function", 1)?;
         let text_id = self.get_string_index(&text);
         
         let object = self.global.objects.get(&object_id).ok_or_else(|| format!("error: tried to use non-extant object type {}", object_id))?;
-        Ok(Value::Number(bool_floaty(object.functions.contains_key(&text_id))))
+        Ok(Value::Number(bool_floaty(object.has_method(text_id))))
     }
     pub (crate) fn sim_func_parse_text(&mut self, mut args : Vec<Value>) -> Result<Value, String>
     {
-        if args.len() != 1
-        {
-            return Err(format!("error: wrong number of arguments to parse_text(); expected 1, got {}", args.len()));
-        }
+        args.expect_count("parse_text", 1)?;
         
-        let text = self.vec_pop_front_text(&mut args).ok_or_else(|| minierr("error: first argument to parse_text() must be a string"))?;
+        let text : String = args.extract_arg("parse_text", 0)?;
         let parser = &mut self.global.parser;
         
         let program_lines : Vec<String> = text.lines().map(|x| x.to_string()).collect();
-        let tokens = parser.tokenize(&program_lines, true)?;
+        let tokens = parser.tokenize(&text, true)?;
         
         let ast = parser.parse_program(&tokens, &program_lines, true)?.ok_or_else(|| minierr("error: string failed to parse"))?;
         
@@ -506,40 +574,43 @@ impl Interpreter
     }
     pub (crate) fn sim_func_parse_text_with_grammar(&mut self, mut args : Vec<Value>) -> Result<Value, String>
     {
-        if args.len() != 2
-        {
-            return Err(format!("error: wrong number of arguments to parse_text_with_grammar(); expected 2, got {}", args.len()));
-        }
+        args.expect_count("parse_text_with_grammar", 2)?;
         
-        let text = self.vec_pop_front_text(&mut args).ok_or_else(|| minierr("error: first argument to parse_text_with_grammar() must be a string"))?;
-        let grammar = self.vec_pop_front_text(&mut args).ok_or_else(|| minierr("error: second argument to parse_text_with_grammar() must be a string"))?;
+        let text : String = args.extract_arg("parse_text_with_grammar", 0)?;
+        let grammar : String = args.extract_arg("parse_text_with_grammar", 1)?;
         let mut parser = Parser::new_from_grammar(&grammar)?;
         
         let program_lines : Vec<String> = text.lines().map(|x| x.to_string()).collect();
-        let tokens = parser.tokenize(&program_lines, true)?;
+        let tokens = parser.tokenize(&text, true)?;
         
         let ast = parser.parse_program(&tokens, &program_lines, true)?.ok_or_else(|| minierr("error: string failed to parse"))?;
         
         Ok(ast_to_dict(&ast))
     }
+    pub (crate) fn sim_func_unparse_ast(&mut self, mut args : Vec<Value>) -> Result<Value, String>
+    {
+        args.expect_count("unparse_ast", 1)?;
+        
+        let dict : HashMap<HashableValue, Value> = args.extract_arg("unparse_ast", 0)?;
+        let ast = dict_to_ast(&dict)?;
+        
+        Ok(Value::Text(self.global.parser.unparse(&ast)?))
+    }
 
     pub (crate) fn sim_func_compile_ast(&mut self, mut args : Vec<Value>) -> Result<Value, String>
     {
-        if args.len() != 1
-        {
-            return Err(format!("error: wrong number of arguments to compile_ast(); expected 1, got {}", args.len()));
-        }
+        args.expect_count("compile_ast", 1)?;
         
-        let dict = self.vec_pop_front_dict(&mut args).ok_or_else(|| minierr("error: first argument to compile_ast() must be a dictionary"))?;
+        let dict : HashMap<HashableValue, Value> = args.extract_arg("compile_ast", 0)?;
         let ast = dict_to_ast(&dict)?;
         let code = compile_bytecode(&ast, &mut self.global)?;
         
-        // endaddr at the start because Rc::new() moves `code`
+        // endaddr at the start because Arc::new() moves `code`
         Ok
         ( Value::new_funcval
           ( None,
             FuncSpec
-            { endaddr : code.len(), // must be before code : Rc::new(code)
+            { endaddr : code.len(), // must be before code : Arc::new(code)
               argcount : 0,
               code,
               startaddr : 0,
@@ -552,21 +623,18 @@ impl Interpreter
     }
     pub (crate) fn sim_func_compile_ast_generator(&mut self, mut args : Vec<Value>) -> Result<Value, String>
     {
-        if args.len() != 1
-        {
-            return Err(format!("error: wrong number of arguments to compile_ast_generator(); expected 1, got {}", args.len()));
-        }
+        args.expect_count("compile_ast_generator", 1)?;
         
-        let dict = self.vec_pop_front_dict(&mut args).ok_or_else(|| minierr("error: first argument to compile_ast_generator() must be a dictionary"))?;
+        let dict : HashMap<HashableValue, Value> = args.extract_arg("compile_ast_generator", 0)?;
         let ast = dict_to_ast(&dict)?;
         let code = compile_bytecode(&ast, &mut self.global)?;
         
-        // endaddr at the start because Rc::new() moves `code`
+        // endaddr at the start because Arc::new() moves `code`
         Ok
         ( Value::new_funcval
           ( None,
             FuncSpec
-            { endaddr : code.len(), // must be before code : Rc::new(code)
+            { endaddr : code.len(), // must be before code : Arc::new(code)
               argcount : 0,
               code,
               startaddr : 0,
@@ -580,26 +648,23 @@ impl Interpreter
 
     pub (crate) fn sim_func_compile_text(&mut self, mut args : Vec<Value>) -> Result<Value, String>
     {
-        if args.len() != 1
-        {
-            return Err(format!("error: wrong number of arguments to compile_text(); expected 1, got {}", args.len()));
-        }
-        let text = self.vec_pop_front_text(&mut args).ok_or_else(|| minierr("error: first argument to compile_text() must be a string"))?;
+        args.expect_count("compile_text", 1)?;
+        let text : String = args.extract_arg("compile_text", 0)?;
         
         let program_lines : Vec<String> = text.lines().map(|x| x.to_string()).collect();
         let parser = &mut self.global.parser;
         
-        let tokens = parser.tokenize(&program_lines, true)?;
+        let tokens = parser.tokenize(&text, true)?;
         let ast = parser.parse_program(&tokens, &program_lines, true)?.ok_or_else(|| minierr("error: string failed to parse"))?;
         
         let code = compile_bytecode(&ast, &mut self.global)?;
         
-        // endaddr at the start because Rc::new() moves `code`
+        // endaddr at the start because Arc::new() moves `code`
         Ok
         ( Value::new_funcval
           ( None,
             FuncSpec
-            { endaddr : code.len(), // must be before code : Rc::new(code)
+            { endaddr : code.len(), // must be before code : Arc::new(code)
               argcount : 0,
               code,
               startaddr : 0,
@@ -613,10 +678,7 @@ impl Interpreter
     
     pub (crate) fn sim_subfunc_len(myself : ValueLoc, args : Vec<Value>) -> Result<Value, String>
     {
-        if !args.is_empty()
-        {
-            return Err(format!("error: wrong number of arguments to len(); expected 0, got {}", args.len()));
-        }
+        args.expect_count("len", 0)?;
         
         Ok(match myself.as_ref()
         {
@@ -629,24 +691,18 @@ impl Interpreter
     }
     pub (crate) fn sim_subfunc_keys(myself : ValueLoc, args : Vec<Value>) -> Result<Value, String>
     {
-        if !args.is_empty()
-        {
-            return Err(format!("error: wrong number of arguments to keys(); expected 0, got {}", args.len()));
-        }
+        args.expect_count("keys", 0)?;
         
         Ok(match myself.as_ref()
         {
             Value::Array(ref array) => Value::Array((0..array.len()).map(|i| Value::Number(i as f64)).collect()),
-            Value::Dict(ref dict) => Value::Array(dict.iter().map(|(key, _)| hashval_to_val(key.clone())).collect()),
+            Value::Dict(ref dict) => Value::Array(dict.keys().map(|key| hashval_to_val(key.clone())).collect()),
             _ => return plainerr("error: tried to take length of lengthless type")
         })
     }
     pub (crate) fn sim_subfunc_slice(myself : ValueLoc, mut args : Vec<Value>) -> Result<Value, String>
     {
-        if args.len() != 2
-        {
-            return Err(format!("error: wrong number of arguments to slice(); expected 2, got {}", args.len()));
-        }
+        args.expect_count("slice", 2)?;
         let start = args.expect_extract(0)?;
         let end = args.expect_extract(1)?;
         let start = match_or_err!(start, Value::Number(start) => start.round() as i64, minierr("error: start and end indexes passed to slice() must be numbers"))?;
@@ -655,22 +711,19 @@ impl Interpreter
         Ok(match myself.as_ref()
         {
             Value::Text(ref string) => slice_any(&string.chars().collect::<Vec<char>>(), start, end).map(|array| Value::Text(array.iter().cloned().collect())).ok_or_else(|| minierr("error: slice() on string went out of range"))?,
-            Value::Array(ref array) => slice_any(&array, start, end).map(|array| Value::Array(array.to_vec())).ok_or_else(|| minierr("error: slice() on array went out of range"))?,
+            Value::Array(ref array) => slice_any(array, start, end).map(|array| Value::Array(array.to_vec())).ok_or_else(|| minierr("error: slice() on array went out of range"))?,
             _ => return plainerr("error: tried to slice lengthless type")
         })
     }
     pub (crate) fn sim_subfunc_contains(myself : ValueLoc, mut args : Vec<Value>) -> Result<Value, String>
     {
-        if args.len() != 1
-        {
-            return Err(format!("error: wrong number of arguments to contains(); expected 1, got {}", args.len()));
-        }
+        args.expect_count("contains", 1)?;
         let key = args.expect_extract(0)?;
         Ok(match myself.as_ref()
         {
             Value::Dict(ref dict) => Value::Number(bool_floaty(dict.contains_key(&val_to_hashval(key)?))),
             Value::Set (ref set ) => Value::Number(bool_floaty(set .contains    (&val_to_hashval(key)?))),
-            _ => return plainerr("error: remove() must be called with an array, dictionary, or set as its argument")
+            _ => return plainerr("error: contains() must be called on a dictionary or set")
         })
     }
     pub (crate) fn sim_subfunc_insert(mut myself : ValueLoc, mut args : Vec<Value>) -> Result<Value, String>
@@ -690,7 +743,7 @@ impl Interpreter
                     let chars : Vec<char> = string.chars().collect();
                     
                     let index = match_or_err!(key, Value::Number(index) => index.round() as isize, minierr("error: tried to insert into a string with a non-number index"))?;
-                    let index = if index < 0 {chars.len() - (-index as usize)} else {index as usize} as usize;
+                    let index = if index < 0 {chars.len() - (-index as usize)} else {index as usize};
                     
                     let left = chars.get(0..index).ok_or_else(|| minierr("error: tried to insert into a string at an out-of-range index"))?.iter().collect::<String>();
                     let right = chars.get(index..chars.len()).ok_or_else(|| minierr("error: tried to insert into a string at an out-of-range index"))?.iter().collect::<String>();
@@ -744,10 +797,7 @@ impl Interpreter
     }
     pub (crate) fn sim_subfunc_push(mut myself : ValueLoc, mut args : Vec<Value>) -> Result<Value, String>
     {
-        if args.len() != 1
-        {
-            return Err(format!("error: wrong number of arguments to push(); expected 1, got {}", args.len()));
-        }
+        args.expect_count("push", 1)?;
         let value = args.expect_extract(0)?;
         match myself.as_mut()?
         {
@@ -770,10 +820,7 @@ impl Interpreter
     }
     pub (crate) fn sim_subfunc_remove(mut myself : ValueLoc, mut args : Vec<Value>) -> Result<Value, String>
     {
-        if args.len() != 1
-        {
-            return Err(format!("error: wrong number of arguments to remove(); expected 1, got {}", args.len()));
-        }
+        args.expect_count("remove", 1)?;
         let key = args.expect_extract(0)?;
         match myself.as_mut()?
         {
@@ -782,7 +829,7 @@ impl Interpreter
                 let mut chars : Vec<char> = string.chars().collect();
                 
                 let index = match_or_err!(key, Value::Number(index) => index.round() as isize, minierr("error: tried to remove from a string with a non-number index"))?;
-                let index = if index < 0 {chars.len() - (-index as usize)} else {index as usize} as usize;
+                let index = if index < 0 {chars.len() - (-index as usize)} else {index as usize};
                 
                 let mid = chars.get(index..=index).ok_or_else(|| minierr("error: tried to remove from a string at an out-of-range index"))?.iter().collect::<String>();
                 chars.drain(index..=index);
@@ -826,10 +873,7 @@ impl Interpreter
     }
     pub (crate) fn sim_subfunc_pop(mut myself : ValueLoc, args : Vec<Value>) -> Result<Value, String>
     {
-        if !args.is_empty()
-        {
-            return Err(format!("error: wrong number of arguments to pop(); expected 0, got {}", args.len()));
-        }
+        args.expect_count("pop", 0)?;
         match myself.as_mut()?
         {
             Value::Array(ref mut array) =>
@@ -842,12 +886,9 @@ impl Interpreter
     }
     pub (crate) fn sim_subfunc_replace_char(mut myself : ValueLoc, mut args : Vec<Value>) -> Result<Value, String>
     {
-        if args.len() != 2
-        {
-            return Err(format!("error: wrong number of arguments to replace_char(); expected 2, got {}", args.len()));
-        }
-        let indexnum = match_or_err!(args.expect_extract(0)?, Value::Number(indexnum) => indexnum, minierr("error: argument 1 to replace_char must be a number"))?.round() as usize;
-        let insert = match_or_err!(args.expect_extract(1)?, Value::Text(text) => text, minierr("error: argument 2 to replace_char must be text"))?;
+        args.expect_count("replace_char", 2)?;
+        let indexnum = args.extract_arg::<f64>("replace_char", 0)?.round() as usize;
+        let insert : String = args.extract_arg("replace_char", 1)?;
         
         match myself.as_mut()?
         {
@@ -0,0 +1,326 @@
+use crate::interpreter::*;
+use super::types::ops::{float_booly, bool_floaty};
+
+/// Opaque id of an instance, for bindings that take or return instances.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InstanceId(pub u64);
+
+/// Opaque id of an object type, for bindings that take or return objects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObjectId(pub usize);
+
+/// Converts a Value into a Rust type. Used to extract binding arguments.
+pub trait FromValue : Sized {
+    /// Describes the kind of value expected, for error messages, e.g. "a number".
+    fn expected() -> String;
+    /// Returns None if the value is of the wrong type.
+    fn from_value(value : Value) -> Option<Self>;
+}
+
+/// Converts a Rust type into a Value. Used for binding return values.
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+/// Types that can be used as dict keys by FromValue/IntoValue.
+pub trait DictKey : Sized + Eq + std::hash::Hash {
+    fn from_key(key : HashableValue) -> Option<Self>;
+    fn into_key(self) -> HashableValue;
+}
+
+macro_rules! simple_conversion { ( $type:ty, $expected:expr, $variant:ident ) =>
+{
+    impl FromValue for $type {
+        fn expected() -> String
+        {
+            $expected.to_string()
+        }
+        fn from_value(value : Value) -> Option<Self>
+        {
+            match_or_none!(value, Value::$variant(inner) => inner)
+        }
+    }
+    impl IntoValue for $type {
+        fn into_value(self) -> Value
+        {
+            Value::$variant(self)
+        }
+    }
+} }
+
+simple_conversion!(f64, "a number", Number);
+simple_conversion!(String, "a string", Text);
+simple_conversion!(Custom, "a custom value", Custom);
+
+impl FromValue for Value {
+    fn expected() -> String
+    {
+        "a value".to_string()
+    }
+    fn from_value(value : Value) -> Option<Self>
+    {
+        Some(value)
+    }
+}
+impl IntoValue for Value {
+    fn into_value(self) -> Value
+    {
+        self
+    }
+}
+
+impl FromValue for bool {
+    fn expected() -> String
+    {
+        "a number".to_string()
+    }
+    fn from_value(value : Value) -> Option<Self>
+    {
+        match_or_none!(value, Value::Number(num) => float_booly(num))
+    }
+}
+impl IntoValue for bool {
+    fn into_value(self) -> Value
+    {
+        Value::Number(bool_floaty(self))
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value
+    {
+        Value::Text(self.to_string())
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value
+    {
+        Value::default()
+    }
+}
+
+impl FromValue for InstanceId {
+    fn expected() -> String
+    {
+        "an instance".to_string()
+    }
+    fn from_value(value : Value) -> Option<Self>
+    {
+        match_or_none!(value, Value::Instance(id) => InstanceId(id))
+    }
+}
+impl IntoValue for InstanceId {
+    fn into_value(self) -> Value
+    {
+        Value::Instance(self.0)
+    }
+}
+
+impl FromValue for ObjectId {
+    fn expected() -> String
+    {
+        "an object".to_string()
+    }
+    fn from_value(value : Value) -> Option<Self>
+    {
+        match_or_none!(value, Value::Object(id) => ObjectId(id))
+    }
+}
+impl IntoValue for ObjectId {
+    fn into_value(self) -> Value
+    {
+        Value::Object(self.0)
+    }
+}
+
+/// Null converts to None.
+impl<T : FromValue> FromValue for Option<T> {
+    fn expected() -> String
+    {
+        format!("{} or null", T::expected())
+    }
+    fn from_value(value : Value) -> Option<Self>
+    {
+        match value
+        {
+            Value::Null => Some(None),
+            value => T::from_value(value).map(Some)
+        }
+    }
+}
+impl<T : IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value
+    {
+        match self
+        {
+            Some(value) => value.into_value(),
+            None => Value::default()
+        }
+    }
+}
+
+impl<T : FromValue> FromValue for Vec<T> {
+    fn expected() -> String
+    {
+        format!("an array (of {})", T::expected())
+    }
+    fn from_value(value : Value) -> Option<Self>
+    {
+        match value
+        {
+            Value::Array(array) => array.into_iter().map(T::from_value).collect(),
+            _ => None
+        }
+    }
+}
+impl<T : IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value
+    {
+        Value::Array(self.into_iter().map(T::into_value).collect())
+    }
+}
+
+impl<K : DictKey, V : FromValue> FromValue for HashMap<K, V> {
+    fn expected() -> String
+    {
+        format!("a dictionary (of {})", V::expected())
+    }
+    fn from_value(value : Value) -> Option<Self>
+    {
+        match value
+        {
+            Value::Dict(dict) => dict.into_iter().map(|(key, value)| Some((K::from_key(key)?, V::from_value(value)?))).collect(),
+            _ => None
+        }
+    }
+}
+impl<K : DictKey, V : IntoValue> IntoValue for HashMap<K, V> {
+    fn into_value(self) -> Value
+    {
+        Value::Dict(Box::new(self.into_iter().map(|(key, value)| (key.into_key(), value.into_value())).collect()))
+    }
+}
+
+impl DictKey for HashableValue {
+    fn from_key(key : HashableValue) -> Option<Self>
+    {
+        Some(key)
+    }
+    fn into_key(self) -> HashableValue
+    {
+        self
+    }
+}
+impl DictKey for String {
+    fn from_key(key : HashableValue) -> Option<Self>
+    {
+        match_or_none!(key, HashableValue::Text(text) => text)
+    }
+    fn into_key(self) -> HashableValue
+    {
+        HashableValue::Text(self)
+    }
+}
+impl DictKey for InstanceId {
+    fn from_key(key : HashableValue) -> Option<Self>
+    {
+        match_or_none!(key, HashableValue::Instance(id) => InstanceId(id))
+    }
+    fn into_key(self) -> HashableValue
+    {
+        HashableValue::Instance(self.0)
+    }
+}
+
+/// Return types that register() accepts: anything that implements IntoValue, or a Result of one.
+pub trait BindingReturn {
+    fn into_result(self) -> Result<Value, String>;
+}
+impl<T : IntoValue> BindingReturn for T {
+    fn into_result(self) -> Result<Value, String>
+    {
+        Ok(self.into_value())
+    }
+}
+impl<T : IntoValue> BindingReturn for Result<T, String> {
+    fn into_result(self) -> Result<Value, String>
+    {
+        self.map(T::into_value)
+    }
+}
+
+/// Send with the thread_safe feature, nothing otherwise.
+#[cfg(not(feature = "thread_safe"))]
+pub trait MaybeSend {}
+#[cfg(not(feature = "thread_safe"))]
+impl<T : ?Sized> MaybeSend for T {}
+/// Send with the thread_safe feature, nothing otherwise.
+#[cfg(feature = "thread_safe")]
+pub trait MaybeSend : Send {}
+#[cfg(feature = "thread_safe")]
+impl<T : ?Sized + Send> MaybeSend for T {}
+
+/// Functions and closures that register() can turn into simple bindings. Args is a tuple of the argument types.
+pub trait IntoBinding<Args> {
+    /// How many arguments the binding takes.
+    const ARITY : usize;
+    /// The name is only used for error messages.
+    fn into_binding(self, funcname : String) -> BindingCell<SimpleBinding>;
+}
+
+macro_rules! impl_into_binding { ( $count:expr $(, $arg:ident : $index:expr )* ) =>
+{
+    impl<Func, Ret $(, $arg)*> IntoBinding<($($arg,)*)> for Func
+    where Func : FnMut($($arg),*) -> Ret + MaybeSend + 'static,
+          Ret : BindingReturn
+          $(, $arg : FromValue)*
+    {
+        const ARITY : usize = $count;
+        #[allow(unused_mut)]
+        fn into_binding(mut self, funcname : String) -> BindingCell<SimpleBinding>
+        {
+            binding_cell(move |mut args : Vec<Value>|
+            {
+                args.expect_count(&funcname, $count)?;
+                self($(args.extract_arg::<$arg>(&funcname, $index)?),*).into_result()
+            })
+        }
+    }
+} }
+
+impl_into_binding!(0);
+impl_into_binding!(1, A : 0);
+impl_into_binding!(2, A : 0, B : 1);
+impl_into_binding!(3, A : 0, B : 1, C : 2);
+impl_into_binding!(4, A : 0, B : 1, C : 2, D : 3);
+impl_into_binding!(5, A : 0, B : 1, C : 2, D : 3, E : 4);
+impl_into_binding!(6, A : 0, B : 1, C : 2, D : 3, E : 4, F : 5);
+impl_into_binding!(7, A : 0, B : 1, C : 2, D : 3, E : 4, F : 5, G : 6);
+impl_into_binding!(8, A : 0, B : 1, C : 2, D : 3, E : 4, F : 5, G : 6, H : 7);
+
+/// "first", "second", etc. for argument error messages.
+pub (crate) fn ordinal(index : usize) -> String
+{
+    const NAMES : [&str; 8] = ["first", "second", "third", "fourth", "fifth", "sixth", "seventh", "eighth"];
+    match NAMES.get(index)
+    {
+        Some(name) => name.to_string(),
+        None => format!("{}th", index+1)
+    }
+}
+
+impl Interpreter
+{
+    /// Registers a Rust function or closure as a simple binding, converting its arguments and return value automatically.
+    ///
+    /// Arguments can be any type that implements FromValue, and the return value can be anything that implements IntoValue, or a Result<_, String> of it. Arity and argument types are checked before the function is called, e.g.:
+    ///
+    /// interpreter.register("lerp", |a : f64, b : f64, t : f64| a + (b - a) * t);
+    pub fn register<Args, Func : IntoBinding<Args>>(&mut self, funcname : &str, func : Func)
+    {
+        let binding = func.into_binding(funcname.to_string());
+        self.insert_simple_binding(funcname.to_string(), binding);
+        self.set_binding_arity(BindingKind::Function, funcname, Func::ARITY);
+    }
+}
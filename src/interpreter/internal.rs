@@ -42,57 +42,45 @@ impl Interpreter
     
     fn call_arrow_function(&mut self, subfuncval : SubFuncVal, args : Vec<Value>, isexpr : bool) -> Result<(), String>
     {
-        if let Some(binding) = self.get_trivial_arrow_binding(subfuncval.name)
+        // native class methods take priority over normal arrow bindings
+        let custom = match &subfuncval.source
         {
-            match subfuncval.source
+            StackValue::Val(Value::Custom(custom)) => Some(custom.clone()),
+            StackValue::Var(source) => match_or_none!(self.evaluate(source.clone())?.as_ref(), Value::Custom(custom) => custom.clone()),
+            _ => None
+        };
+        if let Some(custom) = custom
+        {
+            if self.has_native_method(&custom, subfuncval.name)
             {
-                StackValue::Val(val) =>
-                {
-                    let ret = binding(ValueLoc::Static(val), args)?;
-                    if isexpr
-                    {
-                        self.stack_push_val(ret);
-                    }
-                }
-                StackValue::Var(source) =>
+                let ret = self.call_native_method(&custom, subfuncval.name, args)?;
+                if isexpr
                 {
-                    let val = self.evaluate(source)?;
-                    let ret = binding(val, args)?;
-                    if isexpr
-                    {
-                        self.stack_push_val(ret);
-                    }
+                    self.stack_push_val(ret);
                 }
-            };
+                return Ok(());
+            }
         }
-        else if let Some(binding_wrapper) = self.get_arrow_binding(subfuncval.name)
+        
+        let binding = self.get_binding(BindingKind::Arrow, subfuncval.name)?.ok_or_else(|| format!("error: no such arrow function `{}`", self.get_indexed_string(subfuncval.name)))?;
+        let val = match subfuncval.source
+        {
+            StackValue::Val(val) => ValueLoc::Static(val),
+            StackValue::Var(source) => self.evaluate(source)?,
+        };
+        let ret = match binding
         {
-            let binding = &mut *binding_wrapper.try_borrow_mut().or_else(|_| plainerr("error: tried to borrow internal function while it was borrowed elsewhere"))?;
-            
-            match subfuncval.source
+            BindingFunc::TrivialArrow(binding) => binding(val, args)?,
+            BindingFunc::Arrow(binding_wrapper) =>
             {
-                StackValue::Val(val) =>
-                {
-                    let ret = binding(ValueLoc::Static(val), args)?;
-                    if isexpr
-                    {
-                        self.stack_push_val(ret);
-                    }
-                }
-                StackValue::Var(source) =>
-                {
-                    let val = self.evaluate(source)?;
-                    let ret = binding(val, args)?;
-                    if isexpr
-                    {
-                        self.stack_push_val(ret);
-                    }
-                }
-            };
-        }
-        else
+                let binding = &mut *borrow_binding(&binding_wrapper)?;
+                binding(val, args)?
+            }
+            _ => return plainerr("internal error: arrow binding was not an arrow function")
+        };
+        if isexpr
         {
-            return Err(format!("error: no such arrow function `{}`", subfuncval.name))
+            self.stack_push_val(ret);
         }
         
         Ok(())
@@ -104,15 +92,15 @@ impl Interpreter
         
         //eprintln!("{} args", argcount);
         
-        if cfg!(stack_len_debugging) && argcount+1 > self.stack_len()
+        if cfg!(feature = "stack_len_debugging") && argcount+1 > self.stack_len()
         {
             return plainerr("internal error: fewer values on stack than expected in FUNCEXPR/FUNCCALL");
         }
         
         let mut args = vec!(Value::Null; argcount);
-        for i in argcount-1..=0
+        for arg in args.iter_mut().rev()
         {
-            args[i] = self.stack_pop_val().ok_or_else(|| minierr("internal error: expected values, got variable on stack in FUNCEXPR/FUNCCALL"))?;
+            *arg = self.stack_pop_val().ok_or_else(|| minierr("internal error: expected values, got variable on stack in FUNCEXPR/FUNCCALL"))?;
         }
         
         // FIXME gives bad error message when can't access variable
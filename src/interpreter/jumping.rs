@@ -28,6 +28,24 @@ impl Interpreter
         
         Ok(())
     }
+    // runs a function to completion on its own frame stack, then puts back whatever code the interpreter was in the middle of running
+    // for the host, and for bindings that need a function to be done before they return
+    pub (crate) fn call_function_to_completion(&mut self, funcdata : Box<FuncVal>, args : Vec<Value>) -> Result<(), String>
+    {
+        let old_top_frame = std::mem::replace(&mut self.top_frame, Frame::new_root(&Code::new()));
+        let old_frames = std::mem::take(&mut self.frames);
+        
+        // the function's frame is the only one above the placeholder root frame, so it's done when the frame stack is empty again
+        let mut ret = self.call_function(funcdata, args, false);
+        while ret.is_ok() && !self.frames.is_empty()
+        {
+            ret = self.step();
+        }
+        
+        self.top_frame = old_top_frame;
+        self.frames = old_frames;
+        ret
+    }
     pub (crate) fn push_new_frame(&mut self, mut new_frame : Frame) -> Result<(), String>
     {
         std::mem::swap(&mut new_frame, &mut self.top_frame);
@@ -42,28 +60,21 @@ impl Interpreter
         // some internal functions (e.g. instance_create()) open a new user-function frame
         // if they do, we need to add the return value to the old frame instead of the current frame
         let frames_len_before = self.frames.len();
-        let ret = 
-        if let Some(binding) = self.get_trivial_binding(name)
-        {
-            binding(self, args)?
-        }
-        else if let Some(binding) = self.get_trivial_simple_binding(name)
-        {
-            binding(args)?
-        }
-        else if let Some(binding_wrapper) = self.get_binding(name)
+        let ret = match self.get_binding(BindingKind::Function, name)?
         {
-            let binding = &mut *binding_wrapper.try_borrow_mut().or_else(|_| plainerr("error: tried to borrow internal function while it was borrowed elsewhere"))?;
-            binding(self, args)?
-        }
-        else if let Some(binding_wrapper) = self.get_simple_binding(name)
-        {
-            let binding = &mut *binding_wrapper.try_borrow_mut().or_else(|_| plainerr("error: tried to borrow internal function while it was borrowed elsewhere"))?;
-            binding(args)?
-        }
-        else
-        {
-            return plainerr("internal error: tried to look up non-extant internal function after it was already referenced in a value (this should be unreachable!)");
+            Some(BindingFunc::Trivial(binding)) => binding(self, args)?,
+            Some(BindingFunc::TrivialSimple(binding)) => binding(args)?,
+            Some(BindingFunc::Normal(binding_wrapper)) =>
+            {
+                let binding = &mut *borrow_binding(&binding_wrapper)?;
+                binding(self, args)?
+            }
+            Some(BindingFunc::Simple(binding_wrapper)) =>
+            {
+                let binding = &mut *borrow_binding(&binding_wrapper)?;
+                binding(args)?
+            }
+            _ => return Err(format!("error: tried to call binding `{}`, which no longer exists", self.get_indexed_string(name)))
         };
         if isexpr
         {
@@ -107,7 +118,7 @@ impl Interpreter
         }
         else if !defdata.fromobj
         {
-            self.jump_to_function(&defdata, args, isexpr, &funcdata)?;
+            self.jump_to_function(defdata, args, isexpr, &funcdata)?;
             return Ok(());
         }
         else if defdata.forcecontext != 0
@@ -123,7 +134,7 @@ impl Interpreter
                 {
                     return Err(format!("error: tried to call function from object type {} in the context of an instance of object type {}", defdata.parentobj, inst.objtype));
                 }
-                self.jump_to_function(&defdata, args, isexpr, &funcdata)?;
+                self.jump_to_function(defdata, args, isexpr, &funcdata)?;
                 self.top_frame.instancestack.push(defdata.forcecontext);
                 return Ok(());
             }
@@ -143,7 +154,7 @@ impl Interpreter
                 {
                     return Err(format!("error: tried to call function from object type {} in the context of an instance of object type {}", defdata.parentobj, inst.objtype));
                 }
-                self.jump_to_function(&defdata, args, isexpr, &funcdata)?; // opens a new frame, changing top_frame to a clean slate
+                self.jump_to_function(defdata, args, isexpr, &funcdata)?; // opens a new frame, changing top_frame to a clean slate
                 self.top_frame.instancestack.push(instance);
                 return Ok(());
             }
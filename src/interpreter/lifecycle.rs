@@ -0,0 +1,164 @@
+use std::convert::TryFrom;
+
+use crate::interpreter::*;
+
+/// A copy of all of an interpreter's instances and the messages and alarms queued for them, made by Interpreter::export_instances().
+///
+/// Instances keep their handles when it's imported, so instance values stored anywhere keep referring to the same instances, and handles that were already stale stay stale.
+#[derive(Debug, Clone)]
+pub struct InstanceSnapshot {
+    instances : BTreeMap<u64, Instance>,
+    dead_instances : Vec<u64>,
+    messages : VecDeque<Message>,
+    timers : BTreeMap<(u64, u64), Timer>,
+    tick : u64,
+    timers_scheduled : u64,
+    generations : Vec<u32>,
+    free_slots : VecDeque<usize>,
+    created : u64,
+}
+
+// killing an instance happens in two steps:
+// 1) it's flagged dead, taken out of the per-object and per-tag lists so with() and queries stop finding it, its alarms are cancelled, and its destroy function is started
+// 2) it's removed for real once no code can be in the middle of using it: when the program exits, or when the host calls collect_dead_instances()
+// in between, its variables can still be read and written, so destroy() and the rest of a with() block that killed it keep working
+impl Interpreter
+{
+    pub (crate) fn instance_is_alive(&self, instance_id : u64) -> bool
+    {
+        self.global.instances.get(&instance_id).map(|inst| !inst.dead).unwrap_or(false)
+    }
+    pub (crate) fn kill_instance(&mut self, instance_id : u64) -> Result<(), String>
+    {
+        let destroy_index = self.get_string_index(&"destroy".to_string());
+        
+        let inst = match self.global.instances.get_mut(&instance_id)
+        {
+            Some(inst) if !inst.dead => inst,
+            // killing an instance twice, e.g. from its own destroy function, does nothing
+            _ => return Ok(())
+        };
+        inst.dead = true;
+        let objtype = inst.objtype;
+        let state = inst.state;
+        let created = inst.created;
+        let tags = std::mem::take(&mut inst.tags);
+        let alarms = std::mem::take(&mut inst.alarms);
+        
+        if let Some(ref mut instance_list) = self.global.instances_by_type.get_mut(&objtype)
+        {
+            instance_list.remove(&created);
+        }
+        self.forget_instance_tags(created, &tags);
+        for timer in alarms.values()
+        {
+            self.global.timers.remove(timer);
+        }
+        self.global.dead_instances.push(instance_id);
+        
+        let object = self.global.objects.get(&objtype).ok_or_else(|| format!("error: tried to kill instance of non-extant object type {}", objtype))?;
+        if let Some(function) = object.method(state, destroy_index)
+        {
+            let mut mydata = function.clone();
+            mydata.forcecontext = instance_id;
+            let pseudo_funcvar = Box::new(FuncVal{predefined : None, userdefdata : mydata});
+            self.call_function(pseudo_funcvar, Vec::new(), false)?;
+        }
+        Ok(())
+    }
+    /// Removes instances that were killed, in the order they were killed, and returns how many there were. This happens automatically when the program exits.
+    ///
+    /// Killed instances stop being visible to with(), instance_exists(), and instance queries right away, but they're only removed here, so that code that's still using them (like their destroy function) keeps working.
+    /// Hosts that keep running code in a loop, e.g. once per game frame, should call this after each iteration. Code that's still using a killed instance when it's removed will fail the same way it would for any non-extant instance.
+    pub fn collect_dead_instances(&mut self) -> usize
+    {
+        let dead = std::mem::take(&mut self.global.dead_instances);
+        for instance_id in &dead
+        {
+            self.global.instances.remove(instance_id);
+            
+            let slot = instance_slot(*instance_id);
+            // a slot whose generation can't go any higher is retired instead of being reused
+            if self.global.instance_generations[slot] < u32::MAX
+            {
+                self.global.instance_generations[slot] += 1;
+                self.global.free_instance_slots.push_back(slot);
+            }
+        }
+        dead.len()
+    }
+    // reuses the slot that was freed the longest time ago, if there is one, so that handing out handles is deterministic
+    pub (crate) fn allocate_instance_handle(&mut self) -> Result<u64, String>
+    {
+        let slot = match self.global.free_instance_slots.pop_front()
+        {
+            Some(slot) => slot,
+            None =>
+            {
+                let slot = self.global.instance_generations.len();
+                if u32::try_from(slot).is_err()
+                {
+                    return plainerr("error: ran out of instance id space");
+                }
+                self.global.instance_generations.push(0);
+                slot
+            }
+        };
+        Ok(instance_handle(slot, self.global.instance_generations[slot]))
+    }
+    /// Copies all instances, including killed ones that haven't been removed yet, along with undelivered messages, pending alarms, and what's needed to keep handing out the same handles afterwards.
+    pub fn export_instances(&self) -> InstanceSnapshot
+    {
+        InstanceSnapshot {
+            instances : self.global.instances.clone(),
+            dead_instances : self.global.dead_instances.clone(),
+            messages : self.global.messages.clone(),
+            timers : self.global.timers.clone(),
+            tick : self.global.current_tick,
+            timers_scheduled : self.global.timers_scheduled,
+            generations : self.global.instance_generations.clone(),
+            free_slots : self.global.free_instance_slots.clone(),
+            created : self.global.instances_created,
+        }
+    }
+    /// Replaces all instances, queued messages, and alarms with the ones in the snapshot, and sets the current tick back to what it was. Instances created after this get the same handles that they would have gotten when the snapshot was made.
+    ///
+    /// Variables are restored as they were, so this only makes sense for an interpreter that's running the same program as the one the snapshot was made from.
+    /// Fails without changing anything if one of the instances' object types is missing or has a different number of variables.
+    pub fn import_instances(&mut self, snapshot : &InstanceSnapshot) -> Result<(), String>
+    {
+        for inst in snapshot.instances.values()
+        {
+            match self.global.objects.get(&inst.objtype)
+            {
+                Some(object) if object.variables.len() == inst.variables.len() => {}
+                _ => return Err(format!("error: tried to import an instance of object type `{}`, which doesn't exist or has different variables", self.get_indexed_string(inst.objtype)))
+            }
+        }
+        
+        self.global.instances = snapshot.instances.clone();
+        self.global.dead_instances = snapshot.dead_instances.clone();
+        self.global.messages = snapshot.messages.clone();
+        self.global.timers = snapshot.timers.clone();
+        self.global.current_tick = snapshot.tick;
+        self.global.timers_scheduled = snapshot.timers_scheduled;
+        self.global.instance_generations = snapshot.generations.clone();
+        self.global.free_instance_slots = snapshot.free_slots.clone();
+        self.global.instances_created = snapshot.created;
+        
+        for instance_list in self.global.instances_by_type.values_mut()
+        {
+            instance_list.clear();
+        }
+        self.global.instances_by_tag.clear();
+        for (instance_id, inst) in self.global.instances.iter().filter(|(_, inst)| !inst.dead)
+        {
+            self.global.instances_by_type.entry(inst.objtype).or_default().insert(inst.created, *instance_id);
+            for tag in &inst.tags
+            {
+                self.global.instances_by_tag.entry(*tag).or_default().insert(inst.created, *instance_id);
+            }
+        }
+        Ok(())
+    }
+}
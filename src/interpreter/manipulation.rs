@@ -1,21 +1,5 @@
 use crate::interpreter::*;
 
-macro_rules! vec_pop_front_generic { ( $list:expr, $x:ident ) =>
-{
-    if !$list.is_empty()
-    {
-        match $list.remove(0)
-        {
-            Value::$x(ret) => Some(ret),
-            _ => None
-        }
-    }
-    else
-    {
-        None
-    }
-} }
-
 impl Interpreter
 {
     #[inline]
@@ -50,22 +34,6 @@ impl Interpreter
         r
     }
     
-    pub (crate) fn vec_pop_front_instance(&mut self, args : &mut Vec<Value>) -> Option<usize>
-    {
-        vec_pop_front_generic!(args, Instance)
-    }
-    pub (crate) fn vec_pop_front_object(&mut self, args : &mut Vec<Value>) -> Option<usize>
-    {
-        vec_pop_front_generic!(args, Object)
-    }
-    pub (crate) fn vec_pop_front_text(&mut self, args : &mut Vec<Value>) -> Option<String>
-    {
-        vec_pop_front_generic!(args, Text)
-    }
-    pub (crate) fn vec_pop_front_dict(&mut self, args : &mut Vec<Value>) -> Option<Box<HashMap<HashableValue, Value>>>
-    {
-        vec_pop_front_generic!(args, Dict)
-    }
     
     #[inline]
     pub (crate) fn read_usize(&mut self) -> usize
@@ -97,6 +65,11 @@ impl Interpreter
         self.global.get_string_index(string)
     }
     #[inline]
+    pub (crate) fn find_string_index(&self, string : &str) -> Option<usize>
+    {
+        self.global.find_string_index(string)
+    }
+    #[inline]
     pub (crate) fn get_indexed_string(&self, index : usize) -> String
     {
         self.global.get_string(index)
@@ -134,7 +107,7 @@ impl Interpreter
             captures.push(val);
         }
         
-        let argcount = self.read_usize() as usize;
+        let argcount = self.read_usize();
         let bodylen = self.read_usize();
         
         let startaddr = self.get_pc();
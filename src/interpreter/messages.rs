@@ -0,0 +1,76 @@
+use crate::interpreter::*;
+
+// messages are delivered by the host, not as soon as they're sent, so that instances can message each other (or themselves) without reentering code that's still running
+impl Interpreter
+{
+    /// Queues a message for an instance, to be delivered to its `on_<name>` method by pump_messages(). The same as send() in gammakit code.
+    pub fn send_message(&mut self, instance : InstanceId, name : &str, payload : Value)
+    {
+        let handler = self.get_string_index(&format!("on_{}", name));
+        self.global.messages.push_back(Message { instance : instance.0, handler, payload });
+    }
+    /// Queues a message for every instance of an object type, in the order they were created. Instances created afterwards don't get it. The same as broadcast() in gammakit code.
+    pub fn broadcast_message(&mut self, object : ObjectId, name : &str, payload : Value) -> Result<(), String>
+    {
+        for instance in self.instances_of(object)?
+        {
+            self.send_message(instance, name, payload.clone());
+        }
+        Ok(())
+    }
+    /// How many messages are waiting to be delivered.
+    pub fn pending_messages(&self) -> usize
+    {
+        self.global.messages.len()
+    }
+    /// Delivers the messages that were queued before it was called, in the order they were sent, and returns how many were delivered.
+    ///
+    /// Each one calls the receiving instance's `on_<name>` method, with the payload as its argument if it takes one. Messages for instances that were killed, or that don't have a matching method, are dropped.
+    /// Messages sent while delivering are left for the next call, so handlers that message each other can't make this run forever.
+    ///
+    /// Can be called after the program exits or in between steps; the code that was running is left as it was. If a handler fails, the messages after it stay queued.
+    pub fn pump_messages(&mut self) -> Result<usize, String>
+    {
+        let mut batch = std::mem::take(&mut self.global.messages);
+        let mut delivered = 0;
+        while let Some(message) = batch.pop_front()
+        {
+            match self.deliver_message(message)
+            {
+                Ok(true) => delivered += 1,
+                Ok(false) => {}
+                Err(err) =>
+                {
+                    batch.append(&mut self.global.messages);
+                    self.global.messages = batch;
+                    return Err(err);
+                }
+            }
+        }
+        Ok(delivered)
+    }
+    
+    fn deliver_message(&mut self, message : Message) -> Result<bool, String>
+    {
+        let (objtype, state) = match self.global.instances.get(&message.instance)
+        {
+            Some(inst) if !inst.dead => (inst.objtype, inst.state),
+            _ => return Ok(false)
+        };
+        let object = self.global.objects.get(&objtype).ok_or_else(|| format!("error: tried to send a message to an instance of non-extant object type {}", objtype))?;
+        let mut mydata = match object.method(state, message.handler)
+        {
+            Some(function) => function.clone(),
+            None => return Ok(false)
+        };
+        let args = match mydata.argcount
+        {
+            0 => Vec::new(),
+            1 => vec!(message.payload),
+            _ => return Err(format!("error: message handler `{}` must take zero or one arguments", self.get_indexed_string(message.handler)))
+        };
+        mydata.forcecontext = message.instance;
+        self.call_function_to_completion(Box::new(FuncVal{predefined : None, userdefdata : mydata}), args)?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,158 @@
+use crate::interpreter::*;
+
+/// For methods of native classes. Called as value->name(args).
+#[cfg(not(feature = "thread_safe"))]
+pub type NativeMethod = dyn FnMut(&Custom, Vec<Value>) -> Result<Value, String>;
+/// For methods of native classes. Called as value->name(args).
+#[cfg(feature = "thread_safe")]
+pub type NativeMethod = dyn FnMut(&Custom, Vec<Value>) -> Result<Value, String> + Send;
+/// For property getters of native classes. Called when evaluating value.name.
+#[cfg(not(feature = "thread_safe"))]
+pub type NativeGetter = dyn FnMut(&Custom) -> Result<Value, String>;
+/// For property getters of native classes. Called when evaluating value.name.
+#[cfg(feature = "thread_safe")]
+pub type NativeGetter = dyn FnMut(&Custom) -> Result<Value, String> + Send;
+/// For property setters of native classes. Called when assigning to value.name.
+#[cfg(not(feature = "thread_safe"))]
+pub type NativeSetter = dyn FnMut(&Custom, Value) -> Result<(), String>;
+/// For property setters of native classes. Called when assigning to value.name.
+#[cfg(feature = "thread_safe")]
+pub type NativeSetter = dyn FnMut(&Custom, Value) -> Result<(), String> + Send;
+/// For finalizers of native classes.
+#[cfg(not(feature = "thread_safe"))]
+pub type NativeFinalizer = dyn FnMut(&Custom) -> Result<(), String>;
+/// For finalizers of native classes.
+#[cfg(feature = "thread_safe")]
+pub type NativeFinalizer = dyn FnMut(&Custom) -> Result<(), String> + Send;
+
+/// A host-defined type that Custom values with a given discriminator belong to. Registered with Interpreter::insert_native_class().
+///
+/// Methods are dispatched before normal arrow bindings, so a class can override e.g. len(). Properties with a getter can be read with `.`, and properties with a setter can be assigned to (including with += etc).
+///
+/// Custom values are plain data that gets copied around freely, so gammakit can't tell when the last copy of one goes away. The finalizer only runs when the program calls value->finalize() or the application calls Interpreter::finalize_native().
+#[derive(Clone)]
+pub struct NativeClass {
+    pub (crate) name : String,
+    pub (crate) methods : HashMap<String, BindingCell<NativeMethod>>,
+    pub (crate) getters : HashMap<String, BindingCell<NativeGetter>>,
+    pub (crate) setters : HashMap<String, BindingCell<NativeSetter>>,
+    pub (crate) finalizer : Option<BindingCell<NativeFinalizer>>,
+}
+
+impl NativeClass {
+    /// The name is what typeof_str() reports for values of this class.
+    pub fn new(name : &str) -> NativeClass
+    {
+        NativeClass { name : name.to_string(), methods : HashMap::new(), getters : HashMap::new(), setters : HashMap::new(), finalizer : None }
+    }
+    pub fn name(&self) -> &str
+    {
+        &self.name
+    }
+    pub fn insert_method(&mut self, name : String, func : BindingCell<NativeMethod>)
+    {
+        self.methods.insert(name, func);
+    }
+    pub fn insert_getter(&mut self, name : String, func : BindingCell<NativeGetter>)
+    {
+        self.getters.insert(name, func);
+    }
+    pub fn insert_setter(&mut self, name : String, func : BindingCell<NativeSetter>)
+    {
+        self.setters.insert(name, func);
+    }
+    pub fn set_finalizer(&mut self, func : BindingCell<NativeFinalizer>)
+    {
+        self.finalizer = Some(func);
+    }
+}
+
+impl Interpreter
+{
+    /// Registers a native class for Custom values with the given discriminator, replacing any class that was registered for it before.
+    pub fn insert_native_class(&mut self, discrim : u64, class : NativeClass)
+    {
+        self.global.native_classes.insert(discrim, class);
+    }
+    /// Unregisters the native class for the given discriminator, returning it.
+    pub fn remove_native_class(&mut self, discrim : u64) -> Option<NativeClass>
+    {
+        self.global.native_classes.remove(&discrim)
+    }
+    /// Runs the finalizer of the value's native class, if it has one.
+    pub fn finalize_native(&mut self, value : &Custom) -> Result<(), String>
+    {
+        let finalizer = match self.global.native_classes.get(&value.discrim)
+        {
+            Some(class) => class.finalizer.clone(),
+            None => return Err(format!("error: tried to finalize a custom value with no native class (discriminator {})", value.discrim))
+        };
+        if let Some(finalizer) = finalizer
+        {
+            let finalizer = &mut *borrow_binding(&finalizer)?;
+            finalizer(value)?;
+        }
+        Ok(())
+    }
+    fn get_native_class(&self, value : &Custom, name : usize) -> Result<(&NativeClass, &String), String>
+    {
+        let class = self.global.native_classes.get(&value.discrim).ok_or_else(|| format!("error: tried to access property `{}` of a custom value with no native class (discriminator {})", self.get_indexed_string(name), value.discrim))?;
+        let name = self.global.strings.get_string(name).ok_or_else(|| minierr("internal error: property name has no associated string"))?;
+        Ok((class, name))
+    }
+    pub (crate) fn native_get(&mut self, value : &Custom, name : usize) -> Result<Value, String>
+    {
+        let (class, namestr) = self.get_native_class(value, name)?;
+        let getter = BindingCell::clone(class.getters.get(namestr).ok_or_else(|| format!("error: native class `{}` has no readable property `{}`", class.name, namestr))?);
+        let getter = &mut *borrow_binding(&getter)?;
+        getter(value)
+    }
+    pub (crate) fn native_set(&mut self, value : &Custom, name : usize, newval : Value) -> Result<(), String>
+    {
+        let (class, namestr) = self.get_native_class(value, name)?;
+        let setter = BindingCell::clone(class.setters.get(namestr).ok_or_else(|| format!("error: native class `{}` has no writable property `{}`", class.name, namestr))?);
+        let setter = &mut *borrow_binding(&setter)?;
+        setter(value, newval)
+    }
+    /// Applies an in-place operation (e.g. +=) to a native property by reading it, updating it, and writing it back.
+    pub (crate) fn native_update<F : FnOnce(ValueLoc) -> Result<(), String>>(&mut self, property : PropertyVar, op : F) -> Result<(), String>
+    {
+        let mut current = self.native_get(&property.source, property.name)?;
+        op(ValueLoc::Mut(&mut current))?;
+        self.native_set(&property.source, property.name, current)
+    }
+    pub (crate) fn has_native_method(&self, value : &Custom, name : usize) -> bool
+    {
+        match (self.global.native_classes.get(&value.discrim), self.global.strings.get_string(name))
+        {
+            (Some(class), Some(name)) => class.methods.contains_key(name) || name == "typeof_str" || (name == "finalize" && class.finalizer.is_some()),
+            _ => false
+        }
+    }
+    /// Calls a method of the value's native class. typeof_str() and finalize() are built in, but can be overridden.
+    pub (crate) fn call_native_method(&mut self, value : &Custom, name : usize, args : Vec<Value>) -> Result<Value, String>
+    {
+        let (class, namestr) = self.get_native_class(value, name)?;
+        if let Some(method) = class.methods.get(namestr)
+        {
+            let method = BindingCell::clone(method);
+            let method = &mut *borrow_binding(&method)?;
+            return method(value, args);
+        }
+        match namestr.as_str()
+        {
+            "typeof_str" =>
+            {
+                args.expect_count("typeof_str", 0)?;
+                Ok(Value::Text(class.name.clone()))
+            }
+            "finalize" =>
+            {
+                args.expect_count("finalize", 0)?;
+                self.finalize_native(value)?;
+                Ok(Value::default())
+            }
+            _ => Err(format!("error: native class `{}` has no method `{}`", class.name, namestr))
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use crate::interpreter::*;
+
+impl Interpreter
+{
+    /// Lists the instances of an object type, in the order they were created. This is the same order that with() visits them in.
+    pub fn instances_of(&self, object : ObjectId) -> Result<Vec<InstanceId>, String>
+    {
+        let instances = self.global.instances_by_type.get(&object.0).ok_or_else(|| format!("error: tried to use non-extant object type {}", object.0))?;
+        Ok(instances.values().map(|id| InstanceId(*id)).collect())
+    }
+    /// Adds a tag to an instance. Tags are arbitrary strings that group instances across object types, like "flammable" or "team_red". Killing an instance removes its tags.
+    pub fn instance_tag(&mut self, instance : InstanceId, tag : &str) -> Result<(), String>
+    {
+        let index = self.get_string_index(&tag.to_string());
+        let inst = self.global.instances.get_mut(&instance.0).ok_or_else(|| format!("error: tried to tag non-extant instance `{}`", instance.0))?;
+        if inst.dead
+        {
+            return Ok(());
+        }
+        inst.tags.insert(index);
+        let created = inst.created;
+        self.global.instances_by_tag.entry(index).or_default().insert(created, instance.0);
+        Ok(())
+    }
+    /// Removes a tag from an instance, if it has it.
+    pub fn instance_untag(&mut self, instance : InstanceId, tag : &str) -> Result<(), String>
+    {
+        let index = self.get_string_index(&tag.to_string());
+        let inst = self.global.instances.get_mut(&instance.0).ok_or_else(|| format!("error: tried to untag non-extant instance `{}`", instance.0))?;
+        inst.tags.remove(&index);
+        let created = inst.created;
+        self.untag_everywhere(created, index);
+        Ok(())
+    }
+    /// Whether an instance has a tag.
+    pub fn instance_has_tag(&mut self, instance : InstanceId, tag : &str) -> Result<bool, String>
+    {
+        let index = self.get_string_index(&tag.to_string());
+        let inst = self.global.instances.get(&instance.0).ok_or_else(|| format!("error: tried to check tags of non-extant instance `{}`", instance.0))?;
+        Ok(inst.tags.contains(&index))
+    }
+    /// Lists the instances that have a tag, of any object type, in the order they were created.
+    pub fn instances_tagged(&mut self, tag : &str) -> Vec<InstanceId>
+    {
+        let index = self.get_string_index(&tag.to_string());
+        self.global.instances_by_tag.get(&index).map(|instances| instances.values().map(|id| InstanceId(*id)).collect()).unwrap_or_default()
+    }
+    
+    fn untag_everywhere(&mut self, created : u64, tag : usize)
+    {
+        if let Some(instances) = self.global.instances_by_tag.get_mut(&tag)
+        {
+            instances.remove(&created);
+            if instances.is_empty()
+            {
+                self.global.instances_by_tag.remove(&tag);
+            }
+        }
+    }
+    // called when an instance is killed, so that tag queries don't find it anymore
+    pub (crate) fn forget_instance_tags(&mut self, created : u64, tags : &BTreeSet<usize>)
+    {
+        for tag in tags
+        {
+            self.untag_everywhere(created, *tag);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use crate::interpreter::*;
+
+impl Interpreter
+{
+    /// Lets instances gain variables that their object type doesn't declare, by assigning to them with instance.name or instance_set(). Off by default, in which case that's an error.
+    ///
+    /// Added variables can only be accessed by name, e.g. with self.name inside of methods, not as bare names.
+    pub fn set_dynamic_instance_variables(&mut self, enabled : bool)
+    {
+        self.global.dynamic_instance_variables = enabled;
+    }
+    /// Reads an instance's variable by name. Object functions are returned bound to the instance, the same as with instance.name.
+    pub fn instance_get(&mut self, instance : InstanceId, name : &str) -> Result<Value, String>
+    {
+        let index = self.get_string_index(&name.to_string());
+        self.evaluate_of_indirect_simple(instance.0, index)
+    }
+    /// Sets an instance's variable by name. Fails if the instance doesn't have it, unless dynamic instance variables are enabled.
+    pub fn instance_set(&mut self, instance : InstanceId, name : &str, value : Value) -> Result<(), String>
+    {
+        let index = self.get_string_index(&name.to_string());
+        self.evaluate(Variable::from_indirection(instance.0, index))?.assign(value)
+    }
+    /// Lists an instance's variables: the ones its object type declares, in order and starting with `id`, and then any that were added at runtime, sorted by name.
+    pub fn instance_variables(&self, instance : InstanceId) -> Result<Vec<String>, String>
+    {
+        let inst = self.global.instances.get(&instance.0).ok_or_else(|| format!("error: tried to list variables of non-extant instance `{}`", instance.0))?;
+        let object = self.global.objects.get(&inst.objtype).ok_or_else(|| format!("error: tried to use instance of non-extant object type {}", inst.objtype))?;
+        
+        let mut declared : Vec<(usize, usize)> = object.variables.iter().map(|(name, slot)| (*slot, *name)).collect();
+        declared.sort_unstable();
+        let mut names : Vec<String> = declared.into_iter().map(|(_, name)| self.get_indexed_string(name)).collect();
+        let mut added : Vec<String> = inst.dynamic_variables.keys().map(|name| self.get_indexed_string(*name)).collect();
+        added.sort();
+        names.extend(added);
+        Ok(names)
+    }
+    /// Lists the functions of an object type, including ones that are only defined inside of states, sorted by name.
+    pub fn object_functions(&self, object : ObjectId) -> Result<Vec<String>, String>
+    {
+        let objspec = self.global.objects.get(&object.0).ok_or_else(|| format!("error: tried to use non-extant object type {}", object.0))?;
+        let state_functions = objspec.states.values().flat_map(|functions| functions.keys());
+        let mut names : Vec<String> = objspec.functions.keys().chain(state_functions).map(|name| self.get_indexed_string(*name)).collect();
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+}
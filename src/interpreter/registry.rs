@@ -0,0 +1,206 @@
+use crate::interpreter::*;
+
+/// Whether a binding is called like a normal function or as an arrow function (value->name()). The two kinds have separate namespaces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BindingKind {
+    Function,
+    Arrow,
+}
+
+#[derive(Clone)]
+pub (crate) enum BindingFunc {
+    Normal(BindingCell<Binding>),
+    Trivial(TrivialBinding),
+    Simple(BindingCell<SimpleBinding>),
+    TrivialSimple(TrivialSimpleBinding),
+    Arrow(BindingCell<ArrowBinding>),
+    TrivialArrow(TrivialArrowBinding),
+}
+
+impl BindingFunc {
+    fn kind(&self) -> BindingKind
+    {
+        match self
+        {
+            BindingFunc::Arrow(_) | BindingFunc::TrivialArrow(_) => BindingKind::Arrow,
+            _ => BindingKind::Function
+        }
+    }
+}
+
+#[derive(Clone)]
+pub (crate) struct BindingEntry {
+    pub (crate) func : BindingFunc,
+    pub (crate) capability : Option<String>,
+    pub (crate) arity : Option<usize>,
+}
+
+/// Describes a registered binding, as returned by Interpreter::list_bindings().
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BindingInfo {
+    pub name : String,
+    pub kind : BindingKind,
+    pub capability : Option<String>,
+    /// How many arguments the binding takes, if it's known. Used by the static checker.
+    pub arity : Option<usize>,
+}
+
+/// Every binding the interpreter knows about, keyed by kind and name index.
+#[derive(Clone, Default)]
+pub (crate) struct BindingRegistry {
+    pub (crate) entries : BTreeMap<(BindingKind, usize), BindingEntry>,
+    denied : HashSet<String>,
+}
+
+impl BindingRegistry {
+    pub (crate) fn insert(&mut self, index : usize, func : BindingFunc)
+    {
+        self.entries.insert((func.kind(), index), BindingEntry { func, capability : None, arity : None });
+    }
+    pub (crate) fn remove(&mut self, kind : BindingKind, index : usize) -> bool
+    {
+        self.entries.remove(&(kind, index)).is_some()
+    }
+    pub (crate) fn contains(&self, kind : BindingKind, index : usize) -> bool
+    {
+        self.entries.contains_key(&(kind, index))
+    }
+    /// Returns Ok(None) if there's no such binding, and Err if its capability has been denied.
+    pub (crate) fn get(&self, kind : BindingKind, index : usize) -> Result<Option<BindingFunc>, &str>
+    {
+        match self.entries.get(&(kind, index))
+        {
+            Some(BindingEntry { capability : Some(capability), .. }) if self.denied.contains(capability) => Err(capability),
+            Some(entry) => Ok(Some(entry.func.clone())),
+            None => Ok(None)
+        }
+    }
+    pub (crate) fn set_capability(&mut self, kind : BindingKind, index : usize, capability : &str) -> bool
+    {
+        match self.entries.get_mut(&(kind, index))
+        {
+            Some(entry) => (entry.capability = Some(capability.to_string()), true).1,
+            None => false
+        }
+    }
+    pub (crate) fn set_arity(&mut self, kind : BindingKind, index : usize, arity : usize) -> bool
+    {
+        match self.entries.get_mut(&(kind, index))
+        {
+            Some(entry) => (entry.arity = Some(arity), true).1,
+            None => false
+        }
+    }
+    pub (crate) fn arity(&self, kind : BindingKind, index : usize) -> Option<usize>
+    {
+        self.entries.get(&(kind, index)).and_then(|entry| entry.arity)
+    }
+    pub (crate) fn deny(&mut self, capability : &str)
+    {
+        self.denied.insert(capability.to_string());
+    }
+    pub (crate) fn allow(&mut self, capability : &str)
+    {
+        self.denied.remove(capability);
+    }
+}
+
+impl Interpreter
+{
+    fn insert_binding_func(&mut self, funcname : &str, func : BindingFunc)
+    {
+        let index = self.global.get_string_index(&funcname.to_string());
+        self.global.bindings.insert(index, func);
+    }
+    /// Insert a normal binding that needs access to the interpreter.
+    ///
+    /// The reference-counter wrapping is required to pass functions.
+    ///
+    /// The reference cell wrapping is required to support lambdas that have closure over mutable references, because that closure may indirectly include the interpreter itself. See magmakit for examples.
+    ///
+    /// Names can contain dots to put bindings in a namespace, e.g. "audio.play" is called as audio.play(...).
+    ///
+    /// Inserting a binding replaces any binding of the same kind and name, including its capability.
+    pub fn insert_binding(&mut self, funcname : String, func : BindingCell<Binding>)
+    {
+        self.insert_binding_func(&funcname, BindingFunc::Normal(func));
+    }
+    /// Insert a normal binding that does not need access to the interpreter or indeed any other external state.
+    pub fn insert_trivial_binding(&mut self, funcname : String, func : TrivialBinding)
+    {
+        self.insert_binding_func(&funcname, BindingFunc::Trivial(func));
+    }
+    /// Insert a normal binding that does not need access to the interpreter.
+    pub fn insert_simple_binding(&mut self, funcname : String, func : BindingCell<SimpleBinding>)
+    {
+        self.insert_binding_func(&funcname, BindingFunc::Simple(func));
+    }
+    /// Insert a normal binding that does not need access to the interpreter or indeed any other external state.
+    pub fn insert_trivial_simple_binding(&mut self, funcname : String, func : TrivialSimpleBinding)
+    {
+        self.insert_binding_func(&funcname, BindingFunc::TrivialSimple(func));
+    }
+    /// Insert an associated function ("arrow" function) binding.
+    pub fn insert_arrow_binding(&mut self, funcname : String, func : BindingCell<ArrowBinding>)
+    {
+        self.insert_binding_func(&funcname, BindingFunc::Arrow(func));
+    }
+    pub fn insert_trivial_arrow_binding(&mut self, funcname : String, func : TrivialArrowBinding)
+    {
+        self.insert_binding_func(&funcname, BindingFunc::TrivialArrow(func));
+    }
+    /// Removes a binding. Returns whether there was one to remove.
+    ///
+    /// Code that was already compiled against the binding fails when it tries to call it.
+    pub fn remove_binding(&mut self, kind : BindingKind, funcname : &str) -> bool
+    {
+        match self.find_string_index(funcname)
+        {
+            Some(index) => self.global.bindings.remove(kind, index),
+            None => false
+        }
+    }
+    /// Lists every binding, sorted by kind and then name.
+    pub fn list_bindings(&self) -> Vec<BindingInfo>
+    {
+        let mut list : Vec<BindingInfo> = self.global.bindings.entries.iter().map(|((kind, index), entry)|
+            BindingInfo { name : self.get_indexed_string(*index), kind : *kind, capability : entry.capability.clone(), arity : entry.arity }
+        ).collect();
+        list.sort_by(|a, b| (a.kind, &a.name).cmp(&(b.kind, &b.name)));
+        list
+    }
+    /// Tags a binding with a capability, e.g. "file_io", so that it can be denied along with every other binding with the same tag. Returns false if there's no such binding.
+    pub fn set_binding_capability(&mut self, kind : BindingKind, funcname : &str, capability : &str) -> bool
+    {
+        match self.find_string_index(funcname)
+        {
+            Some(index) => self.global.bindings.set_capability(kind, index, capability),
+            None => false
+        }
+    }
+    /// Records how many arguments a binding takes, so that the static checker can report calls with the wrong number of arguments. Returns false if there's no such binding.
+    ///
+    /// Bindings added with register() have their arity recorded automatically. For arrow bindings, the count doesn't include the value on the left of the arrow.
+    pub fn set_binding_arity(&mut self, kind : BindingKind, funcname : &str, arity : usize) -> bool
+    {
+        match self.find_string_index(funcname)
+        {
+            Some(index) => self.global.bindings.set_arity(kind, index, arity),
+            None => false
+        }
+    }
+    /// Makes every binding tagged with the given capability fail when called, including ones tagged later.
+    pub fn deny_capability(&mut self, capability : &str)
+    {
+        self.global.bindings.deny(capability);
+    }
+    /// Undoes deny_capability().
+    pub fn allow_capability(&mut self, capability : &str)
+    {
+        self.global.bindings.allow(capability);
+    }
+    pub (crate) fn get_binding(&self, kind : BindingKind, name : usize) -> Result<Option<BindingFunc>, String>
+    {
+        self.global.bindings.get(kind, name).map_err(|capability| format!("error: tried to call binding `{}`, but its capability `{}` has been denied", self.get_indexed_string(name), capability))
+    }
+}
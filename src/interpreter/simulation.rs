@@ -9,7 +9,7 @@ fn stack_access_err<S : ToString>(text : S) -> String
     {
         return text.to_string();
     }
-    panic!(text.to_string())
+    panic!("{}", text.to_string())
 }
 #[inline]
 fn stack_access_err_err<A, S : ToString>(text : S) -> Result<A, String>
@@ -18,7 +18,7 @@ fn stack_access_err_err<A, S : ToString>(text : S) -> Result<A, String>
     {
         return Err(text.to_string());
     }
-    panic!(text.to_string())
+    panic!("{}", text.to_string())
 }
 #[inline]
 fn strange_err_plain<A, S : ToString>(text : S) -> Result<A, String>
@@ -27,7 +27,7 @@ fn strange_err_plain<A, S : ToString>(text : S) -> Result<A, String>
     {
         return Err(text.to_string());
     }
-    panic!(text.to_string())
+    panic!("{}", text.to_string())
 }
 #[inline]
 fn strange_err<S : ToString>(text : S) -> String
@@ -36,15 +36,15 @@ fn strange_err<S : ToString>(text : S) -> String
     {
         return text.to_string();
     }
-    panic!(text.to_string())
+    panic!("{}", text.to_string())
 }
 
-pub (crate) static mut OPTABLE : [OpFunc; 256] = [Interpreter::sim_INVALID as OpFunc; 256];
-pub (crate) static mut REVERSE_OPTABLE : Option<BTreeMap<usize, u8>> = None;
+pub (crate) static OPTABLE : [OpFunc; 256] = build_opfunc_table();
 
-pub (crate) fn build_opfunc_table()
+const fn build_opfunc_table() -> [OpFunc; 256]
 {
-    macro_rules! set { ( $x:ident, $y:ident ) => { unsafe { OPTABLE[$x as usize] = Interpreter::$y; } } }
+    let mut table = [Interpreter::sim_INVALID as OpFunc; 256];
+    macro_rules! set { ( $x:ident, $y:ident ) => { table[$x as usize] = Interpreter::$y; } }
     
     set!(NOP, sim_NOP);
     set!(PUSHFLT, sim_PUSHFLT);
@@ -72,6 +72,7 @@ pub (crate) fn build_opfunc_table()
     set!(UNSTATEINCR, sim_UNSTATEINCR);
     set!(UNSTATEDECR, sim_UNSTATEDECR);
     set!(SETBAREGLOBAL, sim_SETBAREGLOBAL);
+    set!(SETOBJCONST, sim_SETOBJCONST);
     set!(BINOPAND, sim_BINOPAND);
     set!(BINOPOR, sim_BINOPOR);
     set!(BINOPEQ, sim_BINOPEQ);
@@ -103,6 +104,8 @@ pub (crate) fn build_opfunc_table()
     set!(COLLECTARRAY, sim_COLLECTARRAY);
     set!(COLLECTDICT, sim_COLLECTDICT);
     set!(COLLECTSET, sim_COLLECTSET);
+    set!(COLLECTSTRING, sim_COLLECTSTRING);
+    set!(FORMATVAL, sim_FORMATVAL);
     set!(ARRAYEXPR, sim_ARRAYEXPR);
     set!(EVALUATEARRAYEXPR, sim_EVALUATEARRAYEXPR);
     set!(BREAK, sim_BREAK);
@@ -118,6 +121,7 @@ pub (crate) fn build_opfunc_table()
     set!(UNSCOPE, sim_UNSCOPE);
     set!(WITH, sim_WITH);
     set!(WITHAS, sim_WITHAS);
+    set!(WITHEACH, sim_WITHEACH);
     set!(WHILETEST, sim_WHILETEST);
     set!(WHILELOOP, sim_WHILELOOP);
     set!(WITHLOOP, sim_WITHLOOP);
@@ -128,20 +132,22 @@ pub (crate) fn build_opfunc_table()
     set!(RETURN, sim_RETURN);
     set!(YIELD, sim_YIELD);
     
-    let mut my_table = BTreeMap::new();
-    for i in 0..=255
+    table
+}
+
+#[cfg(feature = "track_op_performance")]
+pub (crate) fn reverse_optable() -> &'static BTreeMap<usize, u8>
+{
+    static REVERSE_OPTABLE : std::sync::OnceLock<BTreeMap<usize, u8>> = std::sync::OnceLock::new();
+    REVERSE_OPTABLE.get_or_init(||
     {
-        unsafe
+        let mut my_table = BTreeMap::new();
+        for i in 0..=255
         {
             my_table.insert(OPTABLE[i as usize] as *const OpFunc as usize, i);
         }
-    }
-    unsafe
-    {
-        REVERSE_OPTABLE = Some(my_table);
-    }
-    
-    println!("built opfunc table");
+        my_table
+    })
 }
 
 impl Interpreter
@@ -153,7 +159,7 @@ impl Interpreter
         {
             return Err(format!("internal error: no such operation 0x{:02X}", self.pull_single_from_code()));
         }
-        panic!(format!("internal error: no such operation 0x{:02X}", self.pull_single_from_code()))
+        panic!("internal error: no such operation 0x{:02X}", self.pull_single_from_code())
     }
     
     pub (crate) fn sim_NOP(&mut self) -> StepResult
@@ -194,16 +200,16 @@ impl Interpreter
     pub (crate) fn sim_PUSHINSTVAR(&mut self) -> StepResult
     {
         let instance_id = *self.top_frame.instancestack.last().ok_or_else(|| strange_err("internal error: tried to access instance variable when not executing within instance scope"))?;
-        let index = self.read_usize();
-        self.stack_push_var(Variable::from_indirection(instance_id, index));
+        let slot = self.read_usize();
+        self.stack_push_var(Variable::Slot(SlotVar{ident : instance_id, slot}));
         default_step_result()
     }
     pub (crate) fn sim_EVALUATEINSTVAR(&mut self) -> StepResult
     {
         let instance_id = *self.top_frame.instancestack.last().ok_or_else(|| strange_err("internal error: tried to access instance variable when not executing within instance scope"))?;
-        let index = self.read_usize();
+        let slot = self.read_usize();
         
-        self.stack_push_val(self.evaluate_of_indirect_simple(instance_id, index)?);
+        self.stack_push_val(self.evaluate_of_slot_simple(instance_id, slot)?);
         default_step_result()
     }
     pub (crate) fn sim_PUSHBIND(&mut self) -> StepResult
@@ -290,6 +296,11 @@ impl Interpreter
         {
             StackValue::Val(Value::Instance(ident)) =>
                 self.stack_push_var(Variable::from_indirection(ident, name)),
+            StackValue::Val(Value::Custom(source)) =>
+                self.stack_push_var(Variable::Property(Box::new(PropertyVar{source, name}))),
+            // object members are read-only, so they're pushed as values, which can't be assigned to
+            StackValue::Val(Value::Object(object)) =>
+                self.stack_push_val(self.evaluate_of_object_member(object, name)?),
             // FIXME eliminate this
             StackValue::Var(var) =>
             {
@@ -300,6 +311,16 @@ impl Interpreter
                         let id = *id;
                         self.stack_push_var(Variable::from_indirection(id, name))
                     }
+                    Value::Custom(source) =>
+                    {
+                        let source = source.clone();
+                        self.stack_push_var(Variable::Property(Box::new(PropertyVar{source, name})))
+                    }
+                    Value::Object(object) =>
+                    {
+                        let object = *object;
+                        self.stack_push_val(self.evaluate_of_object_member(object, name)?)
+                    }
                     _ => return Err("error: tried to use indirection on a non-instance or non-global value".to_string())
                 }
             }
@@ -325,6 +346,13 @@ impl Interpreter
         {
             StackValue::Val(Value::Instance(ident)) =>
                 self.stack_push_val(self.evaluate_of_indirect_simple(ident, name)?),
+            StackValue::Val(Value::Custom(source)) =>
+            {
+                let val = self.native_get(&source, name)?;
+                self.stack_push_val(val)
+            }
+            StackValue::Val(Value::Object(object)) =>
+                self.stack_push_val(self.evaluate_of_object_member(object, name)?),
             // FIXME eliminate this
             StackValue::Var(var) =>
             {
@@ -335,6 +363,17 @@ impl Interpreter
                         let id = *id;
                         self.stack_push_val(self.evaluate_of_indirect_simple(id, name)?)
                     }
+                    Value::Custom(source) =>
+                    {
+                        let source = source.clone();
+                        let val = self.native_get(&source, name)?;
+                        self.stack_push_val(val)
+                    }
+                    Value::Object(object) =>
+                    {
+                        let object = *object;
+                        self.stack_push_val(self.evaluate_of_object_member(object, name)?)
+                    }
                     q => return Err(format!("error: tried to use eval indirection on a non-instance or non-global value ({:?})", q))
                 }
             }
@@ -493,7 +532,6 @@ impl Interpreter
         self.top_frame.controlstack.push(Controller::While(WhileData{
             variables : self.top_frame.variables.len() as u64,
             expr_start : current_pc,
-            loop_start : current_pc+exprlen,
             loop_end : current_pc+exprlen+codelen
         }));
         default_step_result()
@@ -507,7 +545,6 @@ impl Interpreter
         self.top_frame.controlstack.push(Controller::While(WhileData{
             variables : self.top_frame.variables.len() as u64,
             expr_start : current_pc,
-            loop_start : current_pc+postlen+exprlen,
             loop_end : current_pc+postlen+exprlen+codelen
         }));
         self.add_pc(postlen);
@@ -547,27 +584,68 @@ impl Interpreter
     {
         let object_id = self.read_usize();
         let codelen = self.read_usize();
-        let current_pc = self.get_pc();
         
-        let instance_id_list : Vec<usize> = self.global.instances_by_type.get(&object_id).ok_or_else(|| minierr("error: tried to use non-existant object type in with expression"))?.iter().cloned().collect();
-        if let Some(first) = instance_id_list.first()
-        {
-            self.top_frame.instancestack.push(*first);
-            self.top_frame.controlstack.push(Controller::With(WithData{
-                variables : self.top_frame.variables.len() as u64,
-                loop_start : current_pc,
-                loop_end : current_pc + codelen,
-                instances : instance_id_list.get(1..).unwrap().iter().rev().map(|id| Value::Number(*id as f64)).collect()
-            }));
-        }
-        else
+        let instance_id_list : Vec<u64> = self.global.instances_by_type.get(&object_id).ok_or_else(|| minierr("error: tried to use non-existant object type in with expression"))?.values().cloned().collect();
+        self.start_with_loop(instance_id_list, codelen);
+        
+        default_step_result()
+    }
+    pub (crate) fn sim_WITHEACH(&mut self) -> StepResult
+    {
+        #[cfg(feature = "stack_len_debugging")]
         {
-            // silently skip block if there are no instances of this object type
-            self.add_pc(codelen as usize);
+            if self.stack_len() < 1
+            {
+                return plainerr("internal error: WITHEACH instruction requires 1 values on the stack but found 0");
+            }
         }
+        let value = self.stack_pop_val().ok_or_else(|| stack_access_err("internal error: with expression was a variable instead of a value"))?;
+        let codelen = self.read_usize();
+        
+        let instance_id_list = self.instances_in_collection(value)?;
+        self.start_with_loop(instance_id_list, codelen);
         
         default_step_result()
     }
+    // the instances that with() runs over when it's given a value instead of an object type
+    fn instances_in_collection(&self, value : Value) -> Result<Vec<u64>, String>
+    {
+        let not_instance = || minierr("error: tried to use with() on a collection that has something other than instances in it");
+        match value
+        {
+            Value::Instance(instance_id) => Ok(vec!(instance_id)),
+            Value::Array(values) => values.iter().map(|value| match_or_err!(value, Value::Instance(x) => *x, not_instance())).collect(),
+            Value::Set(values) =>
+            {
+                let mut instance_id_list = values.iter().map(|value| match_or_err!(value, HashableValue::Instance(x) => *x, not_instance())).collect::<Result<Vec<_>, _>>()?;
+                // sets don't have an order, so their instances are visited in the order they were created, like with(Obj)
+                instance_id_list.sort_by_key(|instance_id| (self.global.instances.get(instance_id).map(|inst| inst.created), *instance_id));
+                Ok(instance_id_list)
+            }
+            _ => plainerr("error: tried to use with() with a value that was not an object id, instance id, or array or set of instances")
+        }
+    }
+    // killed and removed instances are skipped, here and in WITHLOOP
+    fn start_with_loop(&mut self, instance_id_list : Vec<u64>, codelen : usize)
+    {
+        let current_pc = self.get_pc();
+        let mut instances : Vec<u64> = instance_id_list.into_iter().rev().collect();
+        while let Some(first) = instances.pop()
+        {
+            if self.instance_is_alive(first)
+            {
+                self.top_frame.instancestack.push(first);
+                self.top_frame.controlstack.push(Controller::With(WithData{
+                    variables : self.top_frame.variables.len() as u64,
+                    loop_start : current_pc,
+                    instances
+                }));
+                return;
+            }
+        }
+        // silently skip block if there are no instances to run it for
+        self.add_pc(codelen);
+    }
     pub (crate) fn sim_WITHAS(&mut self) -> StepResult
     {
         #[cfg(feature = "stack_len_debugging")]
@@ -577,14 +655,40 @@ impl Interpreter
                 return plainerr("internal error: WITHAS instruction requires 1 values on the stack but found 0");
             }
         }
-        let other_id = self.stack_pop_val().ok_or_else(|| stack_access_err("internal error: withas expression was a variable instead of a value"))?;
-        let instance_id = match_or_err!(other_id, Value::Instance(x) => x, minierr("error: tried to use with() with a value that was not an object id or instance id"))?;
+        let value = self.stack_pop_val().ok_or_else(|| stack_access_err("internal error: withas expression was a variable instead of a value"))?;
+        let object_id = self.read_usize();
         let codelen = self.read_usize();
         let current_pc = self.get_pc();
         
-        if !self.global.instances.contains_key(&instance_id)
+        let instance_id = match value
+        {
+            Value::Instance(instance_id) => instance_id,
+            value =>
+            {
+                let instance_id_list = self.instances_in_collection(value)?;
+                for instance_id in &instance_id_list
+                {
+                    match self.global.instances.get(instance_id)
+                    {
+                        Some(instance) if !instance.dead && instance.objtype != object_id =>
+                            return Err(format!("error: tried to use an instance of `{}` as `{}` in with()", self.get_indexed_string(instance.objtype), self.get_indexed_string(object_id))),
+                        _ => {}
+                    }
+                }
+                self.start_with_loop(instance_id_list, codelen);
+                return default_step_result();
+            }
+        };
+        let instance = self.global.instances.get(&instance_id).ok_or_else(|| minierr("error: tried to use non-extant instance as argument of with()"))?;
+        if instance.objtype != object_id
         {
-            return plainerr("error: tried to use non-extant instance as argument of with()");
+            return Err(format!("error: tried to use an instance of `{}` as `{}` in with()", self.get_indexed_string(instance.objtype), self.get_indexed_string(object_id)));
+        }
+        if instance.dead
+        {
+            // killed instances are skipped the same way with(Obj) skips them
+            self.add_pc(codelen);
+            return default_step_result();
         }
         
         self.top_frame.instancestack.push(instance_id);
@@ -592,7 +696,6 @@ impl Interpreter
         self.top_frame.controlstack.push(Controller::With(WithData{
             variables : self.top_frame.variables.len() as u64,
             loop_start : current_pc,
-            loop_end : current_pc + codelen,
             instances : Vec::new()
         }));
         
@@ -613,7 +716,7 @@ impl Interpreter
         let num_cases = self.read_usize();
         let current_pc = self.get_pc();
         
-        let mut case_block_addresses = Vec::with_capacity(num_cases as usize);
+        let mut case_block_addresses = Vec::with_capacity(num_cases);
         for _ in 0..num_cases
         {
             case_block_addresses.push(current_pc + self.read_usize());
@@ -621,7 +724,6 @@ impl Interpreter
         let exit = current_pc + self.read_usize();
         
         self.top_frame.controlstack.push(Controller::Switch(SwitchData{
-            variables : self.top_frame.variables.len() as u64,
             blocks : case_block_addresses,
             exit,
             value
@@ -646,7 +748,7 @@ impl Interpreter
         let which_case = self.read_usize();
         
         let switchdata : &SwitchData = match_or_err!(self.top_frame.controlstack.last(), Some(Controller::Switch(ref x)) => x, strange_err("internal error: SWITCHCASE instruction outside of switch statement"))?;
-        let dest = *switchdata.blocks.get(which_case as usize).ok_or_else(|| strange_err("internal error: which_case in SWITCHCASE was too large"))?;
+        let dest = *switchdata.blocks.get(which_case).ok_or_else(|| strange_err("internal error: which_case in SWITCHCASE was too large"))?;
         
         if ops::value_equal(&value, &switchdata.value)?
         {
@@ -660,7 +762,7 @@ impl Interpreter
     {
         let which_case = self.read_usize();
         let switchdata : &SwitchData = match_or_err!(self.top_frame.controlstack.last(), Some(Controller::Switch(ref x)) => x, strange_err("internal error: SWITCHDEFAULT instruction outside of switch statement"))?;
-        let dest = *switchdata.blocks.get(which_case as usize).ok_or_else(|| strange_err("internal error: which_case in SWITCHDEFAULT was too large"))?;
+        let dest = *switchdata.blocks.get(which_case).ok_or_else(|| strange_err("internal error: which_case in SWITCHDEFAULT was too large"))?;
         self.set_pc(dest);
         
         default_step_result()
@@ -686,7 +788,7 @@ impl Interpreter
     }
     
     #[inline]
-    fn binstate_prep(&mut self) -> Result<(ValueLoc, Value), String>
+    fn binstate_prep(&mut self) -> Result<(Variable, Value), String>
     {
         if self.stack_len() < 2
         {
@@ -696,60 +798,67 @@ impl Interpreter
         let var = self.stack_pop_var().ok_or_else(|| stack_access_err("internal error: primary argument to BINSTATE could not be found or was not a variable"))?;
         let value = self.stack_pop_val().ok_or_else(|| stack_access_err("internal error: not enough values on stack to run instruction BINSTATE (this error should be inaccessible)"))?;
         
-        Ok((self.evaluate(var)?, value))
+        Ok((var, value))
+    }
+    #[inline]
+    fn binstate_apply<F : FnOnce(ValueLoc, &Value) -> Result<(), String>>(&mut self, op : F) -> StepResult
+    {
+        let (var, value) = self.binstate_prep()?;
+        // native properties don't live anywhere we can point into, so they get read, updated, and written back
+        if let Variable::Property(property) = var
+        {
+            return self.native_update(*property, |current| op(current, &value));
+        }
+        op(self.evaluate(var)?, &value)
+    }
+    #[inline]
+    fn unstate_apply<F : FnOnce(ValueLoc) -> Result<(), String>>(&mut self, op : F) -> StepResult
+    {
+        if self.stack_len() < 1
+        {
+            return Err(stack_access_err("internal error: UNSTATEINCR/UNSTATEDECR instruction requires 1 value on the stack but found 0"));
+        }
+        let var = self.stack_pop_var().ok_or_else(|| stack_access_err("internal error: argument to UNSTATEINCR/UNSTATEDECR could not be found or was not a variable"))?;
+        if let Variable::Property(property) = var
+        {
+            return self.native_update(*property, op);
+        }
+        op(self.evaluate(var)?)
     }
     
     pub (crate) fn sim_BINSTATE(&mut self) -> StepResult
     {
-        let (mut var, value) = self.binstate_prep()?;
-        var.assign(value)?;
+        let (var, value) = self.binstate_prep()?;
+        if let Variable::Property(property) = var
+        {
+            return self.native_set(&property.source, property.name, value);
+        }
+        self.evaluate(var)?.assign(value)?;
         default_step_result()
     }
     pub (crate) fn sim_BINSTATEADD(&mut self) -> StepResult
     {
-        let (var, value) = self.binstate_prep()?;
-        inplace_value_op_add(var, &value)?;
-        default_step_result()
+        self.binstate_apply(inplace_value_op_add)
     }
     pub (crate) fn sim_BINSTATESUB(&mut self) -> StepResult
     {
-        let (var, value) = self.binstate_prep()?;
-        inplace_value_op_subtract(var, &value)?;
-        default_step_result()
+        self.binstate_apply(inplace_value_op_subtract)
     }
     pub (crate) fn sim_BINSTATEMUL(&mut self) -> StepResult
     {
-        let (var, value) = self.binstate_prep()?;
-        inplace_value_op_multiply(var, &value)?;
-        default_step_result()
+        self.binstate_apply(inplace_value_op_multiply)
     }
     pub (crate) fn sim_BINSTATEDIV(&mut self) -> StepResult
     {
-        let (var, value) = self.binstate_prep()?;
-        inplace_value_op_divide(var, &value)?;
-        default_step_result()
+        self.binstate_apply(inplace_value_op_divide)
     }
     pub (crate) fn sim_UNSTATEINCR(&mut self) -> StepResult
     {
-        if self.stack_len() < 1
-        {
-            return Err(stack_access_err("internal error: UNSTATEINCR instruction requires 2 values on the stack but found 0"));
-        }
-        let var = self.stack_pop_var().ok_or_else(|| stack_access_err("internal error: argument to UNSTATEINCR could not be found or was not a variable"))?;
-        let val = self.evaluate(var)?;
-        do_inplace_value_op_increment(val)?;
-        default_step_result()
+        self.unstate_apply(do_inplace_value_op_increment)
     }
     pub (crate) fn sim_UNSTATEDECR(&mut self) -> StepResult
     {
-        if self.stack_len() < 1
-        {
-            return Err(stack_access_err("internal error: UNSTATEINCR instruction requires 2 values on the stack but found 0"));
-        }
-        let var = self.stack_pop_var().ok_or_else(|| stack_access_err("internal error: argument to UNSTATEDECR could not be found or was not a variable"))?;
-        let val = self.evaluate(var)?;
-        do_inplace_value_op_decrement(val)?;
-        default_step_result()
+        self.unstate_apply(do_inplace_value_op_decrement)
     }
     pub (crate) fn sim_SETBAREGLOBAL(&mut self) -> StepResult
     {
@@ -766,6 +875,22 @@ impl Interpreter
         
         default_step_result()
     }
+    pub (crate) fn sim_SETOBJCONST(&mut self) -> StepResult
+    {
+        if self.stack_len() < 1
+        {
+            return stack_access_err_err("internal error: SETOBJCONST instruction requires 1 values on the stack but found 0");
+        }
+        
+        let object = self.read_usize();
+        let nameindex = self.read_usize();
+        
+        let value = self.stack_pop_val().ok_or_else(|| stack_access_err("internal error: not enough values on stack to run instruction SETOBJCONST (this error should be inaccessible)"))?;
+        
+        self.global.object_constants.insert((object, nameindex), value);
+        
+        default_step_result()
+    }
     
     #[inline]
     fn binop_prep(&mut self) -> Result<(Value, Value), String>
@@ -877,9 +1002,9 @@ impl Interpreter
         
         let truthy = value_truthy(self, &val);
         
-        if truthy as bool == truthiness
+        if truthy == truthiness
         {
-            self.add_pc(rel as usize);
+            self.add_pc(rel);
             self.stack_push_val(Value::Number(bool_floaty(truthy)));
         }
         else
@@ -931,19 +1056,19 @@ impl Interpreter
     }
     pub (crate) fn sim_COLLECTARRAY(&mut self) -> StepResult
     {
-        let numvals = self.read_usize() as usize;
+        let numvals = self.read_usize();
         let mut myarray = vec!(Value::Null; numvals);
-        for i in numvals-1..=0
+        for element in myarray.iter_mut().rev()
         {
             let val = self.stack_pop_val().ok_or_else(|| stack_access_err("internal error: COLLECTARRAY instruction failed to collect values from stack (this error should be unreachable!)"))?;
-            myarray[i] = val;
+            *element = val;
         }
         self.stack_push_val(Value::Array(myarray));
         default_step_result()
     }
     pub (crate) fn sim_COLLECTDICT(&mut self) -> StepResult
     {
-        let numvals = self.read_usize() as usize;
+        let numvals = self.read_usize();
         #[cfg(feature = "stack_len_debugging")]
         {
             if self.stack_len() < numvals*2
@@ -969,7 +1094,7 @@ impl Interpreter
     }
     pub (crate) fn sim_COLLECTSET(&mut self) -> StepResult
     {
-        let numvals = self.read_usize() as usize;
+        let numvals = self.read_usize();
         #[cfg(feature = "stack_len_debugging")]
         {
             if self.stack_len() < numvals
@@ -987,6 +1112,42 @@ impl Interpreter
         self.stack_push_val(Value::Set(Box::new(myset)));
         default_step_result()
     }
+    pub (crate) fn sim_COLLECTSTRING(&mut self) -> StepResult
+    {
+        let numvals = self.read_usize();
+        #[cfg(feature = "stack_len_debugging")]
+        {
+            if self.stack_len() < numvals
+            {
+                return Err(format!("internal error: not enough values on stack for COLLECTSTRING instruction to build string (need {}, have {})", numvals, self.stack_len()));
+            }
+        }
+        
+        let mut pieces = vec!(String::new(); numvals);
+        for piece in pieces.iter_mut().rev()
+        {
+            let val = self.stack_pop_val().ok_or_else(|| stack_access_err("internal error: COLLECTSTRING instruction failed to collect values from stack"))?;
+            *piece = match_or_err!(val, Value::Text(text) => text, minierr("internal error: COLLECTSTRING instruction found a non-string value on the stack"))?;
+        }
+        self.stack_push_val(Value::Text(pieces.concat()));
+        default_step_result()
+    }
+    pub (crate) fn sim_FORMATVAL(&mut self) -> StepResult
+    {
+        let spec = self.read_indexed_string()?;
+        let val = self.stack_pop_val().ok_or_else(|| stack_access_err("internal error: FORMATVAL instruction requires 1 value on the stack"))?;
+        let text = if spec.is_empty()
+        {
+            format_val(&val).ok_or_else(|| minierr("error: tried to interpolate an unprintable value into a string"))?
+        }
+        else
+        {
+            let spec = FormatSpec::parse(&spec).ok_or_else(|| minierr("internal error: FORMATVAL instruction has an invalid format specifier"))?;
+            format_val_with_spec(&val, &spec)?
+        };
+        self.stack_push_val(Value::Text(text));
+        default_step_result()
+    }
     pub (crate) fn sim_ARRAYEXPR(&mut self) -> StepResult
     {
         #[cfg(feature = "stack_len_debugging")]
@@ -1010,6 +1171,8 @@ impl Interpreter
                 self.stack_push_var(Variable::Array(ArrayVar::new(NonArrayVariable::Direct(dirvar), vec!(index)))),
             StackValue::Var(Variable::Indirect(indirvar)) =>
                 self.stack_push_var(Variable::Array(ArrayVar::new(NonArrayVariable::Indirect(indirvar), vec!(index)))),
+            StackValue::Var(Variable::Slot(slotvar)) =>
+                self.stack_push_var(Variable::Array(ArrayVar::new(NonArrayVariable::Slot(slotvar), vec!(index)))),
             StackValue::Var(Variable::Global(globalvar)) =>
                 self.stack_push_var(Variable::Array(ArrayVar::new(NonArrayVariable::Global(globalvar), vec!(index)))),
             StackValue::Val(Value::Array(array)) =>
@@ -1054,6 +1217,11 @@ impl Interpreter
                 let val = return_indexed(self.evaluate_of_indirect(indirvar)?, &[index])?.to_val();
                 self.stack_push_val(val);
             }
+            StackValue::Var(Variable::Slot(slotvar)) =>
+            {
+                let val = return_indexed(self.evaluate_of_slot(slotvar)?, &[index])?.to_val();
+                self.stack_push_val(val);
+            }
             StackValue::Var(Variable::Global(globalvar)) =>
             {
                 let val = return_indexed(self.evaluate_of_global(globalvar)?, &[index])?.to_val();
@@ -1117,23 +1285,21 @@ impl Interpreter
         
         if let Some(Controller::With(ref mut data)) = self.top_frame.controlstack.last_mut()
         {
-            if let Some(next_instance) = data.instances.pop()
+            while let Some(next_instance) = data.instances.pop()
             {
-                if let Value::Number(next_instance) = next_instance
+                // instances killed earlier in the loop are skipped
+                if !self.global.instances.get(&next_instance).map(|inst| !inst.dead).unwrap_or(false)
                 {
-                    self.top_frame.instancestack.push(next_instance as usize);
-                    let dest = data.loop_start;
-                    self.set_pc(dest);
+                    continue;
                 }
-                else
-                {
-                    return strange_err_plain("internal error: values fed to with controller's 'other' data must be a list of only numbers");
-                }
-            }
-            else
-            {
-                self.top_frame.controlstack.pop();
+                self.top_frame.instancestack.push(next_instance);
+                let dest = data.loop_start;
+                let todrain = data.variables;
+                self.set_pc(dest);
+                self.drain_vars(todrain);
+                return default_step_result();
             }
+            self.top_frame.controlstack.pop();
             return default_step_result();
         }
         strange_err_plain("internal error: WITHLOOP instruction when immediate controller is not a with controller")
@@ -1229,6 +1395,8 @@ impl Interpreter
         }
         else
         {
+            // nothing can be using killed instances anymore once the program is over
+            self.collect_dead_instances();
             return Err("GRACEFUL_EXIT".to_string())
         }
         default_step_result()
@@ -1258,6 +1426,8 @@ impl Interpreter
         }
         else
         {
+            // nothing can be using killed instances anymore once the program is over
+            self.collect_dead_instances();
             return Err("GRACEFUL_EXIT".to_string())
         }
         default_step_result()
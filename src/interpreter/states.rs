@@ -0,0 +1,80 @@
+use crate::interpreter::*;
+
+// instances start out in no state, where only the object's own functions are used
+// once an instance enters a state, the state's functions take the place of the object's functions with the same names
+impl Interpreter
+{
+    /// The name of the state an instance is in, or None if it hasn't entered one. The same as instance_state() in gammakit code.
+    pub fn instance_state(&self, instance : InstanceId) -> Result<Option<String>, String>
+    {
+        let inst = self.global.instances.get(&instance.0).ok_or_else(|| format!("error: tried to check the state of non-extant instance `{}`", instance.0))?;
+        Ok(inst.state.map(|state| self.get_indexed_string(state)))
+    }
+    /// Moves an instance into one of its object type's states, calling the old state's exit() and then the new state's enter(), if they're defined. Both finish running before this returns. The same as goto_state() in gammakit code.
+    ///
+    /// Going to the state the instance is already in exits and re-enters it. Killed instances don't change state.
+    /// State changes made from inside of an enter() or exit() hook happen once that hook returns, in the order they were made, instead of right away.
+    pub fn instance_goto_state(&mut self, instance : InstanceId, state : &str) -> Result<(), String>
+    {
+        let state_index = self.get_string_index(&state.to_string());
+        
+        let inst = self.global.instances.get(&instance.0).ok_or_else(|| format!("error: tried to change the state of non-extant instance `{}`", instance.0))?;
+        let object = self.global.objects.get(&inst.objtype).ok_or_else(|| format!("error: tried to change the state of an instance of non-extant object type {}", inst.objtype))?;
+        if !object.states.contains_key(&state_index)
+        {
+            return Err(format!("error: object type `{}` has no state named `{}`", self.get_indexed_string(inst.objtype), state));
+        }
+        
+        self.global.pending_state_changes.push_back((instance.0, state_index));
+        // hooks run on the host's stack, so if one changes state it's queued for the loop below instead of starting another one inside of it
+        if self.global.changing_state
+        {
+            return Ok(());
+        }
+        self.global.changing_state = true;
+        let mut ret = Ok(());
+        while let Some((instance_id, state_index)) = self.global.pending_state_changes.pop_front()
+        {
+            ret = self.change_state(instance_id, state_index);
+            if ret.is_err()
+            {
+                break;
+            }
+        }
+        self.global.pending_state_changes.clear();
+        self.global.changing_state = false;
+        ret
+    }
+    
+    fn change_state(&mut self, instance_id : u64, state_index : usize) -> Result<(), String>
+    {
+        let enter_index = self.get_string_index(&"enter".to_string());
+        let exit_index = self.get_string_index(&"exit".to_string());
+        
+        let inst = match self.global.instances.get(&instance_id)
+        {
+            Some(inst) if !inst.dead => inst,
+            _ => return Ok(())
+        };
+        let object = self.global.objects.get(&inst.objtype).ok_or_else(|| format!("error: tried to change the state of an instance of non-extant object type {}", inst.objtype))?;
+        let enter = object.states.get(&state_index).and_then(|functions| functions.get(&enter_index)).cloned();
+        let exit = inst.state.and_then(|old_state| object.states.get(&old_state)).and_then(|functions| functions.get(&exit_index)).cloned();
+        
+        if let Some(exit) = exit
+        {
+            self.call_state_hook(instance_id, exit)?;
+        }
+        let inst = self.global.instances.get_mut(&instance_id).ok_or_else(|| format!("error: instance `{}` stopped existing while leaving its state", instance_id))?;
+        inst.state = Some(state_index);
+        if let Some(enter) = enter
+        {
+            self.call_state_hook(instance_id, enter)?;
+        }
+        Ok(())
+    }
+    fn call_state_hook(&mut self, instance_id : u64, mut function : FuncSpec) -> Result<(), String>
+    {
+        function.forcecontext = instance_id;
+        self.call_function_to_completion(Box::new(FuncVal{predefined : None, userdefdata : function}), Vec::new())
+    }
+}
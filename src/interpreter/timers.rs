@@ -0,0 +1,139 @@
+use crate::interpreter::*;
+
+// time only passes when the host calls tick(), so alarms fire at the same points in a replay no matter how fast the host runs
+impl Interpreter
+{
+    /// Schedules a call to one of an instance's methods, `ticks` ticks from now (see tick()). Setting an alarm that's already set reschedules it. The same as alarm_set() in gammakit code.
+    ///
+    /// The method can't take any arguments. If the instance's current state has its own version of the method when the alarm fires, that one is called instead; if only some states have it, the alarm does nothing when the instance isn't in one of them. Alarms on killed instances are never set.
+    pub fn alarm_set(&mut self, instance : InstanceId, method : &str, ticks : u64) -> Result<(), String>
+    {
+        self.schedule_alarm(instance, method, ticks, 0)
+    }
+    /// Same as alarm_set(), but the alarm keeps firing every `ticks` ticks until it's cancelled or set again. The same as alarm_repeat() in gammakit code.
+    pub fn alarm_repeat(&mut self, instance : InstanceId, method : &str, ticks : u64) -> Result<(), String>
+    {
+        self.schedule_alarm(instance, method, ticks, ticks)
+    }
+    /// Cancels an alarm, and returns whether it was set. The same as alarm_cancel() in gammakit code.
+    pub fn alarm_cancel(&mut self, instance : InstanceId, method : &str) -> Result<bool, String>
+    {
+        let method_index = self.get_string_index(&method.to_string());
+        let inst = self.global.instances.get_mut(&instance.0).ok_or_else(|| format!("error: tried to cancel an alarm on non-extant instance `{}`", instance.0))?;
+        match inst.alarms.remove(&method_index)
+        {
+            Some(timer) =>
+            {
+                self.global.timers.remove(&timer);
+                Ok(true)
+            }
+            None => Ok(false)
+        }
+    }
+    /// How many ticks are left before an alarm fires, or None if it isn't set. The same as alarm_get() in gammakit code.
+    pub fn alarm_remaining(&mut self, instance : InstanceId, method : &str) -> Result<Option<u64>, String>
+    {
+        let method_index = self.get_string_index(&method.to_string());
+        let inst = self.global.instances.get(&instance.0).ok_or_else(|| format!("error: tried to check an alarm on non-extant instance `{}`", instance.0))?;
+        Ok(inst.alarms.get(&method_index).map(|(due, _)| due.saturating_sub(self.global.current_tick)))
+    }
+    /// How many ticks have passed in total.
+    pub fn current_tick(&self) -> u64
+    {
+        self.global.current_tick
+    }
+    /// Advances time by some number of ticks, firing alarms as they come due, and returns how many fired.
+    ///
+    /// Alarms that come due on the same tick fire in the order they were scheduled. Alarms set while firing are at least one tick away, so they never fire on the tick they were set on.
+    /// Can be called after the program exits or in between steps; the code that was running is left as it was.
+    /// If an alarm's method fails, time stops at that tick, and the alarms that hadn't fired yet fire on the next call.
+    pub fn tick(&mut self, ticks : u64) -> Result<usize, String>
+    {
+        if ticks == 0
+        {
+            return Ok(0);
+        }
+        let start = self.global.current_tick;
+        let end = start.checked_add(ticks).ok_or_else(|| minierr("error: tried to advance time past the last tick"))?;
+        let mut fired = 0;
+        // ticks without any alarms are skipped over; alarms left over from a call that failed fire on the first tick of this one
+        while let Some(due) = self.global.timers.keys().next().map(|key| key.0).filter(|due| *due <= end)
+        {
+            self.global.current_tick = due.max(start + 1);
+            fired += self.fire_due_alarms()?;
+        }
+        self.global.current_tick = end;
+        Ok(fired)
+    }
+    
+    fn fire_due_alarms(&mut self) -> Result<usize, String>
+    {
+        let mut fired = 0;
+        while let Some(key) = self.global.timers.keys().next().cloned()
+        {
+            if key.0 > self.global.current_tick
+            {
+                break;
+            }
+            let timer = self.global.timers.remove(&key).ok_or_else(|| minierr("internal error: alarm disappeared while firing it"))?;
+            let inst = self.global.instances.get_mut(&timer.instance).ok_or_else(|| minierr("internal error: alarm belongs to an instance that no longer exists"))?;
+            inst.alarms.remove(&timer.method);
+            let objtype = inst.objtype;
+            let state = inst.state;
+            
+            // repeating alarms are rescheduled before their method runs, so that the method can cancel or reset them
+            if timer.period > 0
+            {
+                self.insert_alarm(timer.instance, timer.method, timer.period, timer.period)?;
+            }
+            
+            let object = self.global.objects.get(&objtype).ok_or_else(|| format!("error: tried to fire an alarm on an instance of non-extant object type {}", objtype))?;
+            // the method might only be defined in a state that the instance has left
+            let mut mydata = match object.method(state, timer.method)
+            {
+                Some(function) => function.clone(),
+                None => continue
+            };
+            mydata.forcecontext = timer.instance;
+            self.call_function_to_completion(Box::new(FuncVal{predefined : None, userdefdata : mydata}), Vec::new())?;
+            fired += 1;
+        }
+        Ok(fired)
+    }
+    fn schedule_alarm(&mut self, instance : InstanceId, method : &str, ticks : u64, period : u64) -> Result<(), String>
+    {
+        if ticks == 0
+        {
+            return plainerr("error: alarms must be set at least one tick ahead");
+        }
+        let method_index = self.get_string_index(&method.to_string());
+        let inst = self.global.instances.get(&instance.0).ok_or_else(|| format!("error: tried to set an alarm on non-extant instance `{}`", instance.0))?;
+        if inst.dead
+        {
+            return Ok(());
+        }
+        let object = self.global.objects.get(&inst.objtype).ok_or_else(|| format!("error: tried to set an alarm on an instance of non-extant object type {}", inst.objtype))?;
+        if !object.has_method(method_index)
+        {
+            return Err(format!("error: object type `{}` has no function named `{}` to use as an alarm", self.get_indexed_string(inst.objtype), method));
+        }
+        // states can have their own versions of the method, and all of them have to be usable
+        let mut versions = object.functions.get(&method_index).into_iter().chain(object.states.values().filter_map(|functions| functions.get(&method_index)));
+        if versions.any(|function| function.argcount != 0 || function.generator)
+        {
+            return Err(format!("error: alarm method `{}` must be a normal function that takes no arguments", method));
+        }
+        self.alarm_cancel(instance, method)?;
+        self.insert_alarm(instance.0, method_index, ticks, period)
+    }
+    fn insert_alarm(&mut self, instance_id : u64, method : usize, ticks : u64, period : u64) -> Result<(), String>
+    {
+        let due = self.global.current_tick.checked_add(ticks).ok_or_else(|| minierr("error: tried to set an alarm past the last tick"))?;
+        let key = (due, self.global.timers_scheduled);
+        self.global.timers_scheduled += 1;
+        let inst = self.global.instances.get_mut(&instance_id).ok_or_else(|| minierr("internal error: tried to schedule an alarm on an instance that no longer exists"))?;
+        inst.alarms.insert(method, key);
+        self.global.timers.insert(key, Timer { instance : instance_id, method, period });
+        Ok(())
+    }
+}
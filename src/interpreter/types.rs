@@ -8,7 +8,7 @@ pub (crate) mod ops;
 
 pub (crate) use self::ops::*;
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 // note: for loops are controlled the same way as while loops
 
@@ -16,7 +16,6 @@ use std::collections::BTreeMap;
 pub (crate) struct WhileData {
     pub (super) variables: u64,
     pub (super) expr_start: usize, // continue destination
-    pub (super) loop_start: usize,
     pub (super) loop_end: usize, // continue from here
 }
 
@@ -24,8 +23,7 @@ pub (crate) struct WhileData {
 pub (crate) struct WithData {
     pub (super) variables: u64,
     pub (super) loop_start: usize,
-    pub (super) loop_end: usize,
-    pub (super) instances: Vec<Value>,
+    pub (super) instances: Vec<u64>, // the ones left to visit, last first
 }
 
 #[derive(Debug, Clone)]
@@ -44,7 +42,6 @@ pub (crate) struct ForEachData {
 
 #[derive(Debug, Clone)]
 pub (crate) struct SwitchData {
-    pub (super) variables: u64,
     pub (super) blocks: Vec<usize>,
     pub (super) exit: usize,
     pub (super) value: Value,
@@ -64,7 +61,7 @@ pub (crate) struct Frame {
     pub (super) stack: Vec<StackValue>,
     pub (super) variables: Vec<Value>,
     pub (super) controlstack: Vec<Controller>,
-    pub (super) instancestack: Box<Vec<usize>>,
+    pub (super) instancestack: Box<Vec<u64>>,
     pub (super) pc: usize,
     pub (super) isexpr: bool,
     pub (super) generator: bool,
@@ -79,21 +76,73 @@ pub (crate) struct FuncSpec {
     pub (crate) endaddr: usize,
     pub (crate) argcount: usize,
     pub (crate) parentobj: usize,
-    pub (crate) forcecontext: usize, // the instance to use as context when executing an object function
+    pub (crate) forcecontext: u64, // the instance to use as context when executing an object function
     pub (crate) fromobj: bool, // function is associated with an object type and must be placed in the context of an instance to be used
     pub (crate) generator: bool,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub (crate) struct ObjSpec {
     pub (crate) ident: usize,
     pub (crate) variables: BTreeMap<usize, usize>, // mapping of name to index, zeroth index is always "id" (instance id)
-    pub (crate) functions: BTreeMap<usize, FuncSpec>
+    pub (crate) functions: BTreeMap<usize, FuncSpec>,
+    pub (crate) static_functions: BTreeMap<usize, FuncSpec>, // called through the object type, e.g. Enemy.spawn(), without an instance
+    pub (crate) constants: BTreeSet<usize>, // names of object constants; their values are in GlobalState.object_constants
+    pub (crate) states: BTreeMap<usize, BTreeMap<usize, FuncSpec>>, // state name to the functions defined inside of it
+}
+
+impl ObjSpec {
+    // the current state's version of a function comes first, so states can override the object's own functions
+    pub (crate) fn method(&self, state : Option<usize>, name : usize) -> Option<&FuncSpec>
+    {
+        state.and_then(|state| self.states.get(&state)).and_then(|functions| functions.get(&name)).or_else(|| self.functions.get(&name))
+    }
+    // whether the object or any of its states has a function, regardless of which state an instance is in
+    pub (crate) fn has_method(&self, name : usize) -> bool
+    {
+        self.functions.contains_key(&name) || self.states.values().any(|functions| functions.contains_key(&name))
+    }
 }
 #[derive(Debug, Clone)]
 pub (crate) struct Instance {
-    pub (super) variables: BTreeMap<usize, Value>,
+    pub (super) variables: Vec<Value>, // indexed by the slots in the object type's ObjSpec.variables
+    pub (super) dynamic_variables: BTreeMap<usize, Value>, // ones that were added at runtime, by name
+    pub (super) tags: BTreeSet<usize>, // see Interpreter::instance_tag()
+    pub (super) state: Option<usize>, // see Interpreter::instance_goto_state()
+    pub (super) alarms: BTreeMap<usize, (u64, u64)>, // method name to its entry in GlobalState.timers; see Interpreter::alarm_set()
+    pub (super) dead: bool, // killed, but not removed yet
     pub (super) objtype: usize,
-    pub (super) ident: usize,
+    #[allow(dead_code)]
+    pub (super) ident: u64,
+    pub (super) created: u64, // see GlobalState.instances_created
+}
+
+#[derive(Debug, Clone)]
+pub (crate) struct Timer {
+    pub (super) instance: u64,
+    pub (super) method: usize,
+    pub (super) period: u64, // 0 if it only fires once
+}
+
+#[derive(Debug, Clone)]
+pub (crate) struct Message {
+    pub (super) instance: u64,
+    pub (super) handler: usize, // name of the on_<name> method that receives it
+    pub (super) payload: Value,
+}
+
+// instance handles are the instance's slot in the low 32 bits and the slot's generation in the high 32 bits
+// slots are reused once their instance is removed, but with the next generation, so handles of removed instances never refer to a new instance
+pub (crate) fn instance_handle(slot : usize, generation : u32) -> u64
+{
+    (u64::from(generation) << 32) | slot as u64
+}
+pub (crate) fn instance_slot(handle : u64) -> usize
+{
+    (handle & 0xFFFF_FFFF) as usize
+}
+pub (crate) fn instance_generation(handle : u64) -> u32
+{
+    (handle >> 32) as u32
 }
 
 // variable types (i.e. how to access a variable as an lvalue)
@@ -102,6 +151,7 @@ pub (crate) struct Instance {
 #[derive(Debug, Clone)]
 pub (crate) enum NonArrayVariable {
     Indirect(IndirectVar), // x.y.z evaluates x.y before storing it as the instance identity under which to find y, but then (x.y).z is held as-is
+    Slot(SlotVar),
     Global(usize),
     Direct(usize),
     ActualArray(Box<Vec<Value>>),
@@ -125,21 +175,35 @@ impl ArrayVar {
 
 #[derive(Debug, Clone)]
 pub (crate) struct IndirectVar { // for x.y
-    pub (super) ident: usize,
+    pub (super) ident: u64,
     pub (super) name: usize
 }
 
+#[derive(Debug, Clone)]
+pub (crate) struct SlotVar { // for instance variables whose slot was known when compiling
+    pub (super) ident: u64,
+    pub (super) slot: usize
+}
+
+#[derive(Debug, Clone)]
+pub (crate) struct PropertyVar { // for x.y where x is a custom value with a native class
+    pub (crate) source: Custom,
+    pub (crate) name: usize
+}
+
 #[derive(Debug, Clone)]
 pub (crate) enum Variable {
     Array(ArrayVar),
     Indirect(IndirectVar),
+    Slot(SlotVar),
+    Property(Box<PropertyVar>),
     BareGlobal(usize),
     Global(usize),
     Direct(usize)
 }
 
 impl Variable {
-    pub (crate) fn from_indirection(ident : usize, name : usize) -> Variable
+    pub (crate) fn from_indirection(ident : u64, name : usize) -> Variable
     {
         Variable::Indirect(IndirectVar{ident, name})
     }
@@ -179,8 +243,9 @@ pub struct SubFuncVal {
 }
 
 /// Stores typed values (e.g. variables after evaluation, raw literal values).
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub enum Value {
+    #[default]
     Null,
     Number(f64),
     Text(String),
@@ -190,20 +255,13 @@ pub enum Value {
     InternalFunc(InternalFuncVal),
     Func(Box<FuncVal>),
     Generator(Box<GeneratorState>),
-    Instance(usize),
+    Instance(u64),
     Object(usize),
     Custom(Custom),
     // cannot be assigned
     SubFunc(Box<SubFuncVal>),
 }
 
-impl Value {
-    pub fn default() -> Value
-    {
-        Value::Null
-    }
-}
-
 #[derive(Debug, Clone)]
 pub (crate) enum StackValue {
     Val(Value),
@@ -215,7 +273,7 @@ pub (crate) enum StackValue {
 pub enum HashableValue {
     Number(f64),
     Text(String),
-    Instance(usize),
+    Instance(u64),
 }
 
 // implementations
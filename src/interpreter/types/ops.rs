@@ -11,7 +11,7 @@ pub (crate) fn format_val(val : &Value) -> Option<String>
         Value::Array(array) =>
         {
             let mut ret = String::new();
-            ret.push_str("[");
+            ret.push('[');
             for (i, val) in array.iter().enumerate()
             {
                 if let Value::Text(text) = val
@@ -31,14 +31,14 @@ pub (crate) fn format_val(val : &Value) -> Option<String>
                     ret.push_str(", ");
                 }
             }
-            ret.push_str("]");
+            ret.push(']');
             
             Some(ret)
         }
         Value::Dict(dict) =>
         {
             let mut ret = String::new();
-            ret.push_str("{");
+            ret.push('{');
             for (i, (key, val)) in dict.iter().enumerate()
             {
                 if let HashableValue::Text(text) = key
@@ -73,7 +73,7 @@ pub (crate) fn format_val(val : &Value) -> Option<String>
                     ret.push_str(", ");
                 }
             }
-            ret.push_str("}");
+            ret.push('}');
             
             Some(ret)
         }
@@ -100,11 +100,13 @@ pub (crate) fn format_val(val : &Value) -> Option<String>
                     ret.push_str(", ");
                 }
             }
-            ret.push_str("}");
+            ret.push('}');
             
             Some(ret)
         }
-        Value::Instance(id) => Some(format!("<instance {}>", id)), // TODO: include object name?
+        // TODO: include object name?
+        Value::Instance(id) if instance_generation(*id) == 0 => Some(format!("<instance {}>", id)),
+        Value::Instance(id) => Some(format!("<instance {}:{}>", instance_slot(*id), instance_generation(*id))),
         Value::Object(id) => Some(format!("<object {}>", id)), // TODO: use name?
         Value::Func(_) => Some("<function>".to_string()),
         Value::InternalFunc(_) => Some("<internal function>".to_string()),
@@ -114,6 +116,18 @@ pub (crate) fn format_val(val : &Value) -> Option<String>
     }
 }
 
+// formats a piece of an interpolated string
+pub (crate) fn format_val_with_spec(val : &Value, spec : &FormatSpec) -> Result<String, String>
+{
+    let text = match (val, spec.precision)
+    {
+        (Value::Number(float), Some(precision)) => format!("{:.*}", precision, float),
+        (_, Some(_)) => return Err(minierr("error: format specifiers with a precision only work on numbers")),
+        _ => format_val(val).ok_or_else(|| minierr("error: tried to interpolate an unprintable value into a string"))?
+    };
+    Ok(spec.pad(text, matches!(val, Value::Number(_))))
+}
+
 #[inline]
 pub (crate) fn value_op_add(left : &Value, right : &Value) -> Result<Value, String>
 {
@@ -205,7 +219,7 @@ pub (crate) fn value_equal(left : &Value, right : &Value) -> Result<bool, String
             if_then_return_false!(left.len() != right.len());
             for (left_key, left_val) in left.iter()
             {
-                if let Some(right_val) = right.get(&left_key)
+                if let Some(right_val) = right.get(left_key)
                 {
                     if_then_return_false!(!value_equal(left_val, right_val)?);
                 }
@@ -221,7 +235,7 @@ pub (crate) fn value_equal(left : &Value, right : &Value) -> Result<bool, String
             if_then_return_false!(left.len() != right.len());
             for left_val in left.iter()
             {
-                if_then_return_false!(!right.contains(&left_val));
+                if_then_return_false!(!right.contains(left_val));
             }
             Ok(true)
         }
@@ -235,7 +249,8 @@ pub (crate) fn value_equal(left : &Value, right : &Value) -> Result<bool, String
         }
         // generators are never equal even in their default state
         (Value::Generator(_), Value::Generator(_)) => Ok(false),
-        (Value::Instance(left), Value::Instance(right)) | (Value::Object(left), Value::Object(right)) => Ok(left==right),
+        (Value::Instance(left), Value::Instance(right)) => Ok(left==right),
+        (Value::Object(left), Value::Object(right)) => Ok(left==right),
         (Value::Custom(left), Value::Custom(right)) => Ok(left.discrim == right.discrim && left.storage == right.storage),
         (Value::Null, Value::Null) => Ok(true),
         _ => Ok(false) // all non-matching type pairs test false
@@ -454,12 +469,12 @@ pub (crate) fn value_truthy(interpreter : &Interpreter, immediate : &Value) -> b
     match immediate
     {
         Value::Number(value) => float_booly(*value),
-        Value::Text(text) => text != "",
+        Value::Text(text) => !text.is_empty(),
         Value::Array(array) => !array.is_empty(),
         Value::Dict(dict) => !dict.is_empty(),
         Value::Set(set) => !set.is_empty(),
         Value::Generator(gen_state) => gen_state.frame.is_some(),
-        Value::Instance(instance_id) => interpreter.global.instances.contains_key(&instance_id),
+        Value::Instance(instance_id) => interpreter.instance_is_alive(*instance_id),
         _ => false
     }
 }
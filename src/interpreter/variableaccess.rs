@@ -31,7 +31,7 @@ impl<'a> ValueLoc<'a> {
     {
         match self
         {
-            ValueLoc::Static(v) => &v,
+            ValueLoc::Static(v) => v,
             ValueLoc::Immut(v) => v,
             ValueLoc::Mut(v) => v,
         }
@@ -151,6 +151,8 @@ impl Interpreter
             // FIXME borrow readonlyness 
             NonArrayVariable::Indirect(indirvar) =>
                 return_indexed(self.evaluate_of_indirect(indirvar)?, &arrayvar.indexes),
+            NonArrayVariable::Slot(slotvar) =>
+                return_indexed(self.evaluate_of_slot(slotvar)?, &arrayvar.indexes),
             NonArrayVariable::Direct(dirvar) =>
                 return_indexed(self.evaluate_of_direct(dirvar)?, &arrayvar.indexes),
             NonArrayVariable::Global(globalvar) =>
@@ -163,30 +165,57 @@ impl Interpreter
                 return_indexed(ValueLoc::Static(Value::Text(*string)), &arrayvar.indexes),
         }
     }
-    pub(crate) fn evaluate_of_indirect_simple(&self, ident : usize, name : usize) -> Result<Value, String>
+    pub(crate) fn evaluate_of_indirect_simple(&self, ident : u64, name : usize) -> Result<Value, String>
     {
         if !self.global.instances.contains_key(&ident)
         {
             return Err(format!("error: tried to access variable `{}` from non-extant instance `{}`", self.get_indexed_string(name), ident));
         }
         let instance = self.global.instances.get(&ident).unwrap();
+        let objspec = self.global.objects.get(&instance.objtype).ok_or_else(|| "internal error: tried to access non-extant object type".to_string())?;
         
-        if let Some(var) = instance.variables.get(&name)
+        // the name isn't known to be a variable until now, so its slot has to be looked up
+        if let Some(slot) = objspec.variables.get(&name)
+        {
+            Ok(instance.variables.get(*slot).ok_or_else(|| "internal error: instance variable slot out of range".to_string())?.clone())
+        }
+        else if let Some(var) = instance.dynamic_variables.get(&name)
         {
             Ok(var.clone())
         }
         else
         {
             // fallback to instance functions
-            let objspec = self.global.objects.get(&instance.objtype).ok_or_else(|| "internal error: tried to access non-extant object type".to_string())?;
-            
-            let funcdat = objspec.functions.get(&name).ok_or_else(|| format!("error: tried to read non-extant variable `{}` in instance `{}`", self.get_indexed_string(name), ident))?;
+            let funcdat = objspec.method(instance.state, name).ok_or_else(|| format!("error: tried to read non-extant variable `{}` in instance `{}`", self.get_indexed_string(name), ident))?;
             
             let mut mydata = funcdat.clone();
             mydata.forcecontext = ident;
             Ok(Value::new_funcval(None, mydata))
         }
     }
+    // static functions and constants of an object type, as accessed with Objname.name
+    pub(crate) fn evaluate_of_object_member(&self, object : usize, name : usize) -> Result<Value, String>
+    {
+        let objspec = self.global.objects.get(&object).ok_or_else(|| format!("error: tried to access member `{}` of non-extant object type {}", self.get_indexed_string(name), object))?;
+        
+        if let Some(funcdat) = objspec.static_functions.get(&name)
+        {
+            Ok(Value::new_funcval(None, funcdat.clone()))
+        }
+        else if objspec.constants.contains(&name)
+        {
+            self.global.object_constants.get(&(object, name)).cloned().ok_or_else(|| format!("error: tried to read object constant `{}.{}` before it was defined", self.get_indexed_string(object), self.get_indexed_string(name)))
+        }
+        else if objspec.states.contains_key(&name)
+        {
+            // states are referred to by name, e.g. for goto_state()
+            Ok(Value::Text(self.get_indexed_string(name)))
+        }
+        else
+        {
+            Err(format!("error: object type `{}` has no static function or constant named `{}`", self.get_indexed_string(object), self.get_indexed_string(name)))
+        }
+    }
     pub(crate) fn evaluate_of_indirect(&mut self, indirvar : IndirectVar) -> Result<ValueLoc<'_>, String>
     {
         let ident = indirvar.ident;
@@ -194,24 +223,38 @@ impl Interpreter
         {
             return Err(format!("error: tried to access variable `{}` from non-extant instance `{}`", self.get_indexed_string(indirvar.name), ident));
         }
+        let strings = &self.global.strings;
         let instance = self.global.instances.get_mut(&ident).unwrap();
+        let objspec = self.global.objects.get(&instance.objtype).ok_or_else(|| "internal error: tried to access non-extant object type".to_string())?;
         
-        if let Some(var) = instance.variables.get_mut(&indirvar.name)
+        if let Some(slot) = objspec.variables.get(&indirvar.name)
+        {
+            Ok(ValueLoc::Mut(instance.variables.get_mut(*slot).ok_or_else(|| "internal error: instance variable slot out of range".to_string())?))
+        }
+        else if !objspec.has_method(indirvar.name) && (self.global.dynamic_instance_variables || instance.dynamic_variables.contains_key(&indirvar.name))
         {
-            Ok(ValueLoc::Mut(var))
+            Ok(ValueLoc::Mut(instance.dynamic_variables.entry(indirvar.name).or_default()))
         }
         else
         {
             // fallback to instance functions
-            let objspec = self.global.objects.get(&instance.objtype).ok_or_else(|| "internal error: tried to access non-extant object type".to_string())?;
-            
-            let funcdat = objspec.functions.get(&indirvar.name).ok_or_else(|| "error: tried to read non-extant instance variable".to_string())?;
+            let funcdat = objspec.method(instance.state, indirvar.name).ok_or_else(|| format!("error: tried to access non-extant variable `{}` in instance `{}`", strings.get_string(indirvar.name).cloned().unwrap_or_default(), ident))?;
             
             let mut mydata = funcdat.clone();
             mydata.forcecontext = ident;
             Ok(ValueLoc::Static(Value::new_funcval(None, mydata)))
         }
     }
+    pub(crate) fn evaluate_of_slot_simple(&self, ident : u64, slot : usize) -> Result<Value, String>
+    {
+        let instance = self.global.instances.get(&ident).ok_or_else(|| format!("error: tried to access instance variable from non-extant instance `{}`", ident))?;
+        Ok(instance.variables.get(slot).ok_or_else(|| "internal error: instance variable slot out of range".to_string())?.clone())
+    }
+    pub(crate) fn evaluate_of_slot(&mut self, slotvar : SlotVar) -> Result<ValueLoc<'_>, String>
+    {
+        let instance = self.global.instances.get_mut(&slotvar.ident).ok_or_else(|| format!("error: tried to access instance variable from non-extant instance `{}`", slotvar.ident))?;
+        Ok(ValueLoc::Mut(instance.variables.get_mut(slotvar.slot).ok_or_else(|| "internal error: instance variable slot out of range".to_string())?))
+    }
     pub(crate) fn evaluate_of_global(&mut self, globalvar : usize) -> Result<ValueLoc<'_>, String>
     {
         if !self.global.variables.contains_key(&globalvar)
@@ -234,6 +277,8 @@ impl Interpreter
         {
             Variable::Array(arrayvar) => self.evaluate_of_array(arrayvar),
             Variable::Indirect(indirvar) => self.evaluate_of_indirect(indirvar),
+            Variable::Slot(slotvar) => self.evaluate_of_slot(slotvar),
+            Variable::Property(property) => self.native_get(&property.source, property.name).map(ValueLoc::Static),
             Variable::Global(globalvar) => self.evaluate_of_global(globalvar),
             Variable::BareGlobal(bareglobalvar) => self.evaluate_of_bareglobal(bareglobalvar),
             Variable::Direct(name) => self.evaluate_of_direct(name),
@@ -16,7 +16,7 @@
 
 #![allow(clippy::suspicious_else_formatting)]
 #![allow(clippy::redundant_closure)]
-#![allow(clippy::box_vec)] // we use Box<Vec> for arrays in order to reduce the size of the Value enum to 64 bytes
+#![allow(clippy::box_collection)] // we use Box<Vec> for arrays in order to reduce the size of the Value enum to 64 bytes
 
 #[macro_use]
 mod matches;
@@ -24,12 +24,16 @@ mod strings;
 mod regexholder;
 mod ast;
 mod parser;
+mod formatter;
+mod unparser;
 mod bytecode;
 mod grammar;
 mod compiler;
+mod program;
 mod interpreter;
+mod lsp;
 
-pub use crate::{parser::*, compiler::*, interpreter::*};
+pub use crate::{ast::ASTNode, parser::*, compiler::*, program::*, interpreter::*, lsp::LanguageServer};
 
 #[cfg(test)]
 mod tests {
@@ -39,6 +43,33 @@ mod tests {
     
     use super::*;
     
+    fn test_assert(args : Vec<Value>) -> Result<Value, String>
+    {
+        match args.first()
+        {
+            Some(Value::Number(num)) if *num != 0.0 => Ok(Value::default()),
+            _ => Err(format!("error: assertion failed ({:?})", args))
+        }
+    }
+    
+    fn assert_interpreter() -> Result<Interpreter, String>
+    {
+        let mut interpreter = Interpreter::new(Parser::new_from_default()?);
+        interpreter.insert_default_bindings();
+        interpreter.insert_trivial_simple_binding("assert".to_string(), test_assert);
+        Ok(interpreter)
+    }
+    
+    fn run_with_assert(interpreter : &mut Interpreter) -> Result<(), String>
+    {
+        interpreter.step_until_error_or_exit().ok();
+        match &interpreter.last_error
+        {
+            Some(err) => Err(err.clone()),
+            None => Ok(())
+        }
+    }
+    
     #[test]
     fn test_everything() -> Result<(), String>
     {
@@ -47,7 +78,7 @@ mod tests {
         interpreter.insert_default_bindings();
 
         let mut program = String::new();
-        File::open("examples/general.txt").or_else(|_| Err("failed to open program".to_string()))?.read_to_string(&mut program).or_else(|_| Err("failed to read program into memory".to_string()))?;
+        File::open("examples/general.txt").map_err(|_| "failed to open program".to_string())?.read_to_string(&mut program).map_err(|_| "failed to read program into memory".to_string())?;
         
         interpreter.restart_into_string(&program)?;
         
@@ -98,7 +129,7 @@ mod tests {
         interpreter.insert_default_bindings();
 
         let mut program = String::new();
-        File::open("examples/nbody.txt").or_else(|_| Err("failed to open program".to_string()))?.read_to_string(&mut program).or_else(|_| Err("failed to read program into memory".to_string()))?;
+        File::open("examples/nbody.txt").map_err(|_| "failed to open program".to_string())?.read_to_string(&mut program).map_err(|_| "failed to read program into memory".to_string())?;
         
         interpreter.restart_into_string(&program)?;
         
@@ -116,7 +147,8 @@ mod tests {
         println!("simulation took {:?}", duration);
         println!("steps {:?}", steps);
         println!("{:?} steps per second", steps as f64 / (duration.as_millis() as f64 / 1000.0));
-        println!("{:?} nanooseconds per step", duration.as_millis() as f64 * 1000_000.0 / steps as f64);
+        println!("{:?} nanooseconds per step", duration.as_millis() as f64 * 1_000_000.0 / steps as f64);
+        #[cfg(feature = "track_op_performance")]
         interpreter.print_op_perf_log();
         
         if let Some(err) = &interpreter.last_error
@@ -127,6 +159,1051 @@ mod tests {
         Ok(())
     }
     
+    #[test]
+    fn test_shared_program() -> Result<(), String>
+    {
+        let mut compiler = assert_interpreter()?;
+        
+        let program = compiler.compile_program("
+            globalvar runs;
+            global.runs = 0;
+            obj Thing { def create() { global.runs += 1; } }
+            assert(object_count(Thing) == 0);
+            instance_create(Thing);
+            instance_create(Thing);
+            assert(object_count(Thing) == 2);
+            assert(global.runs == 2);
+        ")?;
+        
+        // compiling a program doesn't leak its objects into the interpreter that compiled it
+        assert!(compiler.compile_program("instance_create(Thing);").is_err());
+        
+        for _ in 0..2
+        {
+            let mut interpreter = Interpreter::new(Parser::new_from_default()?);
+            // different binding order, so that binding names get different string indexes than in the compiling interpreter
+            interpreter.insert_trivial_simple_binding("assert".to_string(), test_assert);
+            interpreter.insert_default_bindings();
+            interpreter.load_program(&program);
+            run_with_assert(&mut interpreter)?;
+            
+            // reloading gives a fresh set of instances and globals
+            interpreter.load_program(&program);
+            run_with_assert(&mut interpreter)?;
+        }
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_threads() -> Result<(), String>
+    {
+        let mut compiler = assert_interpreter()?;
+        let program = compiler.compile_program("
+            var total = 0;
+            for(var i = 0; i < 1000; i += 1) { total += i; }
+            assert(total == 499500);
+        ")?;
+        
+        // programs can always be shared between threads
+        let workers : Vec<_> = (0..4).map(|_|
+        {
+            let program = program.clone();
+            std::thread::spawn(move ||
+            {
+                let mut interpreter = Interpreter::new(Parser::default());
+                interpreter.insert_trivial_simple_binding("assert".to_string(), test_assert);
+                interpreter.load_program(&program);
+                run_with_assert(&mut interpreter)
+            })
+        }).collect();
+        for worker in workers
+        {
+            worker.join().unwrap()?;
+        }
+        
+        // interpreters themselves can only be moved to other threads with the thread_safe feature
+        #[cfg(feature = "thread_safe")]
+        {
+            let mut interpreter = Interpreter::new(Parser::default());
+            let results = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+            let results_inner = std::sync::Arc::clone(&results);
+            interpreter.insert_simple_binding("assert".to_string(), binding_cell(move |args : Vec<Value>|
+            {
+                results_inner.lock().unwrap().push(args.len());
+                test_assert(args)
+            }));
+            interpreter.load_program(&program);
+            std::thread::spawn(move || run_with_assert(&mut interpreter)).join().unwrap()?;
+            assert_eq!(*results.lock().unwrap(), vec!(1));
+        }
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_register() -> Result<(), String>
+    {
+        use std::collections::HashMap;
+        
+        fn run(text : &str) -> Result<(), String>
+        {
+            let mut interpreter = assert_interpreter()?;
+            interpreter.register("lerp", |a : f64, b : f64, t : f64| a + (b - a) * t);
+            interpreter.register("repeat", |text : String, count : f64| text.repeat(count as usize));
+            interpreter.register("sum", |list : Vec<f64>| list.iter().sum::<f64>());
+            interpreter.register("lookup", |dict : HashMap<String, f64>, key : String| dict.get(&key).copied());
+            interpreter.register("same_object", |a : InstanceId, b : ObjectId| -> Result<bool, String> { Ok(a.0 != 0 && b.0 != 0) });
+            interpreter.restart_into_string(text)?;
+            // only the first line, without the location info
+            run_with_assert(&mut interpreter).map_err(|err| err.lines().next().unwrap_or("").to_string())
+        }
+        
+        run("
+            assert(lerp(2, 4, 0.5) == 3);
+            assert(repeat(\"ab\", 3) == \"ababab\");
+            assert(sum([1, 2, 3]) == 6);
+            assert(lookup({\"a\" : 5}, \"a\") == 5);
+            assert(lookup({\"a\" : 5}, \"b\") == null);
+            obj Thing { }
+            assert(same_object(instance_create(Thing), Thing));
+        ")?;
+        
+        assert_eq!(run("lerp(1, 2);").unwrap_err(), "error: wrong number of arguments to lerp(); expected 3, got 2");
+        assert_eq!(run("repeat(\"a\", \"b\");").unwrap_err(), "error: second argument to repeat() must be a number");
+        assert_eq!(run("sum([1, \"2\"]);").unwrap_err(), "error: first argument to sum() must be an array (of a number)");
+        assert_eq!(run("instance_exists(1, 2);").unwrap_err(), "error: wrong number of arguments to instance_exists(); expected 1, got 2");
+        assert_eq!(run("instance_object(1);").unwrap_err(), "error: first argument to instance_object() must be an instance");
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_native_class() -> Result<(), String>
+    {
+        use std::sync::{Arc, Mutex};
+        
+        // sprite positions live on the application's side; custom values just refer to them by index
+        let positions = Arc::new(Mutex::new(Vec::<f64>::new()));
+        let finalized = Arc::new(Mutex::new(Vec::<u64>::new()));
+        
+        let mut interpreter = assert_interpreter()?;
+        
+        let positions_inner = Arc::clone(&positions);
+        interpreter.register("sprite_create", move |x : f64|
+        {
+            let mut positions = positions_inner.lock().unwrap();
+            positions.push(x);
+            Custom { discrim : 1, storage : positions.len() as u64 - 1 }
+        });
+        
+        let mut class = NativeClass::new("Sprite");
+        let positions_inner = Arc::clone(&positions);
+        class.insert_getter("x".to_string(), binding_cell(move |me : &Custom| Ok(Value::Number(positions_inner.lock().unwrap()[me.storage as usize]))));
+        let positions_inner = Arc::clone(&positions);
+        class.insert_setter("x".to_string(), binding_cell(move |me : &Custom, value : Value|
+        {
+            positions_inner.lock().unwrap()[me.storage as usize] = f64::from_value(value).ok_or_else(|| "error: sprite x must be a number".to_string())?;
+            Ok(())
+        }));
+        let positions_inner = Arc::clone(&positions);
+        class.insert_method("scaled".to_string(), binding_cell(move |me : &Custom, mut args : Vec<Value>|
+        {
+            args.expect_count("scaled", 1)?;
+            let scale : f64 = args.extract_arg("scaled", 0)?;
+            Ok(Value::Number(positions_inner.lock().unwrap()[me.storage as usize] * scale))
+        }));
+        let finalized_inner = Arc::clone(&finalized);
+        class.set_finalizer(binding_cell(move |me : &Custom|
+        {
+            finalized_inner.lock().unwrap().push(me.storage);
+            Ok(())
+        }));
+        interpreter.insert_native_class(1, class);
+        
+        interpreter.restart_into_string("
+            var a = sprite_create(3);
+            var b = sprite_create(10);
+            assert(a.x == 3);
+            a.x = 5;
+            a.x += 2;
+            a.x++;
+            assert(a.x == 8 and b.x == 10);
+            assert(a->scaled(2) == 16);
+            assert(a->typeof_str() == \"Sprite\");
+            assert(a->discriminator() == 1);
+            b->finalize();
+            var c = a;
+            c.x = 0;
+            assert(a.x == 0);
+            a.y = 1;
+        ")?;
+        let err = run_with_assert(&mut interpreter).unwrap_err();
+        assert!(err.starts_with("error: native class `Sprite` has no writable property `y`"), "{}", err);
+        
+        assert_eq!(*positions.lock().unwrap(), vec!(0.0, 10.0));
+        assert_eq!(*finalized.lock().unwrap(), vec!(1));
+        interpreter.finalize_native(&Custom { discrim : 1, storage : 0 })?;
+        assert_eq!(*finalized.lock().unwrap(), vec!(1, 0));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_binding_registry() -> Result<(), String>
+    {
+        let mut interpreter = assert_interpreter()?;
+        interpreter.register("audio.volume", || 0.5);
+        interpreter.register("gfx.text.width", |text : String| text.len() as f64);
+        interpreter.register("file.read", |_path : String| "contents".to_string());
+        assert!(interpreter.set_binding_capability(BindingKind::Function, "file.read", "file_io"));
+        
+        interpreter.restart_into_string("
+            assert(audio.volume() == 0.5);
+            assert(gfx.text.width(\"four\") == 4);
+            var gfx = [1, 2];
+            assert(gfx->len() == 2);
+            assert(file.read(\"x\") == \"contents\");
+        ")?;
+        run_with_assert(&mut interpreter)?;
+        
+        let info = interpreter.list_bindings();
+        assert!(info.contains(&BindingInfo { name : "file.read".to_string(), kind : BindingKind::Function, capability : Some("file_io".to_string()), arity : Some(1) }));
+        assert!(info.contains(&BindingInfo { name : "len".to_string(), kind : BindingKind::Arrow, capability : None, arity : Some(0) }));
+        
+        // denied capabilities make calls fail, even in code that was compiled before they were denied
+        interpreter.deny_capability("file_io");
+        interpreter.restart_in_place();
+        let err = run_with_assert(&mut interpreter).unwrap_err();
+        assert!(err.starts_with("error: tried to call binding `file.read`, but its capability `file_io` has been denied"), "{}", err);
+        interpreter.allow_capability("file_io");
+        
+        assert!(interpreter.remove_binding(BindingKind::Arrow, "len"));
+        assert!(!interpreter.remove_binding(BindingKind::Arrow, "len"));
+        interpreter.restart_in_place();
+        let err = run_with_assert(&mut interpreter).unwrap_err();
+        assert!(err.starts_with("error: no such arrow function `len`"), "{}", err);
+        
+        assert!(interpreter.remove_binding(BindingKind::Function, "audio.volume"));
+        assert!(interpreter.restart_into_string("audio.volume();").is_err());
+        
+        // looking up names that were never registered doesn't add them to the string table
+        assert!(!interpreter.remove_binding(BindingKind::Function, "no.such.binding"));
+        assert!(!interpreter.set_binding_capability(BindingKind::Function, "no.such.binding", "file_io"));
+        assert!(!interpreter.set_binding_arity(BindingKind::Function, "no.such.binding", 1));
+        assert!(interpreter.find_string_index("no.such.binding").is_none());
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_deep_nesting() -> Result<(), String>
+    {
+        // without the parser's packrat cache, each level of nesting doubles the parse time
+        let mut interpreter = assert_interpreter()?;
+        let depth = 40;
+        let expr = format!("{}[1][0]{}", "(".repeat(depth), " + 1)".repeat(depth));
+        interpreter.restart_into_string(&format!("assert({} == {});", expr, depth + 1))?;
+        run_with_assert(&mut interpreter)
+    }
+    
+    #[test]
+    fn test_string_literals() -> Result<(), String>
+    {
+        let mut interpreter = assert_interpreter()?;
+        interpreter.restart_into_string("
+            var multiline = \"one
+two\";
+            assert(multiline == \"one\\ntwo\");
+            /* a comment
+               over several lines */
+            assert(r\"C:\\n\" == \"C:\\\\n\");
+            var block = \"\"\"
+                Hello, \"traveler\".
+                  Indented.
+                \"\"\";
+            assert(block == \"Hello, \\\"traveler\\\".\\n  Indented.\");
+        ")?;
+        run_with_assert(&mut interpreter)?;
+        
+        // columns are counted in codepoints, on the line the token is on
+        let err = interpreter.restart_into_string("var s = \"あ\nい\"; var ü;").unwrap_err();
+        assert!(err.starts_with("failed to tokenize program\noffending line (2):\nい\"; var ü;\n        ^"), "{}", err);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_escape_sequences() -> Result<(), String>
+    {
+        let mut interpreter = assert_interpreter()?;
+        interpreter.restart_into_string(r#"
+            assert("\u{1F600}" == "😀");
+            assert("\x41\u{42}" == "AB");
+            assert("a\0b"->len() == 3);
+            assert($"\{\u{43}\}" == "{C}");
+            assert(string(["\x07\t"]) == "[\"\\x07\\t\"]");
+        "#)?;
+        run_with_assert(&mut interpreter)?;
+        
+        let mut err = |text : &str| interpreter.restart_into_string(text).unwrap_err();
+        assert_eq!(err("var a;\nvar s = \"ok \\q\";"), "error: invalid escape sequence `\\q` on line 2, position 13");
+        assert_eq!(err("var s = \"\\x80\";"), "error: invalid escape sequence `\\x80` on line 1, position 10");
+        assert_eq!(err("var s = \"\\u{110000}\";"), "error: invalid escape sequence `\\u{110000}` on line 1, position 10");
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_string_interpolation() -> Result<(), String>
+    {
+        let mut interpreter = assert_interpreter()?;
+        interpreter.restart_into_string("
+            var hp = 7;
+            var maxhp = 10.5;
+            assert($\"HP: {hp}/{maxhp}\" == \"HP: 7/10.5\");
+            assert($\"[{hp:>4}][{hp:<4}][{-hp:04}][{maxhp:.2}][{\"ab\":>3}]\" == \"[   7][7   ][-007][10.50][ ab]\");
+            assert($\"{{\"k\": [1, 2]}[\"k\"]->len()} \\{braces\\} {$\"{hp}\"}\" == \"2 {braces} 7\");
+        ")?;
+        run_with_assert(&mut interpreter)?;
+        
+        assert!(interpreter.restart_into_string("print($\"{1:1e5}\");").unwrap_err().starts_with("error: invalid format specifier `1e5`"));
+        interpreter.restart_into_string("print($\"{\"a\":.2}\");")?;
+        assert!(run_with_assert(&mut interpreter).unwrap_err().starts_with("error: format specifiers with a precision only work on numbers"));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_grammar_token_attributes() -> Result<(), String>
+    {
+        let grammar = "
+program:
+$item$*
+
+item:
+$word$
+$numbered$
+$quote$
+
+reserved: TOKEN
+stop
+
+word: TOKEN EXCLUDE reserved
+%[a-z]+%
+
+numbered: TOKEN PRIORITY 1
+%[a-z]+[0-9]+%
+
+quote:
+$quotestart$ $quotetext$? $quoteend$
+
+quotestart: TOKEN PUSH quoted
+%<%
+
+quotetext: TOKEN MODE quoted
+%[^>]+%
+
+quoteend: TOKEN MODE quoted POP
+%>%
+";
+        let mut parser = Parser::new_from_grammar(grammar)?;
+        let text = "ab cd12 < x // y > ef";
+        let tokens = parser.tokenize(text, true)?;
+        let texts : Vec<&str> = tokens.iter().map(|token| token.text.as_str()).collect();
+        assert_eq!(texts, vec!("ab", "cd12", "<", " x // y ", ">", "ef"));
+        assert!(parser.parse_program(&tokens, &[text.to_string()], true)?.is_some());
+        
+        // reserved words can't be words
+        let tokens = parser.tokenize("ab stop", true)?;
+        assert!(parser.parse_program(&tokens, &["ab stop".to_string()], true)?.is_none());
+        
+        assert!(parser.tokenize("ab < cd", true).unwrap_err().starts_with("failed to tokenize program: program ended in lexer mode `quoted`"));
+        
+        let broken = |from : &str, to : &str| Parser::new_from_grammar(&grammar.replace(from, to)).err().unwrap_or_default();
+        assert_eq!(broken("PRIORITY 1", "PRIORITY x"), "error: PRIORITY argument must be an integer");
+        assert_eq!(broken("PUSH quoted", "PUSH elsewhere"), "error: rule `quotestart` pushes lexer mode `elsewhere`, which has no tokens");
+        assert_eq!(broken("EXCLUDE reserved", "EXCLUDE numbered"), "error: rule `word` excludes `numbered`, which must be a TOKEN rule made of plain words");
+        assert_eq!(broken("item:", "item: TOKEN MODE quoted"), "error: rule `item` is in lexer mode `quoted`, so it can only be made of regexes");
+        assert_eq!(broken("program:", "program: POP"), "error: rule `program` uses PRIORITY, EXCLUDE, MODE, PUSH, or POP without being a TOKEN rule");
+        assert_eq!(broken("program:", "program: BOGUS"), "error: unknown attribute `BOGUS` on rule `program`");
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_error_recovery() -> Result<(), String>
+    {
+        let mut parser = Parser::new_from_default()?;
+        
+        let mut program : String = "".to_string();
+        File::open("examples/general.txt").map_err(|_| "failed to open program".to_string())?.read_to_string(&mut program).map_err(|_| "failed to read program into memory".to_string())?;
+        let (_, diagnostics) = parser.parse_with_recovery(&program)?;
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        
+        let program = "var a = ;
+print(\"ok\");
+def f() { var b = 1 + ; return b; }
+var c = 3
+print(c);
+}
+if (c { print(1); }
+print(c ` 1);
+";
+        let (ast, diagnostics) = parser.parse_with_recovery(program)?;
+        let locations : Vec<(usize, usize)> = diagnostics.iter().map(|diagnostic| (diagnostic.line, diagnostic.position)).collect();
+        assert_eq!(locations, vec!((1, 9), (3, 23), (5, 1), (6, 1), (7, 14), (8, 9), (8, 11)));
+        assert_eq!(diagnostics[0].message, "error: expected one of `!`, `(`, `+`, `-`, `[`, `false`, `interpstart`, `invoke`, `name`, `null`, `number`, `set`, `string`, `true`, `{`");
+        assert_eq!(diagnostics[3].message, "error: unexpected `}`");
+        assert_eq!(diagnostics[5].message, "error: unexpected character");
+        
+        // valid statements around the errors are still there, including the function with an error inside it
+        let kinds : Vec<&str> = ast.children().iter().map(|node| node.text()).collect();
+        assert_eq!(kinds, vec!("error", "statement", "statement", "error", "error", "error", "error"));
+        assert_eq!(ast.children()[2].children()[0].text(), "funcdef");
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_language_server() -> Result<(), String>
+    {
+        let mut interpreter = Interpreter::new(Parser::new_from_default()?);
+        interpreter.insert_default_bindings();
+        let mut server = LanguageServer::new(interpreter);
+        
+        let document = r#""textDocument":{"uri":"file:///a.txt"}"#;
+        let messages = [
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#.to_string(),
+            r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"file:///a.txt","text":"var count = 1;\nobj Thing { var hp; def hurt(n) { hp -= n; } }\ncount += missing;\n[1]->le"}}}"#.to_string(),
+            format!(r#"{{"jsonrpc":"2.0","id":6,"method":"textDocument/completion","params":{{{},"position":{{"line":3,"character":7}}}}}}"#, document),
+            format!(r#"{{"jsonrpc":"2.0","method":"textDocument/didChange","params":{{{},"contentChanges":[{{"text":"var count = 1;\nobj Thing {{ var hp; def hurt(n) {{ hp -= n; }} }}\ncount += missing;\nprint(count);"}}]}}}}"#, document),
+            format!(r#"{{"jsonrpc":"2.0","id":2,"method":"textDocument/definition","params":{{{},"position":{{"line":3,"character":8}}}}}}"#, document),
+            format!(r#"{{"jsonrpc":"2.0","id":3,"method":"textDocument/hover","params":{{{},"position":{{"line":1,"character":35}}}}}}"#, document),
+            format!(r#"{{"jsonrpc":"2.0","id":4,"method":"textDocument/completion","params":{{{},"position":{{"line":3,"character":0}}}}}}"#, document),
+            format!(r#"{{"jsonrpc":"2.0","method":"textDocument/didChange","params":{{{},"contentChanges":[{{"text":"var unused = 1;"}}]}}}}"#, document),
+            r#"{"jsonrpc":"2.0","id":5,"method":"shutdown"}"#.to_string(),
+            r#"{"jsonrpc":"2.0","method":"exit"}"#.to_string(),
+        ];
+        let mut input = String::new();
+        for message in &messages
+        {
+            input += &format!("Content-Length: {}\r\n\r\n{}", message.len(), message);
+        }
+        let mut output = Vec::new();
+        server.run(std::io::Cursor::new(input), &mut output)?;
+        let output = String::from_utf8(output).map_err(|_| "output is not utf-8".to_string())?;
+        
+        // syntax errors first, then compile errors once the syntax is fixed
+        assert!(output.contains(r#""diagnostics":[{"range":{"start":{"line":3,"character":7},"end":{"line":3,"character":7}},"severity":1,"source":"gammakit","message":"error: expected one of `(`, `->`, `.`, `[`, `{`"}]"#), "{}", output);
+        assert!(output.contains(r#""diagnostics":[{"range":{"start":{"line":2,"character":9},"end":{"line":2,"character":16}},"severity":1,"source":"gammakit","message":"error: unknown identifier `missing`"}]"#), "{}", output);
+        assert!(output.contains(r#"{"jsonrpc":"2.0","id":2,"result":{"uri":"file:///a.txt","range":{"start":{"line":0,"character":4},"end":{"line":0,"character":9}}}}"#), "{}", output);
+        assert!(output.contains(r#""severity":2,"source":"gammakit","message":"warning: unused variable `unused`""#), "{}", output);
+        assert!(output.contains(r#"{"jsonrpc":"2.0","id":3,"result":{"contents":{"kind":"plaintext","value":"instance variable `hp` (defined on line 2)"}"#), "{}", output);
+        assert!(output.contains(r#"{"label":"Thing","kind":7}"#) && output.contains(r#"{"label":"print","kind":3}"#), "{}", output);
+        assert!(output.ends_with("Content-Length: 38\r\n\r\n{\"jsonrpc\":\"2.0\",\"id\":5,\"result\":null}"), "{}", output);
+        
+        // arrow functions are completed after ->, and nothing else is
+        assert!(output.contains(r#"{"jsonrpc":"2.0","id":6,"result":[{"label":"contains","kind":2},"#), "{}", output);
+        assert!(output.contains(r#"{"label":"len","kind":2}"#), "{}", output);
+        
+        // the server was shut down at the end
+        let replies = server.handle_message(r#"{"jsonrpc":"2.0","id":7,"method":"initialize"}"#);
+        assert!(replies[0].contains("\"error\":{\"code\":-32600"));
+        
+        // deeply nested JSON and huge messages are refused instead of overflowing the stack or running out of memory
+        let replies = server.handle_message(&"[".repeat(100_000));
+        assert!(replies[0].contains("\"error\":{\"code\":-32700") && replies[0].contains("nested too deeply"), "{:?}", replies);
+        let err = server.run(std::io::Cursor::new("Content-Length: 99999999999\r\n\r\n"), Vec::new()).unwrap_err();
+        assert!(err.starts_with("error: Content-Length 99999999999 is larger than the maximum"), "{}", err);
+        
+        Ok(())
+    }
+    
+    // compares ASTs, ignoring where their nodes are in the program text
+    fn same_tree(a : &ASTNode, b : &ASTNode) -> bool
+    {
+        a.text() == b.text() && a.is_parent() == b.is_parent() && a.children().len() == b.children().len()
+        && a.children().iter().zip(b.children()).all(|(a, b)| same_tree(a, b))
+    }
+    
+    #[test]
+    fn test_formatter() -> Result<(), String>
+    {
+        let mut parser = Parser::new_from_default()?;
+        for name in &["examples/general.txt", "examples/generator.txt", "examples/nbody.txt"]
+        {
+            let mut program : String = "".to_string();
+            File::open(name).map_err(|_| "failed to open program".to_string())?.read_to_string(&mut program).map_err(|_| "failed to read program into memory".to_string())?;
+            let formatted = parser.format(&program)?;
+            assert_eq!(parser.format(&formatted)?, formatted, "formatting {} is not idempotent", name);
+            
+            let mut asts = Vec::new();
+            let mut comments = Vec::new();
+            for text in &[&program, &formatted]
+            {
+                let lines : Vec<String> = text.lines().map(|x| x.to_string()).collect();
+                let tokens = parser.tokenize(text, true)?;
+                asts.push(parser.parse_program(&tokens, &lines, true)?.ok_or_else(|| format!("failed to parse {}", name))?);
+                let mut found = Vec::new();
+                parser.tokenize_with_comments(text, &mut found)?;
+                comments.push(found.into_iter().map(|comment| comment.text).collect::<Vec<_>>());
+            }
+            assert!(same_tree(&asts[0], &asts[1]), "formatting {} changed its AST", name);
+            assert_eq!(comments[0], comments[1]);
+        }
+        
+        assert!(parser.format("var x = ;").is_err());
+        assert_eq!(parser.format("if(a){print(1);}else if(b)  print(- -a);// done\nvar f = [](){};")?,
+            "if(a)\n{\n    print(1);\n}\nelse if(b)\n    print(- -a); // done\nvar f = [](){};\n");
+        assert_eq!(parser.format("f(a, b /* second */ , c);\nf(a, // first\nb, c);\nvar x = a + // y\nb;")?,
+            "f(a, b /* second */, c);\nf(a, // first\n    b, c);\nvar x = a + // y\n    b;\n");
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_unparse() -> Result<(), String>
+    {
+        let mut parser = Parser::new_from_default()?;
+        for name in &["examples/general.txt", "examples/generator.txt", "examples/nbody.txt"]
+        {
+            let mut program : String = "".to_string();
+            File::open(name).map_err(|_| "failed to open program".to_string())?.read_to_string(&mut program).map_err(|_| "failed to read program into memory".to_string())?;
+            let mut asts = Vec::new();
+            for _ in 0..2
+            {
+                let lines : Vec<String> = program.lines().map(|x| x.to_string()).collect();
+                let tokens = parser.tokenize(&program, true)?;
+                let ast = parser.parse_program(&tokens, &lines, true)?.ok_or_else(|| format!("failed to parse {}", name))?;
+                program = parser.unparse(&ast)?;
+                asts.push(ast);
+            }
+            assert!(same_tree(&asts[0], &asts[1]), "unparsing {} changed its AST", name);
+        }
+        
+        let mut interpreter = Interpreter::new(parser);
+        interpreter.insert_default_bindings();
+        interpreter.insert_trivial_simple_binding("assert".to_string(), test_assert);
+        interpreter.restart_into_string(r#"
+            var ast = parse_text("var x=[1,2];if(x[0]) print($\"{x[1]:>3}!\");");
+            ast["children"][0]["children"][0]["children"][1]["children"][0]["children"][0]["text"] = "y";
+            var text = unparse_ast(ast);
+            assert(text == "var y = [1, 2];\nif(x[0])\n    print($\"{x[1]:>3}!\");\n");
+            assert(unparse_ast(parse_text(text)) == text);
+        "#)?;
+        run_with_assert(&mut interpreter)?;
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_checker() -> Result<(), String>
+    {
+        let mut interpreter = Interpreter::new(Parser::new_from_default()?);
+        interpreter.insert_default_bindings();
+        interpreter.register("audio.play", |_sound : String| 0.0);
+        
+        // the examples don't have any errors in them
+        for name in &["examples/general.txt", "examples/generator.txt", "examples/nbody.txt"]
+        {
+            let mut program : String = "".to_string();
+            File::open(name).map_err(|_| "failed to open program".to_string())?.read_to_string(&mut program).map_err(|_| "failed to read program into memory".to_string())?;
+            let diagnostics = interpreter.check_program(&program)?;
+            assert!(diagnostics.iter().all(|diagnostic| diagnostic.severity == Severity::Warning), "{:?}", diagnostics);
+        }
+        
+        let program = "var unused = 1;
+var x = 2;
+print(y);
+def f(a, b) { return a + b; print(a); }
+f(1);
+audio.play(\"boom\", 1);
+[]->push(1, 2);
+def g() { yield 1; }
+generator h() { yield 1; }
+while(true) { for(var i = 0; i < 3; i += 1) { var x = i; print(x); } }
+break;
+obj Foo { var hp; def hurt(n) { var hp = n; print(hp); } }
+with(Foo) { hurt(); }
+var l = [q = x](z) { return lambda_self(z); };
+var _ignored = 3;
+obj Bar { const N = 2; static def make(n) { return n * N; } }
+Bar.make(); print(Bar.NOPE);
+";
+        let found : Vec<(usize, usize, String)> = interpreter.check_program(program)?.into_iter().map(|diagnostic| (diagnostic.line, diagnostic.position, diagnostic.message)).collect();
+        let expected = [
+            (1, 5, "warning: unused variable `unused`"),
+            (3, 7, "error: unknown identifier `y`"),
+            (4, 29, "warning: unreachable code"),
+            (5, 1, "error: wrong number of arguments to f(); expected 2, got 1"),
+            (6, 1, "error: wrong number of arguments to audio.play(); expected 1, got 2"),
+            (7, 1, "error: wrong number of arguments to push(); expected 1, got 2"),
+            (8, 11, "error: `yield` inside of a function that isn't a generator; use return instead"),
+            (10, 51, "warning: `x` shadows a variable from an outer scope"),
+            (11, 1, "error: `break` outside of a while or for loop"),
+            (12, 37, "warning: `hp` shadows an instance variable"),
+            (13, 13, "error: wrong number of arguments to hurt(); expected 1, got 0"),
+            (14, 5, "warning: unused variable `l`"),
+            (14, 10, "warning: unused variable `q`"),
+            (17, 1, "error: wrong number of arguments to Bar.make(); expected 1, got 0"),
+            (17, 23, "error: object type `Bar` has no static function or constant named `NOPE`"),
+        ];
+        assert_eq!(found, expected.iter().map(|(line, position, message)| (*line, *position, message.to_string())).collect::<Vec<_>>());
+        
+        // the checks are done by the compiler, so they show up in analyze_program() as well, and an unknown identifier doesn't hide the rest of its statement
+        let diagnostics = interpreter.analyze_program("var z = nope + 1;\nprint(z, also_nope);\nvar w;")?.diagnostics;
+        let found : Vec<(usize, usize, Severity)> = diagnostics.iter().map(|diagnostic| (diagnostic.line, diagnostic.position, diagnostic.severity)).collect();
+        assert_eq!(found, vec!((1, 9, Severity::Error), (2, 10, Severity::Error), (3, 5, Severity::Warning)), "{:?}", diagnostics);
+        
+        // syntax errors are reported instead of anything else
+        let diagnostics = interpreter.check_program("var a = ;\nprint(b);")?;
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.starts_with("error: expected one of"));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_instance_slots() -> Result<(), String>
+    {
+        let mut interpreter = assert_interpreter()?;
+        interpreter.restart_into_string("
+            obj Counter
+            {
+                var count, history;
+                def create() { count = 0; history = [0]; }
+                def bump(n) { count += n; history->push(count); history[0] = n; return get(); }
+                def get() { return count; }
+            }
+            obj Other { var hp; }
+            var c = instance_create(Counter);
+            assert(c.bump(2) == 2 and c.bump(3) == 5);
+            with(Counter) { count *= 2; history[1] = -1; }
+            // dynamic indirection still works, and sees the same storage
+            assert(c.count == 10 and c.history[0] == 3 and c.history[1] == -1 and c.id == c);
+            c.count = 1;
+            with(c as Counter) { assert(get() == 1 and self.count == 1); }
+            assert(instance_has_variable(c, \"history\") and !instance_has_variable(c, \"bump\"));
+            with(c as Other) { hp = 1; }
+        ")?;
+        let err = run_with_assert(&mut interpreter).unwrap_err();
+        assert!(err.starts_with("error: tried to use an instance of `Counter` as `Other` in with()"), "{}", err);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_instance_reflection() -> Result<(), String>
+    {
+        let mut interpreter = assert_interpreter()?;
+        interpreter.restart_into_string("
+            obj Door { var locked, target; def open() { return !locked; } def describe() { return self.label; } }
+            var door = instance_create(Door);
+            instance_set(door, \"locked\", true);
+            assert(instance_get(door, \"locked\") and !instance_get(door, \"open\")());
+            assert(instance_variables(door) == [\"id\", \"locked\", \"target\"]);
+            assert(object_functions(Door) == [\"describe\", \"open\"]);
+            instance_set(door, \"label\", \"front door\");
+        ")?;
+        let err = run_with_assert(&mut interpreter).unwrap_err();
+        assert!(err.starts_with("error: tried to access non-extant variable `label` in instance"), "{}", err);
+        
+        // with dynamic instance variables, assigning to a variable an instance doesn't have adds it
+        let mut interpreter = assert_interpreter()?;
+        interpreter.set_dynamic_instance_variables(true);
+        interpreter.restart_into_string("
+            obj Door { var locked; def describe() { return self.label; } }
+            var door = instance_create(Door);
+            instance_set(door, \"label\", \"front door\");
+            door.weight = 5;
+            door.weight += 1;
+            assert(door.describe() == \"front door\" and door.weight == 6);
+            assert(instance_has_variable(door, \"label\") and instance_variables(door) == [\"id\", \"locked\", \"label\", \"weight\"]);
+        ")?;
+        run_with_assert(&mut interpreter)?;
+        
+        // the host can do the same things
+        let door = InstanceId(1);
+        interpreter.instance_set(door, "locked", Value::Text("yes".to_string()))?;
+        assert!(matches!(interpreter.instance_get(door, "locked")?, Value::Text(_)));
+        assert_eq!(interpreter.instance_variables(door)?, vec!("id", "locked", "label", "weight"));
+        assert!(interpreter.instance_get(door, "missing").is_err());
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_static_object_members() -> Result<(), String>
+    {
+        let mut interpreter = assert_interpreter()?;
+        interpreter.restart_into_string("
+            obj Enemy
+            {
+                var hp;
+                const SPEED = 4;
+                const HEALTH = SPEED * 10;
+                def create() { hp = HEALTH; }
+                def hurt() { hp -= damage(1); }
+                static def damage(n) { return n * SPEED; }
+                static def spawn_wave(n)
+                {
+                    var wave = [];
+                    for(var i = 0; i < n; i += 1) { wave->push(instance_create(Enemy)); }
+                    return wave;
+                }
+            }
+            var wave = Enemy.spawn_wave(3);
+            assert(wave->len() == 3 and Enemy.HEALTH == 40);
+            wave[0].hurt();
+            assert(wave[0].hp == 36 and Enemy.damage(2) == 8);
+            var f = Enemy.damage;
+            assert(f(3) == 12);
+            print(Enemy.SHIELD);
+        ")?;
+        let err = run_with_assert(&mut interpreter).unwrap_err();
+        assert!(err.starts_with("error: object type `Enemy` has no static function or constant named `SHIELD`"), "{}", err);
+        
+        // object members are read-only, and static functions don't have an instance
+        let err = interpreter.restart_into_string("obj A { const X = 1; } A.X = 2;").unwrap_err();
+        assert!(err.contains("error: tried to assign to `A.X`; object constants and static functions are read-only"), "{}", err);
+        let err = interpreter.restart_into_string("obj B { var hp; static def f() { return hp; } }").unwrap_err();
+        assert!(err.contains("error: unknown identifier `hp`"), "{}", err);
+        let err = interpreter.restart_into_string("obj C { static def f() { return self; } }").unwrap_err();
+        assert!(err.contains("error: `self` used in a static function"), "{}", err);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_instance_queries() -> Result<(), String>
+    {
+        let mut interpreter = assert_interpreter()?;
+        interpreter.restart_into_string("
+            obj Enemy { var hp; }
+            obj Crate { var hp; }
+            for(var i = 0; i < 5; i += 1) { var e = instance_create(Enemy); e.hp = i * 3; }
+            var c = instance_create(Crate);
+            var enemies = instances_of(Enemy);
+            assert(enemies->len() == 5 and instance_find(Enemy, 1) == enemies[1] and instance_find(Enemy, 5) == null);
+            var weak = [];
+            with(Enemy where hp < 7) { weak->push(hp); }
+            assert(weak == [0, 3, 6]);
+            instance_tag(c, \"flammable\");
+            instance_tag(enemies[0], \"flammable\");
+            assert(instances_tagged(\"flammable\") == [enemies[0], c] and instance_has_tag(c, \"flammable\"));
+            instance_kill(enemies[0]);
+            instance_untag(c, \"flammable\");
+            assert(instances_tagged(\"flammable\") == [] and instances_of(Enemy)[0] == enemies[1]);
+        ")?;
+        run_with_assert(&mut interpreter)?;
+        
+        let enemy = ObjectId(interpreter.get_string_index(&"Enemy".to_string()));
+        assert_eq!(interpreter.instances_of(enemy)?.len(), 4);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_deferred_destruction() -> Result<(), String>
+    {
+        let mut interpreter = assert_interpreter()?;
+        interpreter.restart_into_string("
+            globalvar destroyed = [];
+            obj Enemy { var hp; def destroy() { global.destroyed->push(hp); hp = -1; instance_kill(self); } }
+            for(var i = 0; i < 4; i += 1) { var e = instance_create(Enemy); e.hp = i; }
+            var first = instance_find(Enemy, 0);
+            var seen = [];
+            with(Enemy)
+            {
+                if(hp == 0) { instance_kill(self); }
+                if(hp == 1) { instance_kill(instance_find(Enemy, 1)); }
+                seen->push(hp);
+            }
+            assert(seen == [-1, 1, 3] and global.destroyed == [0, 2]);
+            assert(!instance_exists(first) and first.hp == -1 and object_count(Enemy) == 2);
+            if(first) { assert(false); }
+            with(first as Enemy) { assert(false); }
+        ")?;
+        run_with_assert(&mut interpreter)?;
+        
+        // the killed instances were removed when the program exited
+        assert!(interpreter.instance_get(InstanceId(1), "hp").is_err());
+        assert_eq!(interpreter.collect_dead_instances(), 0);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_generational_handles() -> Result<(), String>
+    {
+        let mut interpreter = assert_interpreter()?;
+        interpreter.restart_into_string("
+            obj A { var n; }
+            globalvar old, keep;
+            global.old = instance_create(A);
+            global.keep = instance_create(A);
+            global.keep.n = 5;
+            instance_kill(global.old);
+        ")?;
+        run_with_assert(&mut interpreter)?;
+        let snapshot = interpreter.export_instances();
+        
+        // the removed instance's slot is reused, but its old handle doesn't refer to the new instance
+        interpreter.restart_into_string("
+            var c = instance_create(A);
+            assert(c != global.old and !instance_exists(global.old) and instance_exists(global.keep));
+            if(global.old) { assert(false); }
+            assert(instances_of(A) == [global.keep, c]);
+            global.old = c;
+        ")?;
+        run_with_assert(&mut interpreter)?;
+        
+        // importing brings back the old instances, and the next handle is the same one that c got
+        interpreter.import_instances(&snapshot)?;
+        interpreter.restart_into_string("
+            assert(instances_of(A) == [global.keep] and global.keep.n == 5);
+            assert(instance_create(A) == global.old);
+        ")?;
+        run_with_assert(&mut interpreter)?;
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_messages() -> Result<(), String>
+    {
+        let mut interpreter = assert_interpreter()?;
+        interpreter.restart_into_string("
+            obj Enemy
+            {
+                var hp, log;
+                def create() { hp = 10; log = []; }
+                def on_hit(damage) { hp -= damage; log->push(damage); send(self, \"echo\", damage); }
+                def on_echo(x) { log->push(-x); }
+                def on_alarm() { log->push(\"alarm\"); }
+            }
+            obj Wall { }
+            globalvar a, b;
+            global.a = instance_create(Enemy);
+            global.b = instance_create(Enemy);
+            instance_create(Wall);
+            send(global.a, \"hit\", 3);
+            broadcast(Enemy, \"alarm\", null);
+            broadcast(Wall, \"alarm\", null);
+            send(global.b, \"hit\", 1);
+            assert(global.a.log == []);
+        ")?;
+        run_with_assert(&mut interpreter)?;
+        
+        // nothing is delivered until the host pumps the queue, and the echoes sent while pumping wait for the next pump
+        assert_eq!(interpreter.pending_messages(), 5);
+        assert_eq!(interpreter.pump_messages()?, 4);
+        assert_eq!(interpreter.pending_messages(), 2);
+        assert_eq!(interpreter.pump_messages()?, 2);
+        
+        interpreter.restart_into_string("
+            assert(global.a.log == [3, \"alarm\", -3] and global.b.log == [\"alarm\", 1, -1]);
+            assert(global.a.hp == 7 and global.b.hp == 9);
+            send(global.b, \"hit\", 100);
+            instance_kill(global.b);
+        ")?;
+        run_with_assert(&mut interpreter)?;
+        
+        // messages to killed instances are dropped, and the host can send its own
+        let enemy = ObjectId(interpreter.get_string_index(&"Enemy".to_string()));
+        let a = interpreter.instances_of(enemy)?[0];
+        interpreter.send_message(a, "hit", Value::Number(2.0));
+        assert_eq!(interpreter.pump_messages()?, 1);
+        assert_eq!(interpreter.pump_messages()?, 1);
+        interpreter.restart_into_string("
+            assert(global.a.log == [3, \"alarm\", -3, 2, -2] and global.a.hp == 5);
+        ")?;
+        run_with_assert(&mut interpreter)?;
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_alarms() -> Result<(), String>
+    {
+        let mut interpreter = assert_interpreter()?;
+        interpreter.restart_into_string("
+            globalvar log;
+            global.log = [];
+            obj Gun
+            {
+                var name;
+                def reload() { global.log->push([name, \"reload\"]); }
+                def blink() { global.log->push([name, \"blink\"]); }
+                def stop() { alarm_cancel(self, \"blink\"); global.log->push([name, \"stop\"]); }
+            }
+            globalvar a, b;
+            global.a = instance_create(Gun);
+            global.a.name = \"a\";
+            global.b = instance_create(Gun);
+            global.b.name = \"b\";
+            alarm_set(global.b, \"reload\", 3);
+            alarm_set(global.a, \"reload\", 3);
+            alarm_repeat(global.a, \"blink\", 2);
+            alarm_set(global.a, \"stop\", 5);
+            alarm_set(global.b, \"blink\", 10);
+            assert(alarm_get(global.a, \"reload\") == 3 and alarm_get(global.a, \"missing\") == null);
+        ")?;
+        run_with_assert(&mut interpreter)?;
+        
+        // alarms due on the same tick fire in the order they were set, and stop cancels the repeating blink
+        assert_eq!(interpreter.tick(2)?, 1);
+        assert_eq!(interpreter.tick(4)?, 4);
+        assert_eq!(interpreter.current_tick(), 6);
+        interpreter.restart_into_string("
+            assert(global.log == [[\"a\", \"blink\"], [\"b\", \"reload\"], [\"a\", \"reload\"], [\"a\", \"blink\"], [\"a\", \"stop\"]]);
+            assert(alarm_get(global.a, \"blink\") == null and alarm_get(global.b, \"blink\") == 4);
+            instance_kill(global.b);
+        ")?;
+        run_with_assert(&mut interpreter)?;
+        
+        // killing an instance clears its alarms
+        assert_eq!(interpreter.tick(10)?, 0);
+        
+        // time skips straight to the next alarm, however far away it is, and alarms can't be set past the last tick
+        let gun = ObjectId(interpreter.get_string_index(&"Gun".to_string()));
+        let a = interpreter.instances_of(gun)?[0];
+        interpreter.alarm_set(a, "reload", 1_000_000_000_000)?;
+        assert!(interpreter.alarm_set(a, "blink", u64::MAX).is_err());
+        assert_eq!(interpreter.tick(2_000_000_000_000)?, 1);
+        assert_eq!(interpreter.current_tick(), 2_000_000_000_016);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_object_states() -> Result<(), String>
+    {
+        let mut interpreter = assert_interpreter()?;
+        interpreter.restart_into_string("
+            globalvar log, e;
+            global.log = [];
+            obj Enemy
+            {
+                var speed, state; // `state` is only special at the start of an object part
+                def create() { speed = 0; goto_state(Idle); }
+                def step() { global.log->push(\"default step\"); }
+                def describe() { return \"enemy\"; }
+                state Idle
+                {
+                    def enter() { speed = 0; global.log->push(\"enter idle\"); }
+                    def exit() { global.log->push(\"exit idle\"); }
+                    def step() { goto_state(Walk); global.log->push(instance_state(self)); }
+                }
+                state Walk
+                {
+                    def enter() { speed = 2; global.log->push(\"enter walk\"); }
+                    def describe() { return \"walking enemy\"; }
+                }
+            }
+            global.e = instance_create(Enemy);
+            assert(instance_state(global.e) == \"Idle\" and Enemy.Walk == \"Walk\");
+            global.e.step();
+            global.e.step();
+            assert(global.log == [\"enter idle\", \"exit idle\", \"enter walk\", \"Walk\", \"default step\"]);
+            assert(global.e.describe() == \"walking enemy\" and global.e.speed == 2);
+        ")?;
+        run_with_assert(&mut interpreter)?;
+        
+        // the host can see and change the current state, which runs the hooks the same way
+        let enemy = ObjectId(interpreter.get_string_index(&"Enemy".to_string()));
+        let e = interpreter.instances_of(enemy)?[0];
+        assert_eq!(interpreter.instance_state(e)?, Some("Walk".to_string()));
+        interpreter.instance_goto_state(e, "Idle")?;
+        assert!(interpreter.instance_goto_state(e, "Run").is_err());
+        interpreter.restart_into_string("
+            assert(global.e.speed == 0 and global.e.describe() == \"enemy\");
+        ")?;
+        run_with_assert(&mut interpreter)?;
+        
+        // changing state from inside of a hook waits for the hook to return, so hooks can chain into each other as many times as they like
+        interpreter.restart_into_string("
+            obj Counter
+            {
+                var n, log;
+                def create() { n = 0; log = []; goto_state(Counting); }
+                state Counting
+                {
+                    def enter() { n += 1; if(n < 5000) { goto_state(Counting); log->push(instance_state(self)); } }
+                    def exit() { log->push(n); }
+                    // killed instances don't change state
+                    def destroy() { goto_state(Counting); log->push(\"destroyed\"); }
+                }
+            }
+            var c = instance_create(Counter);
+            assert(c.n == 5000 and c.log[0] == \"Counting\" and c.log[1] == 1 and c.log->len() == 4999 * 2);
+            instance_kill(c);
+            assert(c.n == 5000 and c.log[c.log->len() - 1] == \"destroyed\");
+        ")?;
+        run_with_assert(&mut interpreter)?;
+        
+        assert!(interpreter.restart_into_string("obj A { state S { def create() { } } }").is_err());
+        assert!(interpreter.restart_into_string("obj A { state S { def destroy(x) { } } }").is_err());
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_with_collections() -> Result<(), String>
+    {
+        let mut interpreter = assert_interpreter()?;
+        interpreter.restart_into_string("
+            obj A { var n; def bump() { n += 1; } }
+            obj B
+            {
+                var log;
+                def create() { log = []; }
+                // `other` is the instance that's running the with(), and the block sees A's members through self
+                def visit(list) { with(list) { other.log->push(self.n); self.bump(); } }
+            }
+            var a1 = instance_create(A);
+            var a2 = instance_create(A);
+            var a3 = instance_create(A);
+            a1.n = 1;
+            a2.n = 2;
+            a3.n = 3;
+            var b = instance_create(B);
+            
+            instance_kill(a2);
+            b.visit([a3, a2, a1]);
+            assert(b.log == [3, 1] and a1.n == 2 and a3.n == 4);
+            
+            // sets are visited in creation order
+            var seen = [];
+            with(set {a3, a1, a2}) { seen->push(self.n); }
+            assert(seen == [2, 4]);
+            
+            with(instances_of(A)) { self.n = 0; }
+            with([a3, a1] as A) { n += 10; }
+            assert(a1.n == 10 and a3.n == 10);
+            
+            // where works the same way as with(Obj where condition)
+            var list = [a1, a3];
+            seen = [];
+            with(list where self.n > 5) { seen->push(self.n); }
+            with(list where self.n > 50) { seen->push(-1); }
+            assert(seen == [10, 10]);
+        ")?;
+        run_with_assert(&mut interpreter)?;
+        
+        interpreter.restart_into_string("
+            obj A { }
+            obj B { }
+            with([instance_create(A), instance_create(B)] as A) { }
+        ")?;
+        interpreter.step_until_error_or_exit().ok();
+        let err = interpreter.last_error.clone().ok_or("expected an error")?;
+        assert!(err.starts_with("error: tried to use an instance of `B` as `A` in with()"), "{}", err);
+        
+        Ok(())
+    }
+    
     /*
     #[test]
     fn test_nopspeed() -> Result<(), String>
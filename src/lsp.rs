@@ -0,0 +1,388 @@
+use std::collections::{HashMap, BTreeMap};
+use std::io::{BufRead, Write};
+
+use crate::{compiler::*, interpreter::*, parser::{Diagnostic, Severity}};
+
+#[macro_use]
+mod json;
+use self::json::Json;
+
+/// A language server for gammakit programs, speaking the language server protocol (JSON-RPC) to an editor.
+///
+/// Provides diagnostics, go to definition, hover, and completion, using the parser, compiler, and bindings of the interpreter it's given (see Interpreter::analyze_program()).
+///
+/// Documents are always synced in full.
+pub struct LanguageServer {
+    interpreter : Interpreter,
+    documents : HashMap<String, Document>,
+    outbox : Vec<Json>, // notifications to send after handling the current message
+    shut_down : bool,
+    exited : bool,
+}
+
+struct Document {
+    text : String,
+    analysis : Option<ProgramAnalysis>,
+}
+
+// error codes from the JSON-RPC spec
+const PARSE_ERROR : i64 = -32700;
+const INVALID_REQUEST : i64 = -32600;
+const METHOD_NOT_FOUND : i64 = -32601;
+const INVALID_PARAMS : i64 = -32602;
+
+// diagnostic severities from the LSP spec
+const SEVERITY_ERROR : f64 = 1.0;
+const SEVERITY_WARNING : f64 = 2.0;
+
+// messages are read into memory all at once, so a bogus Content-Length shouldn't be able to make the server allocate everything it can
+const MAX_MESSAGE_LENGTH : usize = 64 * 1024 * 1024;
+
+// completion item kinds from the LSP spec
+const COMPLETION_METHOD : f64 = 2.0;
+const COMPLETION_FUNCTION : f64 = 3.0;
+const COMPLETION_FIELD : f64 = 5.0;
+const COMPLETION_VARIABLE : f64 = 6.0;
+const COMPLETION_CLASS : f64 = 7.0;
+const COMPLETION_CONSTANT : f64 = 21.0;
+
+type LspResult = Result<Json, (i64, String)>;
+
+fn json_text(text : &str) -> Json
+{
+    Json::Text(text.to_string())
+}
+
+fn error_response(id : &Json, code : i64, message : &str) -> Json
+{
+    json_object!("jsonrpc" => json_text("2.0"), "id" => id.clone(), "error" => json_object!("code" => Json::Number(code as f64), "message" => json_text(message)))
+}
+
+fn missing(what : &str) -> (i64, String)
+{
+    (INVALID_PARAMS, format!("error: request is missing `{}`", what))
+}
+
+fn line_text(text : &str, line : usize) -> &str
+{
+    text.lines().nth(line.saturating_sub(1)).unwrap_or("")
+}
+
+// gammakit counts lines and positions from 1 in codepoints, but the protocol counts them from 0 in UTF-16 code units
+fn lsp_position(text : &str, line : usize, position : usize) -> Json
+{
+    let character : usize = line_text(text, line).chars().take(position.saturating_sub(1)).map(|c| c.len_utf16()).sum();
+    json_object!("line" => Json::Number(line.saturating_sub(1) as f64), "character" => Json::Number(character as f64))
+}
+fn lsp_range(text : &str, line : usize, position : usize, length : usize) -> Json
+{
+    json_object!("start" => lsp_position(text, line, position), "end" => lsp_position(text, line, position + length))
+}
+fn gammakit_position(text : &str, params : &Json) -> Result<(usize, usize), (i64, String)>
+{
+    let line = params.get("position").get("line").as_usize().ok_or_else(|| missing("position.line"))?;
+    let character = params.get("position").get("character").as_usize().ok_or_else(|| missing("position.character"))?;
+    let mut units = 0;
+    let mut position = 1;
+    for c in text.lines().nth(line).unwrap_or("").chars()
+    {
+        if units >= character
+        {
+            break;
+        }
+        units += c.len_utf16();
+        position += 1;
+    }
+    Ok((line + 1, position))
+}
+
+// diagnostics only have a starting point, so they're highlighted up to the end of the word they start on
+fn diagnostic_length(text : &str, diagnostic : &Diagnostic) -> usize
+{
+    let word = line_text(text, diagnostic.line).chars().skip(diagnostic.position.saturating_sub(1)).take_while(|c| c.is_alphanumeric() || *c == '_').count();
+    std::cmp::max(word, 1)
+}
+
+fn describe_symbol(symbol : &Symbol) -> String
+{
+    let kind = match symbol.kind
+    {
+        SymbolKind::Local => "local variable",
+        SymbolKind::Function => "function",
+        SymbolKind::InstanceVar => "instance variable",
+        SymbolKind::Method => "object function",
+        SymbolKind::ObjectConst => "object constant",
+        SymbolKind::State => "object state",
+        SymbolKind::BareGlobal => "bare global",
+        SymbolKind::GlobalVar => "global variable",
+        SymbolKind::GlobalFunc => "global function",
+        SymbolKind::Binding => "binding",
+        SymbolKind::Object => "object",
+    };
+    match symbol.definition
+    {
+        Some((line, _)) => format!("{} `{}` (defined on line {})", kind, symbol.name, line),
+        None => format!("{} `{}`", kind, symbol.name)
+    }
+}
+
+fn completion_kind(kind : SymbolKind) -> f64
+{
+    match kind
+    {
+        SymbolKind::Local | SymbolKind::GlobalVar => COMPLETION_VARIABLE,
+        SymbolKind::Function | SymbolKind::GlobalFunc | SymbolKind::Binding => COMPLETION_FUNCTION,
+        SymbolKind::InstanceVar => COMPLETION_FIELD,
+        SymbolKind::Method => COMPLETION_METHOD,
+        SymbolKind::BareGlobal | SymbolKind::ObjectConst | SymbolKind::State => COMPLETION_CONSTANT,
+        SymbolKind::Object => COMPLETION_CLASS,
+    }
+}
+
+// reads a message in the base protocol: headers, a blank line, and then Content-Length bytes of JSON
+fn read_message<R : BufRead>(input : &mut R) -> Result<Option<String>, String>
+{
+    let mut length = None;
+    loop
+    {
+        let mut line = String::new();
+        if input.read_line(&mut line).map_err(|err| format!("error: failed to read message: {}", err))? == 0
+        {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty()
+        {
+            if length.is_some()
+            {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = line.split_once(':')
+        {
+            if name.trim().eq_ignore_ascii_case("Content-Length")
+            {
+                length = Some(value.trim().parse::<usize>().map_err(|_| format!("error: invalid Content-Length `{}`", value.trim()))?);
+            }
+        }
+    }
+    let length = length.unwrap_or(0);
+    if length > MAX_MESSAGE_LENGTH
+    {
+        return Err(format!("error: Content-Length {} is larger than the maximum of {}", length, MAX_MESSAGE_LENGTH));
+    }
+    let mut body = vec!(0; length);
+    input.read_exact(&mut body).map_err(|err| format!("error: failed to read message: {}", err))?;
+    String::from_utf8(body).map(Some).map_err(|_| "error: message is not valid UTF-8".to_string())
+}
+
+impl LanguageServer {
+    /// Insert bindings into the interpreter before giving it to the server, so that they're known about.
+    pub fn new(interpreter : Interpreter) -> LanguageServer
+    {
+        LanguageServer { interpreter, documents : HashMap::new(), outbox : Vec::new(), shut_down : false, exited : false }
+    }
+    /// Serves an editor over the base protocol (JSON messages with Content-Length headers), e.g. over stdin and stdout, until it sends `exit`.
+    ///
+    /// Returns an error if the input ends, or the editor exits without shutting the server down first.
+    pub fn run<R : BufRead, W : Write>(&mut self, mut input : R, mut output : W) -> Result<(), String>
+    {
+        while let Some(message) = read_message(&mut input)?
+        {
+            for reply in self.handle_message(&message)
+            {
+                write!(output, "Content-Length: {}\r\n\r\n{}", reply.len(), reply).and_then(|_| output.flush()).map_err(|err| format!("error: failed to write message: {}", err))?;
+            }
+            if self.exited
+            {
+                return if self.shut_down { Ok(()) } else { Err("error: exited without being shut down first".to_string()) };
+            }
+        }
+        Err("error: input ended without an `exit` notification".to_string())
+    }
+    /// Handles a single JSON-RPC message and returns the JSON messages to send back, in order.
+    pub fn handle_message(&mut self, message : &str) -> Vec<String>
+    {
+        let message = match Json::parse(message)
+        {
+            Ok(message) => message,
+            Err(err) => return vec!(error_response(&Json::Null, PARSE_ERROR, &err).to_string())
+        };
+        let id = message.get("id").clone();
+        let method = message.get("method").as_str().unwrap_or_default().to_string();
+        
+        let result = if self.shut_down && method != "exit"
+        {
+            Err((INVALID_REQUEST, "error: server has been shut down".to_string()))
+        }
+        else
+        {
+            self.handle(&method, message.get("params"))
+        };
+        
+        let mut replies = Vec::new();
+        // notifications don't have ids and never get responses, even if they fail
+        if id != Json::Null
+        {
+            replies.push(match result
+            {
+                Ok(result) => json_object!("jsonrpc" => json_text("2.0"), "id" => id, "result" => result),
+                Err((code, message)) => error_response(&id, code, &message)
+            });
+        }
+        replies.append(&mut self.outbox);
+        replies.iter().map(|reply| reply.to_string()).collect()
+    }
+    fn handle(&mut self, method : &str, params : &Json) -> LspResult
+    {
+        match method
+        {
+            "initialize" => Ok(json_object!(
+                "capabilities" => json_object!(
+                    "textDocumentSync" => Json::Number(1.0), // full
+                    "definitionProvider" => Json::Bool(true),
+                    "hoverProvider" => Json::Bool(true),
+                    "completionProvider" => json_object!("triggerCharacters" => Json::Array(vec!(json_text(">")))),
+                ),
+                "serverInfo" => json_object!("name" => json_text("gammakit-lsp")),
+            )),
+            "initialized" => Ok(Json::Null),
+            "shutdown" =>
+            {
+                self.shut_down = true;
+                Ok(Json::Null)
+            }
+            "exit" =>
+            {
+                self.exited = true;
+                Ok(Json::Null)
+            }
+            "textDocument/didOpen" =>
+            {
+                let text = params.get("textDocument").get("text").as_str().ok_or_else(|| missing("textDocument.text"))?;
+                self.update_document(&document_uri(params)?, text);
+                Ok(Json::Null)
+            }
+            "textDocument/didChange" =>
+            {
+                let changes = params.get("contentChanges").as_array().ok_or_else(|| missing("contentChanges"))?;
+                let text = changes.last().and_then(|change| change.get("text").as_str()).ok_or_else(|| missing("contentChanges.text"))?;
+                self.update_document(&document_uri(params)?, text);
+                Ok(Json::Null)
+            }
+            "textDocument/didClose" =>
+            {
+                let uri = document_uri(params)?;
+                self.documents.remove(&uri);
+                self.publish_diagnostics(&uri, "", &[]);
+                Ok(Json::Null)
+            }
+            "textDocument/definition" => self.definition(params),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/completion" => self.completion(params),
+            _ => Err((METHOD_NOT_FOUND, format!("error: unknown method `{}`", method)))
+        }
+    }
+    
+    fn update_document(&mut self, uri : &str, text : &str)
+    {
+        let (analysis, diagnostics) = match self.interpreter.analyze_program(text)
+        {
+            Ok(analysis) =>
+            {
+                let diagnostics = analysis.diagnostics.clone();
+                (Some(analysis), diagnostics)
+            }
+            Err(err) => (None, vec!(Diagnostic { line : 1, position : 1, severity : Severity::Error, message : err }))
+        };
+        self.publish_diagnostics(uri, text, &diagnostics);
+        self.documents.insert(uri.to_string(), Document { text : text.to_string(), analysis });
+    }
+    fn publish_diagnostics(&mut self, uri : &str, text : &str, diagnostics : &[Diagnostic])
+    {
+        let diagnostics = diagnostics.iter().map(|diagnostic| json_object!(
+            "range" => lsp_range(text, diagnostic.line, diagnostic.position, diagnostic_length(text, diagnostic)),
+            "severity" => Json::Number(match diagnostic.severity { Severity::Error => SEVERITY_ERROR, Severity::Warning => SEVERITY_WARNING }),
+            "source" => json_text("gammakit"),
+            "message" => json_text(&diagnostic.message),
+        )).collect();
+        self.outbox.push(json_object!(
+            "jsonrpc" => json_text("2.0"),
+            "method" => json_text("textDocument/publishDiagnostics"),
+            "params" => json_object!("uri" => json_text(uri), "diagnostics" => Json::Array(diagnostics)),
+        ));
+    }
+    
+    fn document(&self, params : &Json) -> Result<&Document, (i64, String)>
+    {
+        let uri = document_uri(params)?;
+        self.documents.get(&uri).ok_or_else(|| (INVALID_PARAMS, format!("error: document `{}` is not open", uri)))
+    }
+    fn symbol_at(&self, params : &Json) -> Result<Option<(&Document, &Symbol)>, (i64, String)>
+    {
+        let document = self.document(params)?;
+        let (line, position) = gammakit_position(&document.text, params)?;
+        let symbol = document.analysis.as_ref().and_then(|analysis| analysis.symbols.iter().find(|symbol|
+            symbol.line == line && position >= symbol.position && position <= symbol.position + symbol.name.chars().count()
+        ));
+        Ok(symbol.map(|symbol| (document, symbol)))
+    }
+    fn definition(&self, params : &Json) -> LspResult
+    {
+        match self.symbol_at(params)?
+        {
+            Some((document, Symbol { name, definition : Some((line, position)), .. })) => Ok(json_object!(
+                "uri" => json_text(&document_uri(params)?),
+                "range" => lsp_range(&document.text, *line, *position, name.chars().count()),
+            )),
+            _ => Ok(Json::Null)
+        }
+    }
+    fn hover(&self, params : &Json) -> LspResult
+    {
+        match self.symbol_at(params)?
+        {
+            Some((document, symbol)) => Ok(json_object!(
+                "contents" => json_object!("kind" => json_text("plaintext"), "value" => json_text(&describe_symbol(symbol))),
+                "range" => lsp_range(&document.text, symbol.line, symbol.position, symbol.name.chars().count()),
+            )),
+            None => Ok(Json::Null)
+        }
+    }
+    // after ->, completes arrow bindings; anywhere else, completes normal bindings and everything the program defines
+    fn completion(&self, params : &Json) -> LspResult
+    {
+        let document = self.document(params)?;
+        let (line, position) = gammakit_position(&document.text, params)?;
+        let before : String = line_text(&document.text, line).chars().take(position - 1).collect();
+        let after_arrow = before.trim_end_matches(|c : char| c.is_alphanumeric() || c == '_').ends_with("->");
+        
+        let mut items = BTreeMap::new();
+        for binding in self.interpreter.list_bindings()
+        {
+            match binding.kind
+            {
+                BindingKind::Arrow if after_arrow => items.insert(binding.name, COMPLETION_METHOD),
+                BindingKind::Function if !after_arrow => items.insert(binding.name, COMPLETION_FUNCTION),
+                _ => None
+            };
+        }
+        if let (Some(analysis), false) = (&document.analysis, after_arrow)
+        {
+            for symbol in &analysis.symbols
+            {
+                if symbol.definition == Some((symbol.line, symbol.position))
+                {
+                    items.insert(symbol.name.clone(), completion_kind(symbol.kind));
+                }
+            }
+        }
+        Ok(Json::Array(items.into_iter().map(|(label, kind)| json_object!("label" => Json::Text(label), "kind" => Json::Number(kind))).collect()))
+    }
+}
+
+fn document_uri(params : &Json) -> Result<String, (i64, String)>
+{
+    params.get("textDocument").get("uri").as_str().map(|uri| uri.to_string()).ok_or_else(|| missing("textDocument.uri"))
+}
@@ -0,0 +1,293 @@
+// just enough JSON for the language server protocol
+
+#[derive(Debug, Clone, PartialEq)]
+pub (crate) enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Text(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>), // in order, so that output is predictable
+}
+
+impl Json {
+    pub (crate) fn get(&self, key : &str) -> &Json
+    {
+        match self
+        {
+            Json::Object(fields) => fields.iter().find(|(name, _)| name == key).map(|(_, value)| value).unwrap_or(&Json::Null),
+            _ => &Json::Null
+        }
+    }
+    pub (crate) fn as_str(&self) -> Option<&str>
+    {
+        match_or_none!(self, Json::Text(text) => text.as_str())
+    }
+    pub (crate) fn as_usize(&self) -> Option<usize>
+    {
+        match self
+        {
+            Json::Number(number) if *number >= 0.0 => Some(*number as usize),
+            _ => None
+        }
+    }
+    pub (crate) fn as_array(&self) -> Option<&Vec<Json>>
+    {
+        match_or_none!(self, Json::Array(array) => array)
+    }
+    
+    pub (crate) fn parse(text : &str) -> Result<Json, String>
+    {
+        let mut parser = JsonParser { chars : text.chars().collect(), index : 0, depth : 0 };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.index != parser.chars.len()
+        {
+            return Err(format!("error: trailing characters after JSON value at character {}", parser.index));
+        }
+        Ok(value)
+    }
+}
+
+// builds a JSON object out of (name, value) pairs
+macro_rules! json_object { ($($name:expr => $value:expr),* $(,)?) =>
+{
+    Json::Object(vec!($(($name.to_string(), $value)),*))
+} }
+
+impl std::fmt::Display for Json {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self
+        {
+            Json::Null => write!(f, "null"),
+            Json::Bool(boolean) => write!(f, "{}", boolean),
+            Json::Number(number) if number.fract() == 0.0 && number.abs() < 1e15 => write!(f, "{}", *number as i64),
+            Json::Number(number) => write!(f, "{}", number),
+            Json::Text(text) => write_json_string(f, text),
+            Json::Array(array) =>
+            {
+                write!(f, "[")?;
+                for (i, value) in array.iter().enumerate()
+                {
+                    if i > 0
+                    {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) =>
+            {
+                write!(f, "{{")?;
+                for (i, (name, value)) in fields.iter().enumerate()
+                {
+                    if i > 0
+                    {
+                        write!(f, ",")?;
+                    }
+                    write_json_string(f, name)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_json_string(f : &mut std::fmt::Formatter<'_>, text : &str) -> std::fmt::Result
+{
+    write!(f, "\"")?;
+    for c in text.chars()
+    {
+        match c
+        {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?
+        }
+    }
+    write!(f, "\"")
+}
+
+// values are parsed recursively, so deeply nested arrays and objects would overflow the stack
+const MAX_DEPTH : usize = 256;
+
+struct JsonParser {
+    chars : Vec<char>,
+    index : usize,
+    depth : usize,
+}
+
+impl JsonParser {
+    fn skip_whitespace(&mut self)
+    {
+        while self.chars.get(self.index).map(|c| c.is_whitespace()).unwrap_or(false)
+        {
+            self.index += 1;
+        }
+    }
+    fn error<T>(&self, what : &str) -> Result<T, String>
+    {
+        Err(format!("error: {} at character {} of JSON", what, self.index))
+    }
+    fn expect(&mut self, word : &str) -> Result<(), String>
+    {
+        for c in word.chars()
+        {
+            if self.chars.get(self.index) != Some(&c)
+            {
+                return self.error(&format!("expected `{}`", word));
+            }
+            self.index += 1;
+        }
+        Ok(())
+    }
+    fn value(&mut self) -> Result<Json, String>
+    {
+        if self.depth >= MAX_DEPTH
+        {
+            return self.error("arrays and objects nested too deeply");
+        }
+        self.depth += 1;
+        let value = self.value_inner();
+        self.depth -= 1;
+        value
+    }
+    fn value_inner(&mut self) -> Result<Json, String>
+    {
+        self.skip_whitespace();
+        match self.chars.get(self.index)
+        {
+            Some('n') => self.expect("null").map(|_| Json::Null),
+            Some('t') => self.expect("true").map(|_| Json::Bool(true)),
+            Some('f') => self.expect("false").map(|_| Json::Bool(false)),
+            Some('"') => self.string().map(Json::Text),
+            Some('[') =>
+            {
+                self.index += 1;
+                let mut array = Vec::new();
+                self.skip_whitespace();
+                if self.chars.get(self.index) == Some(&']')
+                {
+                    self.index += 1;
+                    return Ok(Json::Array(array));
+                }
+                loop
+                {
+                    array.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.chars.get(self.index)
+                    {
+                        Some(',') => self.index += 1,
+                        Some(']') => break,
+                        _ => return self.error("expected `,` or `]`")
+                    }
+                }
+                self.index += 1;
+                Ok(Json::Array(array))
+            }
+            Some('{') =>
+            {
+                self.index += 1;
+                let mut fields = Vec::new();
+                self.skip_whitespace();
+                if self.chars.get(self.index) == Some(&'}')
+                {
+                    self.index += 1;
+                    return Ok(Json::Object(fields));
+                }
+                loop
+                {
+                    self.skip_whitespace();
+                    if self.chars.get(self.index) != Some(&'"')
+                    {
+                        return self.error("expected a string");
+                    }
+                    let name = self.string()?;
+                    self.skip_whitespace();
+                    self.expect(":")?;
+                    fields.push((name, self.value()?));
+                    self.skip_whitespace();
+                    match self.chars.get(self.index)
+                    {
+                        Some(',') => self.index += 1,
+                        Some('}') => break,
+                        _ => return self.error("expected `,` or `}`")
+                    }
+                }
+                self.index += 1;
+                Ok(Json::Object(fields))
+            }
+            Some(c) if *c == '-' || c.is_ascii_digit() =>
+            {
+                let start = self.index;
+                while self.chars.get(self.index).map(|c| *c == '-' || *c == '+' || *c == '.' || *c == 'e' || *c == 'E' || c.is_ascii_digit()).unwrap_or(false)
+                {
+                    self.index += 1;
+                }
+                let text : String = self.chars[start..self.index].iter().collect();
+                text.parse::<f64>().map(Json::Number).or_else(|_| self.error("malformed number"))
+            }
+            _ => self.error("expected a value")
+        }
+    }
+    fn string(&mut self) -> Result<String, String>
+    {
+        self.index += 1;
+        let mut text = String::new();
+        loop
+        {
+            match self.chars.get(self.index)
+            {
+                Some('"') => break,
+                Some('\\') =>
+                {
+                    self.index += 1;
+                    match self.chars.get(self.index)
+                    {
+                        Some('"') => text.push('"'),
+                        Some('\\') => text.push('\\'),
+                        Some('/') => text.push('/'),
+                        Some('b') => text.push('\u{8}'),
+                        Some('f') => text.push('\u{c}'),
+                        Some('n') => text.push('\n'),
+                        Some('r') => text.push('\r'),
+                        Some('t') => text.push('\t'),
+                        Some('u') =>
+                        {
+                            let mut code = self.hex4()?;
+                            // surrogate pairs
+                            if (0xD800..0xDC00).contains(&code) && self.chars.get(self.index+1) == Some(&'\\') && self.chars.get(self.index+2) == Some(&'u')
+                            {
+                                self.index += 2;
+                                let low = self.hex4()?;
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+                            }
+                            text.push(std::char::from_u32(code).unwrap_or('\u{FFFD}'));
+                        }
+                        _ => return self.error("invalid escape sequence")
+                    }
+                }
+                Some(c) => text.push(*c),
+                None => return self.error("unterminated string")
+            }
+            self.index += 1;
+        }
+        self.index += 1;
+        Ok(text)
+    }
+    // reads the four hex digits after a \u, leaving the index on the last one
+    fn hex4(&mut self) -> Result<u32, String>
+    {
+        let digits : String = self.chars.get(self.index+1..self.index+5).map(|digits| digits.iter().collect()).unwrap_or_default();
+        let code = u32::from_str_radix(&digits, 16).or_else(|_| self.error("invalid unicode escape"))?;
+        self.index += 4;
+        Ok(code)
+    }
+}
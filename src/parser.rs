@@ -74,13 +74,37 @@ pub (crate) struct GrammarPoint {
     pub (crate) name: String,
     pub (crate) forms: Vec<GrammarForm>,
     pub (crate) istoken: bool,
-    pub (crate) precedence : Option<u64> // precedence of left-associative binary operator rules
+    pub (crate) precedence : Option<u64>, // precedence of left-associative binary operator rules
+    pub (crate) id : usize, // index into the packrat cache
+    // the rest are only used by TOKEN rules
+    pub (crate) priority : i64, // tokens with higher priority are lexed first
+    pub (crate) exclude : Vec<String>, // names of rules listing words that this token can't be
+    pub (crate) excluded : HashSet<String>, // the words themselves
+    pub (crate) mode : String, // lexer mode this token is lexed in
+    pub (crate) modechange : ModeChange, // what happens to the lexer mode after lexing this token
+}
+
+pub (crate) const DEFAULT_MODE : &str = "default";
+
+#[derive(Clone, PartialEq)]
+pub (crate) enum ModeChange {
+    None,
+    Push(String),
+    Pop,
+}
+
+// a regex token, in the order the lexer tries them
+#[derive(Clone)]
+pub (crate) struct LexRule {
+    regex : String,
+    mode : String,
+    modechange : ModeChange,
 }
 
 #[derive(Clone)]
 /// Provides facilities for turning program text into an AST.
 pub struct Parser {
-    pub (crate) regex_list : Vec<String>,
+    pub (crate) lex_rules : Vec<LexRule>,
     pub (crate) symbol_list : Vec<String>,
     pub (crate) text_list : Vec<String>,
     // token matchers are inserted into both sets and vectors, sets to quickly check for duplicate insertion and vectors are for order
@@ -103,14 +127,91 @@ fn plainerr<T>(mystr : &str) -> Result<T, String>
     Err(minierr(mystr))
 }
 
+/// How bad a Diagnostic is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// The program fails to compile, or is certain to fail when the code in question runs.
+    Error,
+    /// Probably a mistake, but doesn't stop the program from working.
+    Warning,
+}
+
+/// A problem found in program text by Parser::parse_with_recovery(), Interpreter::analyze_program(), or Interpreter::check_program().
+///
+/// The message starts with "error: " or "warning: ", matching the severity, so that it can be printed as-is.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub line : usize,
+    pub position : usize,
+    pub severity : Severity,
+    pub message : String,
+}
+
+// grammar point that error recovery happens at, and the tokens it synchronizes on
+const RECOVERY_POINT : &str = "statement";
+const RECOVERY_PAIRS : [(&str, &str); 3] = [("{", "}"), ("(", ")"), ("[", "]")];
+
+// turns the set of expected tokens of a parse error into a message
+fn describe_parse_error(error : &ParseError) -> String
+{
+    let mut expected : Vec<String> = error.expected.iter().map(|x| x.clone().into_string()).collect();
+    expected.sort();
+    if expected.len() == 1
+    {
+        format!("error: expected `{}`", expected[0])
+    }
+    else
+    {
+        format!("error: expected one of `{}`", expected.join("`, `"))
+    }
+}
+
+// points at the place in the program text where tokenization failed
+fn tokenize_error(text : &str, linecount : usize, column : usize, message : &str) -> String
+{
+    let line = text.lines().nth(linecount-1).unwrap_or("");
+    format!("{}\noffending line ({}):\n{}\n{}^", message, linecount, line, " ".repeat(column-1))
+}
+
 type ParseInfo = (Option<ASTNode>, usize, Option<ParseError>);
 type ParseVecInfo = (Option<Vec<ASTNode>>, usize, Option<ParseError>);
 
+// packrat memoization: the result of parsing a given grammar point at a given token index never changes, errors included, so it only needs to be computed once
+// successful results are only stored the second time they're asked for (None marks the first), because cloning every subtree into the cache costs more than reparsing the ones that are never asked for again
+struct ParseCache {
+    // one short list of (grammar point, result) per token index; much faster than hashing every lookup
+    entries : Vec<Vec<(usize, Option<ParseInfo>)>>,
+    // whether statements that fail to parse get skipped and turned into error nodes; recovering changes results, so it's part of the cache
+    recover : bool,
+}
+
+impl ParseCache {
+    fn new(tokencount : usize, recover : bool) -> ParseCache
+    {
+        ParseCache { entries : vec!(Vec::new(); tokencount+1), recover }
+    }
+    fn get(&self, id : usize, index : usize) -> Option<&Option<ParseInfo>>
+    {
+        self.entries.get(index)?.iter().find(|entry| entry.0 == id).map(|entry| &entry.1)
+    }
+    fn insert(&mut self, id : usize, index : usize, info : Option<ParseInfo>)
+    {
+        if let Some(list) = self.entries.get_mut(index)
+        {
+            match list.iter_mut().find(|entry| entry.0 == id)
+            {
+                Some(entry) => entry.1 = info,
+                None => list.push((id, info))
+            }
+        }
+    }
+}
+
 impl Default for Parser {
     fn default() -> Parser
     {
         Parser {
-            regex_list: Vec::new(),
+            lex_rules: Vec::new(),
             symbol_list: Vec::new(),
             text_list: Vec::new(),
             regex_set: HashSet::new(),
@@ -141,8 +242,6 @@ impl Parser {
     }
     fn init(&mut self, text: &str) -> Result<(), String>
     {
-        let start_time = Instant::now();
-        
         let mut lines : Vec<_> = vec!("".to_string());
         lines.extend(text.lines().rev().map(|x| x.to_string()));
     
@@ -151,24 +250,45 @@ impl Parser {
             macro_rules! pop { () => { lines.pop().ok_or_else(|| "tried to access past end of program text".to_string()) }; }
             
             let mut line : String = pop!()?;
-            if line == ""
+            if line.is_empty()
             {
                 continue;
             }
-            let captures = self.internal_regexes.captures("([a-zA-Z_][a-zA-Z_0-9]*):[ ]*(TOKEN)?[ ]*(LEFTBINEXPR [0-9]+)?", &line)
+            let captures = self.internal_regexes.captures("([a-zA-Z_][a-zA-Z_0-9]*):(.*)", &line)
                 .ok_or_else(|| minierr(&format!("general syntax error\noffending line:\n{}", line)))?;
             let name = captures.get(1).ok_or_else(|| minierr("unreachable error in parser init getting rule name"))?.as_str().to_string();
-            let istoken = captures.get(2).is_some();
-            let precedence =
-            match captures.get(3)
-            {
-                Some(x) => Some(slice_to_end(x.as_str(), 12).parse::<u64>().or_else(|_| plainerr("error: LEFTBINEXPR argument must be a positive integer"))?),
-                None => None
-            };
+            let mut attributes = captures.get(2).ok_or_else(|| minierr("unreachable error in parser init getting rule attributes"))?.as_str().split_whitespace();
             // last line is guaranteed to be "" which means we are unable to pop past the end here
-            let mut nodetype : GrammarPoint = GrammarPoint{name, forms: Vec::new(), istoken, precedence};
+            let mut nodetype : GrammarPoint = GrammarPoint{name, forms: Vec::new(), istoken : false, precedence : None, id : self.nodetypemap.len(),
+                priority : 0, exclude : Vec::new(), excluded : HashSet::new(), mode : DEFAULT_MODE.to_string(), modechange : ModeChange::None};
+            while let Some(attribute) = attributes.next()
+            {
+                macro_rules! argument { () => { attributes.next().ok_or_else(|| minierr(&format!("error: `{}` attribute of rule `{}` is missing its argument", attribute, nodetype.name))) }; }
+                match attribute
+                {
+                    "TOKEN" => nodetype.istoken = true,
+                    "LEFTBINEXPR" => nodetype.precedence = Some(argument!()?.parse::<u64>().or_else(|_| plainerr("error: LEFTBINEXPR argument must be a positive integer"))?),
+                    "PRIORITY" => nodetype.priority = argument!()?.parse::<i64>().or_else(|_| plainerr("error: PRIORITY argument must be an integer"))?,
+                    "EXCLUDE" => nodetype.exclude.push(argument!()?.to_string()),
+                    "MODE" => nodetype.mode = argument!()?.to_string(),
+                    "PUSH" | "POP" =>
+                    {
+                        if nodetype.modechange != ModeChange::None
+                        {
+                            return plainerr(&format!("error: rule `{}` changes the lexer mode more than once", nodetype.name));
+                        }
+                        nodetype.modechange = if attribute == "PUSH" { ModeChange::Push(argument!()?.to_string()) } else { ModeChange::Pop };
+                    }
+                    _ => return plainerr(&format!("error: unknown attribute `{}` on rule `{}`", attribute, nodetype.name))
+                }
+            }
+            if !nodetype.istoken && (nodetype.priority != 0 || !nodetype.exclude.is_empty() || nodetype.mode != DEFAULT_MODE || nodetype.modechange != ModeChange::None)
+            {
+                return plainerr(&format!("error: rule `{}` uses PRIORITY, EXCLUDE, MODE, PUSH, or POP without being a TOKEN rule", nodetype.name));
+            }
+            let istoken = nodetype.istoken;
             line = pop!()?;
-            while line != ""
+            while !line.is_empty()
             {
                 nodetype.forms.push(GrammarForm::new(&line, self, istoken)?);
                 line = pop!()?;
@@ -182,7 +302,7 @@ impl Parser {
         
         for regex in &self.regex_set
         {
-            self.internal_regexes.prepare_exact(&regex);
+            self.internal_regexes.prepare_exact(regex);
         }
         
         for tuple in &self.nodetypemap
@@ -200,7 +320,7 @@ impl Parser {
                         GrammarToken::SeparatorNameList{text, ..} => text.clone(),
                         _ => "".to_string()
                     };
-                    if name != "" && !self.nodetypemap.contains_key(&name)
+                    if !name.is_empty() && !self.nodetypemap.contains_key(&name)
                     {
                         return plainerr(&format!("error: node name {} is used without actually defined", name));
                     }
@@ -211,113 +331,247 @@ impl Parser {
         {
             return plainerr("error: grammar does not define name \"program\"");
         }
+        self.init_lex_rules()?;
         
         self.symbol_list.sort_by_key(|text| -(text.len() as i64));
         self.text_list  .sort_by_key(|text| -(text.len() as i64));
         
         self.inited = true;
         
-        println!("init took {:?}", Instant::now().duration_since(start_time));
+        Ok(())
+    }
+    
+    // validates the attributes of TOKEN rules and builds the list of regex tokens the lexer tries
+    fn init_lex_rules(&mut self) -> Result<(), String>
+    {
+        // in definition order, so that errors are reported consistently
+        let mut points : Vec<&GrammarPoint> = self.nodetypemap.values().collect();
+        points.sort_by_key(|point| point.id);
+        
+        let mut excluded = HashMap::new();
+        for point in points
+        {
+            for exclude in &point.exclude
+            {
+                let target = self.nodetypemap.get(exclude).ok_or_else(|| minierr(&format!("error: rule `{}` excludes `{}`, which is not defined", point.name, exclude)))?;
+                let mut words = HashSet::new();
+                for form in &target.forms
+                {
+                    match (target.istoken, form.tokens.as_slice())
+                    {
+                        (true, [GrammarToken::Plain(word)]) => words.insert(word.clone()),
+                        _ => return plainerr(&format!("error: rule `{}` excludes `{}`, which must be a TOKEN rule made of plain words", point.name, exclude))
+                    };
+                }
+                excluded.entry(point.name.clone()).or_insert_with(HashSet::new).extend(words);
+            }
+            if let ModeChange::Push(mode) = &point.modechange
+            {
+                if mode != DEFAULT_MODE && !self.nodetypemap.values().any(|other| other.istoken && other.mode == *mode)
+                {
+                    return plainerr(&format!("error: rule `{}` pushes lexer mode `{}`, which has no tokens", point.name, mode));
+                }
+            }
+            if point.mode != DEFAULT_MODE
+            {
+                if !self.nodetypemap.values().any(|other| other.modechange == ModeChange::Push(point.mode.clone()))
+                {
+                    return plainerr(&format!("error: rule `{}` is in lexer mode `{}`, which nothing pushes", point.name, point.mode));
+                }
+                // words and symbols are only lexed in the default mode
+                if point.forms.iter().any(|form| form.tokens.iter().any(|token| !matches!(token, GrammarToken::Regex(_))))
+                {
+                    return plainerr(&format!("error: rule `{}` is in lexer mode `{}`, so it can only be made of regexes", point.name, point.mode));
+                }
+            }
+        }
+        for (name, words) in excluded
+        {
+            if let Some(point) = self.nodetypemap.get_mut(&name)
+            {
+                point.excluded = words;
+            }
+        }
+        
+        let mut points : Vec<&GrammarPoint> = self.nodetypemap.values().filter(|point| point.istoken).collect();
+        points.sort_by_key(|point| (-point.priority, point.id));
+        self.lex_rules = points.iter().flat_map(|point| point.forms.iter().flat_map(move |form| form.tokens.iter().filter_map(move |token|
+            match_or_none!(token, GrammarToken::Regex(regex) => LexRule{regex : regex.clone(), mode : point.mode.clone(), modechange : point.modechange.clone()})
+        ))).collect();
         
         Ok(())
     }
     
-    // FIXME: change it to not be line-based; seek to the next newline instead. necessary for things like strings containing newline literals, which should definitely be supported.
-    pub (crate) fn tokenize(&mut self, lines : &[String], silent: bool) -> Result<Vec<LexToken>, String>
+    // works on the whole program text, so tokens (e.g. strings) can span several lines
+    // token positions are codepoint columns, not bytes
+    pub (crate) fn tokenize(&mut self, text : &str, silent: bool) -> Result<Vec<LexToken>, String>
+    {
+        self.tokenize_inner(text, silent, None, None)
+    }
+    // skips over text that can't be tokenized instead of failing, recording diagnostics for it
+    pub (crate) fn tokenize_with_recovery(&mut self, text : &str, diagnostics : &mut Vec<Diagnostic>) -> Result<Vec<LexToken>, String>
+    {
+        self.tokenize_inner(text, true, Some(diagnostics), None)
+    }
+    // keeps comments as trivia instead of throwing them away, for tools that write the program back out
+    pub (crate) fn tokenize_with_comments(&mut self, text : &str, comments : &mut Vec<LexToken>) -> Result<Vec<LexToken>, String>
+    {
+        self.tokenize_inner(text, true, None, Some(comments))
+    }
+    fn tokenize_inner(&mut self, text : &str, silent: bool, mut diagnostics : Option<&mut Vec<Diagnostic>>, mut comments : Option<&mut Vec<LexToken>>) -> Result<Vec<LexToken>, String>
     {
         let start_time = Instant::now();
         
         let mut ret : Vec<_> = Vec::new();
+        
+        let mut offset : usize = 0; // in bytes
         let mut linecount = 1;
+        let mut column = 1; // in codepoints
+        
+        // moves past the given text, keeping track of lines and columns
+        macro_rules! advance { ($text:expr) =>
+        {
+            for c in $text.chars()
+            {
+                if c == '\n'
+                {
+                    linecount += 1;
+                    column = 1;
+                }
+                else
+                {
+                    column += 1;
+                }
+            }
+            offset += $text.len();
+        } }
         
-        let mut in_multiline_comment = false;
+        let mut modes = vec!(DEFAULT_MODE.to_string());
         
-        for line in lines
+        // offset just past the last text skipped in recovery mode, so that a run of bad text only gets one diagnostic
+        let mut skipped_until = None;
+        
+        // fails, or in recovery mode, records a diagnostic and skips a single character
+        macro_rules! lex_error { ($message:expr, $diagnostic:expr) =>
         {
-            let mut offset : usize = 0; // in bytes
-            while offset < line.len() // also in bytes
+            if let Some(diagnostics) = diagnostics.as_mut()
+            {
+                if skipped_until != Some(offset)
+                {
+                    diagnostics.push(Diagnostic{line : linecount, position : column, severity : Severity::Error, message : $diagnostic});
+                }
+                let len = text[offset..].chars().next().map(|c| c.len_utf8()).unwrap_or(1);
+                advance!(&text[offset..offset+len]);
+                skipped_until = Some(offset);
+                continue;
+            }
+            else
+            {
+                return Err(tokenize_error(text, linecount, column, &$message));
+            }
+        } }
+        
+        while offset < text.len()
+        {
+            let mode = modes.last().cloned().unwrap_or_default();
+            // whitespace, comments, symbols, and words only exist in the default mode
+            let in_default = mode == DEFAULT_MODE;
+            if in_default
             {
                 // check for comments before doing anything else
-                if let Some(signal) = line.get(offset..offset+2)
+                if let Some(signal) = text.get(offset..offset+2)
                 {
-                    if signal == "*/" && in_multiline_comment
+                    let len = match signal
                     {
-                        in_multiline_comment = false;
-                        offset += 2;
-                        continue;
-                    }
-                    else if signal == "/*"
+                        "/*" => text[offset+2..].find("*/").map(|end| end+4).unwrap_or(text.len()-offset),
+                        "//" => text[offset..].find('\n').map(|end| text[offset..offset+end].trim_end_matches('\r').len()).unwrap_or(text.len()-offset),
+                        _ => 0
+                    };
+                    if len > 0
                     {
-                        in_multiline_comment = true;
-                        offset += 2;
+                        if let Some(comments) = comments.as_mut()
+                        {
+                            comments.push(LexToken{text : text[offset..offset+len].to_string(), line : linecount, position : column});
+                        }
+                        advance!(&text[offset..offset+len]);
                         continue;
                     }
-                    else if signal == "//"
-                    {
-                        break;
-                    }
                 }
-                if in_multiline_comment
+                // check for whitespace before doing any tokens
+                if let Some(found) = self.internal_regexes.match_at("[ \r\n\t]+", text, offset)
                 {
-                    offset += 1;
+                    advance!(found);
                     continue;
                 }
-                // check for whitespace before doing any tokens
-                if let Some(text) = self.internal_regexes.match_at("[ \r\n\t]+", &line, offset)
+            }
+            
+            let mut continue_the_while = false;
+            for rule in &self.lex_rules
+            {
+                if rule.mode != mode
                 {
-                    offset += text.len();
                     continue;
                 }
-                
-                let mut continue_the_while = false;
-                for rule in &self.regex_list
+                if let Some(found) = self.internal_regexes.match_at(&rule.regex, text, offset)
                 {
-                    if let Some(text) = self.internal_regexes.match_at(&rule, &line, offset)
+                    ret.push(LexToken{text : found.to_string(), line : linecount, position : column});
+                    match &rule.modechange
                     {
-                        // TODO: fix position everywhere to be codepoints instead of bytes
-                        ret.push(LexToken{text : text.clone(), line : linecount, position : offset+1});
-                        offset += text.len();
-                        continue_the_while = true;
-                        break;
+                        ModeChange::Push(newmode) => modes.push(newmode.clone()),
+                        // popping the base mode does nothing, so that e.g. a stray } is reported by the parser instead
+                        ModeChange::Pop if modes.len() > 1 => drop(modes.pop()),
+                        _ => {}
                     }
+                    advance!(found);
+                    continue_the_while = true;
+                    break;
                 }
-                if continue_the_while { continue; }
-                for text in &self.symbol_list
+            }
+            if continue_the_while { continue; }
+            if !in_default
+            {
+                lex_error!(format!("failed to tokenize program in lexer mode `{}`", mode), format!("error: unexpected character in lexer mode `{}`", mode));
+            }
+            for symbol in &self.symbol_list
+            {
+                if let Some(segment) = text.get(offset..offset+symbol.len())
                 {
-                    if let Some(segment) = line.get(offset..offset+text.len())
+                    if segment == symbol.as_str()
                     {
-                        if segment == text.as_str()
-                        {
-                            ret.push(LexToken{text : text.clone(), line : linecount, position : offset+1});
-                            offset += text.len();
-                            continue_the_while = true;
-                            break;
-                        }
+                        ret.push(LexToken{text : symbol.clone(), line : linecount, position : column});
+                        advance!(symbol);
+                        continue_the_while = true;
+                        break;
                     }
                 }
-                if continue_the_while { continue; }
-                for text in &self.text_list
+            }
+            if continue_the_while { continue; }
+            for word in &self.text_list
+            {
+                if let Some(segment) = text.get(offset..offset+word.len())
                 {
-                    if let Some(segment) = line.get(offset..offset+text.len())
+                    // don't tokenize the beginnings of names as actual words
+                    let at_boundary = text[offset+segment.len()..].chars().next().map(|c| !(c.is_alphanumeric() || c == '_')).unwrap_or(true);
+                    if segment == word.as_str() && at_boundary
                     {
-                        if segment == text.as_str()
-                        {
-                            // don't tokenize the beginnings of names as actual names
-                            if offset + text.len() + 1 > line.len() && self.internal_regexes.is_exact(r"[a-zA-Z0-9_]", &slice(&line, (offset+text.len()) as i64, (offset+text.len()+1) as i64))
-                            {
-                                continue;
-                            }
-                            ret.push(LexToken{text : text.clone(), line : linecount, position : offset+1});
-                            offset += text.len();
-                            continue_the_while = true;
-                            break;
-                        }
+                        ret.push(LexToken{text : word.clone(), line : linecount, position : column});
+                        advance!(word);
+                        continue_the_while = true;
+                        break;
                     }
                 }
-                if continue_the_while { continue; }
-                return plainerr(&format!("failed to tokenize program\noffending line:\n{}", line));
             }
-            linecount += 1;
+            if continue_the_while { continue; }
+            lex_error!("failed to tokenize program", "error: unexpected character".to_string());
+        }
+        // unclosed default modes (e.g. braces) are left for the parser to report
+        if let Some(mode) = modes.last().filter(|mode| mode.as_str() != DEFAULT_MODE)
+        {
+            match diagnostics
+            {
+                Some(diagnostics) => diagnostics.push(Diagnostic{line : linecount, position : column, severity : Severity::Error, message : format!("error: program ended in lexer mode `{}`", mode)}),
+                None => return Err(tokenize_error(text, linecount, column, &format!("failed to tokenize program: program ended in lexer mode `{}`", mode)))
+            }
         }
         
         if !silent
@@ -329,7 +583,7 @@ impl Parser {
     }
 
     // attempts to parse a token list as a particular form of a grammar point
-    fn parse_form(&self, tokens : &[LexToken], index : usize, form : &GrammarForm, formname : Option<&str>) -> Result<ParseVecInfo, String>
+    fn parse_form(&self, tokens : &[LexToken], index : usize, form : &GrammarForm, formname : Option<&str>, cache : &mut ParseCache) -> Result<ParseVecInfo, String>
     {
         if tokens.len() == 0
         {
@@ -354,7 +608,7 @@ impl Parser {
                 {
                     let kind = self.nodetypemap.get(text).ok_or_else(|| minierr(&format!("internal error: failed to find node type {} used by some grammar form", text)))?;
                     
-                    let (bit, consumed, error) = self.parse(&tokens, index+totalconsumed, kind)?;
+                    let (bit, consumed, error) = self.parse(tokens, index+totalconsumed, kind, cache)?;
                     build_best_error(&mut latesterror, error);
                     if let Some(node) = bit
                     {
@@ -370,7 +624,7 @@ impl Parser {
                 {
                     let kind = self.nodetypemap.get(text).ok_or_else(|| minierr(&format!("internal error: failed to find node type {} used by some grammar form", text)))?;
                     
-                    let (bit, consumed, error) = self.parse(&tokens, index+totalconsumed, kind)?;
+                    let (bit, consumed, error) = self.parse(tokens, index+totalconsumed, kind, cache)?;
                     build_best_error(&mut latesterror, error);
                     if let Some(node) = bit
                     {
@@ -382,27 +636,32 @@ impl Parser {
                 {
                     let kind = self.nodetypemap.get(text).ok_or_else(|| minierr(&format!("internal error: failed to find node type {} used by some grammar form", text)))?;
                     
-                    let (mut bit, mut consumed, mut error) = self.parse(&tokens, index+totalconsumed, kind)?;
-                    build_best_error(&mut latesterror, error);
-                    
-                    while let Some(node) = bit
+                    loop
                     {
-                        nodes.push(node);
-                        totalconsumed += consumed;
-                        
-                        let tuple = self.parse(&tokens, index+totalconsumed, kind)?;
-                        bit = tuple.0;
-                        consumed = tuple.1;
-                        error = tuple.2;
-                        
-                        build_best_error(&mut latesterror, error);
+                        let (bit, consumed, error) = self.parse(tokens, index+totalconsumed, kind, cache)?;
+                        if let Some(node) = bit
+                        {
+                            nodes.push(node);
+                            totalconsumed += consumed;
+                            build_best_error(&mut latesterror, error);
+                        }
+                        else if let Some((node, skipped)) = self.recover(tokens, index+totalconsumed, kind, &error, cache)
+                        {
+                            nodes.push(node);
+                            totalconsumed += skipped;
+                        }
+                        else
+                        {
+                            build_best_error(&mut latesterror, error);
+                            break;
+                        }
                     }
                 }
                 GrammarToken::SpecialNameList{text, subtype} =>
                 {
                     let kind = self.nodetypemap.get(text).ok_or_else(|| minierr(&format!("internal error: failed to find node type {} used by some grammar form", text)))?;
                     
-                    let (mut bit, mut consumed, mut error) = self.parse(&tokens, index+totalconsumed, kind)?;
+                    let (mut bit, mut consumed, mut error) = self.parse(tokens, index+totalconsumed, kind, cache)?;
                     build_best_error(&mut latesterror, error);
                     
                     while let Some(node) = bit
@@ -410,7 +669,7 @@ impl Parser {
                         nodes.push(node);
                         totalconsumed += consumed;
                         
-                        let tuple = self.parse(&tokens, index+totalconsumed, kind)?;
+                        let tuple = self.parse(tokens, index+totalconsumed, kind, cache)?;
                         bit = tuple.0;
                         consumed = tuple.1;
                         error = tuple.2;
@@ -420,7 +679,6 @@ impl Parser {
                     
                     if nodes.len() == 0 || nodes.last().unwrap().child(0).unwrap().text != *subtype
                     {
-                        println!("{:#?}", nodes.last().unwrap());
                         return Ok((defaultreturn.0, defaultreturn.1, latesterror));
                     }
                 }
@@ -428,7 +686,7 @@ impl Parser {
                 {
                     let kind = self.nodetypemap.get(text).ok_or_else(|| minierr(&format!("internal error: failed to find node type {} used by some grammar form", text)))?;
                     
-                    let (mut bit, mut consumed, mut error) = self.parse(&tokens, index+totalconsumed, kind)?;
+                    let (mut bit, mut consumed, mut error) = self.parse(tokens, index+totalconsumed, kind, cache)?;
                     build_best_error(&mut latesterror, error);
                     if bit.is_none()
                     {
@@ -445,7 +703,7 @@ impl Parser {
                             {
                                 totalconsumed += 1;
                                 
-                                let tuple = self.parse(&tokens, index+totalconsumed, kind)?;
+                                let tuple = self.parse(tokens, index+totalconsumed, kind, cache)?;
                                 bit = tuple.0;
                                 consumed = tuple.1;
                                 error = tuple.2;
@@ -474,7 +732,7 @@ impl Parser {
                             continue;
                         }
                     }
-                    build_new_error(&mut latesterror, index+totalconsumed, &text);
+                    build_new_error(&mut latesterror, index+totalconsumed, text);
                     return Ok((defaultreturn.0, defaultreturn.1, latesterror));
                 }
                 GrammarToken::Regex(text) =>
@@ -488,7 +746,7 @@ impl Parser {
                             continue;
                         }
                     }
-                    build_new_error(&mut latesterror, index+totalconsumed, formname.unwrap_or(&text));
+                    build_new_error(&mut latesterror, index+totalconsumed, formname.unwrap_or(text));
                     return Ok((defaultreturn.0, defaultreturn.1, latesterror));
                 }
                 GrammarToken::RestIsOptional =>
@@ -501,14 +759,115 @@ impl Parser {
         Ok((Some(nodes), totalconsumed, latesterror))
     }
 
+    // in recovery mode, skips a statement that failed to parse, up to and including the next ; or block, and returns an error node to put in its place
+    // the error node's first child holds the error message at the location of the error, and the rest are the skipped tokens
+    fn recover(&self, tokens : &[LexToken], index : usize, kind : &GrammarPoint, error : &Option<ParseError>, cache : &ParseCache) -> Option<(ASTNode, usize)>
+    {
+        if !cache.recover || kind.name != RECOVERY_POINT
+        {
+            return None;
+        }
+        let first = tokens.get(index)?;
+        // the end of a block belongs to whatever contains this statement list
+        if first.text == "}"
+        {
+            return None;
+        }
+        // unclosed parens and brackets are dropped once the block around them is closed, so that they don't swallow the rest of the program
+        let mut openers = Vec::new();
+        let mut end = index;
+        while let Some(token) = tokens.get(end)
+        {
+            let text = token.text.as_str();
+            if text == "}" && !openers.contains(&"{")
+            {
+                break;
+            }
+            end += 1;
+            if RECOVERY_PAIRS.iter().any(|pair| pair.0 == text)
+            {
+                openers.push(text);
+            }
+            else if text == "}"
+            {
+                while openers.pop().map(|opener| opener != "{").unwrap_or(false) {}
+                while openers.last().map(|opener| *opener != "{").unwrap_or(false)
+                {
+                    openers.pop();
+                }
+                if openers.is_empty()
+                {
+                    break;
+                }
+            }
+            else if let Some(pair) = RECOVERY_PAIRS.iter().find(|pair| pair.1 == text)
+            {
+                if openers.last() == Some(&pair.0)
+                {
+                    openers.pop();
+                }
+            }
+            else if text == ";" && openers.is_empty()
+            {
+                break;
+            }
+        }
+        
+        let (message, line, position) = match error
+        {
+            Some(error) =>
+            {
+                let location = match tokens.get(error.token)
+                {
+                    Some(token) => (token.line, token.position),
+                    // past the end of the program
+                    None => tokens.last().map(|token| (token.line, token.position + token.text.chars().count())).unwrap_or((first.line, first.position))
+                };
+                (describe_parse_error(error), location.0, location.1)
+            }
+            None => ("error: unexpected or malformed statement".to_string(), first.line, first.position)
+        };
+        let mut children = vec!(ASTNode{text : message, line, position, isparent : false, children : Vec::new(), precedence : None});
+        for token in &tokens[index..end]
+        {
+            children.push(ASTNode{text : token.text.clone(), line : token.line, position : token.position, isparent : false, children : Vec::new(), precedence : None});
+        }
+        Some((ASTNode{text : "error".to_string(), line : first.line, position : first.position, isparent : true, children, precedence : None}, end - index))
+    }
     // attempts to parse a token list as each form of a grammar point in order and uses the first valid one
-    fn parse(&self, tokens : &[LexToken], index : usize, nodetype : &GrammarPoint) -> Result<ParseInfo, String>
+    fn parse(&self, tokens : &[LexToken], index : usize, nodetype : &GrammarPoint, cache : &mut ParseCache) -> Result<ParseInfo, String>
     {
         if tokens.len() == 0
         {
             return Ok((Some(ASTNode{text : "program".to_string(), line : 0, position : 0, isparent : true, children : Vec::new(), precedence : None }), 0, None));
         }
-        
+        // tokens are cheap enough to parse that caching them is a net loss
+        if nodetype.istoken
+        {
+            if let Some(token) = tokens.get(index)
+            {
+                if nodetype.excluded.contains(&token.text)
+                {
+                    let mut error = None;
+                    build_new_error(&mut error, index, &nodetype.name);
+                    return Ok((None, 0, error));
+                }
+            }
+            return self.parse_uncached(tokens, index, nodetype, cache);
+        }
+        let seen = match cache.get(nodetype.id, index)
+        {
+            Some(Some(info)) => return Ok(info.clone()),
+            Some(None) => true,
+            None => false
+        };
+        let info = self.parse_uncached(tokens, index, nodetype, cache)?;
+        let stored = if seen || info.0.is_none() { Some(info.clone()) } else { None };
+        cache.insert(nodetype.id, index, stored);
+        Ok(info)
+    }
+    fn parse_uncached(&self, tokens : &[LexToken], index : usize, nodetype : &GrammarPoint, cache : &mut ParseCache) -> Result<ParseInfo, String>
+    {
         let mut latesterror : Option<ParseError> = None;
         
         for form in &nodetype.forms
@@ -522,7 +881,7 @@ impl Parser {
             {
                 None
             };
-            let (nodes, consumed, error) = self.parse_form(&tokens, index, form, sentname)?;
+            let (nodes, consumed, error) = self.parse_form(tokens, index, form, sentname, cache)?;
             build_best_error(&mut latesterror, error);
             if let Some(token) = tokens.get(index)
             {
@@ -578,9 +937,9 @@ impl Parser {
             }
             else
             {
-                for mut child in &mut ast.children
+                for child in &mut ast.children
                 {
-                    self.parse_fix_associativity(&mut child)?;
+                    self.parse_fix_associativity(child)?;
                 }
             }
         }
@@ -602,14 +961,14 @@ impl Parser {
                 std::mem::swap(ast, dummy);
             }
             
-            for mut child in &mut ast.children
+            for child in &mut ast.children
             {
-                self.parse_tweak_ast(&mut child)?;
+                self.parse_tweak_ast(child)?;
             }
         }
         Ok(())
     }
-    fn parse_tweak_ast_pass_2(&self, mut ast : &mut ASTNode) -> Result<(), String>
+    fn parse_tweak_ast_pass_2(&self, ast : &mut ASTNode) -> Result<(), String>
     {
         if ast.isparent
         {
@@ -649,9 +1008,9 @@ impl Parser {
                 }
             }
             
-            for mut child in &mut ast.children
+            for child in &mut ast.children
             {
-                self.parse_tweak_ast_pass_2(&mut child)?;
+                self.parse_tweak_ast_pass_2(child)?;
             }
         }
         Ok(())
@@ -679,7 +1038,7 @@ impl Parser {
         }
         if let Some(program_type) = self.nodetypemap.get("program")
         {
-            let (raw_ast, consumed, latesterror) = self.parse(&tokens, 0, program_type)?;
+            let (raw_ast, consumed, latesterror) = self.parse(tokens, 0, program_type, &mut ParseCache::new(tokens.len(), false))?;
             if !silent
             {
                 println!("successfully parsed {} out of {} tokens", consumed, tokens.len());
@@ -690,28 +1049,12 @@ impl Parser {
             {
                 if let Some(mut error) = latesterror
                 {
-                    let mut expected : Vec<String> = error.expected.into_iter().map(|x| x.into_string()).collect();
-                    expected.sort();
+                    println!("{}", describe_parse_error(&error));
                     let onepast = error.token == tokens.len();
                     if onepast
                     {
                         error.token -= 1;
                     }
-                    if expected.len() == 1
-                    {
-                        if let Some(expect) = expected.get(0)
-                        {
-                            println!("error: expected `{}`", expect);
-                        }
-                        else
-                        {
-                            println!("internal error: failed to grab expected symbol that was supposed to be there while printing parser error");
-                        }
-                    }
-                    else
-                    {
-                        println!("error: expected one of `{}`", expected.join("`, `"));
-                    }
                     if let Some(token) = tokens.get(error.token)
                     {
                         let linenum = token.line;
@@ -785,4 +1128,90 @@ impl Parser {
             plainerr("error: grammar does not define \"program\" node type")
         }
     }
+    // parses a whole program without fixing associativity or tweaking the tree, so that its leaves are still the program's tokens (minus list separators)
+    pub (crate) fn parse_raw(&self, tokens : &[LexToken]) -> Result<Option<ASTNode>, String>
+    {
+        let program_type = self.nodetypemap.get("program").ok_or_else(|| minierr("error: grammar does not define \"program\" node type"))?;
+        let (raw_ast, consumed, _) = self.parse(tokens, 0, program_type, &mut ParseCache::new(tokens.len(), false))?;
+        Ok(raw_ast.filter(|_| consumed == tokens.len()))
+    }
+    /// Parses program text without stopping at the first syntax error, for tooling like editor integrations.
+    ///
+    /// Statements that fail to parse are skipped up to the next `;` or the end of their block and replaced with "error" nodes.
+    /// The first child of an error node is the error message, located where the error is, and the rest are the skipped tokens.
+    ///
+    /// Returns the AST along with every syntax error found, in order. The AST can only be compiled if there are no errors.
+    pub fn parse_with_recovery(&mut self, text : &str) -> Result<(ASTNode, Vec<Diagnostic>), String>
+    {
+        let mut diagnostics = Vec::new();
+        let tokens = self.tokenize_with_recovery(text, &mut diagnostics)?;
+        let program_type = self.nodetypemap.get("program").ok_or_else(|| minierr("error: grammar does not define \"program\" node type"))?;
+        
+        // programs without syntax errors are parsed exactly like normal
+        let (raw_ast, consumed, _) = self.parse(&tokens, 0, program_type, &mut ParseCache::new(tokens.len(), false))?;
+        let mut ast = match raw_ast
+        {
+            Some(ast) if consumed == tokens.len() => ast,
+            _ =>
+            {
+                let mut cache = ParseCache::new(tokens.len(), true);
+                let mut ast : Option<ASTNode> = None;
+                let mut index = 0;
+                loop
+                {
+                    let (raw_ast, consumed, _) = self.parse(&tokens, index, program_type, &mut cache)?;
+                    match (ast.as_mut(), raw_ast)
+                    {
+                        (Some(ast), Some(mut more)) => ast.children.append(&mut more.children),
+                        (None, more) => ast = more,
+                        _ => {}
+                    }
+                    index += consumed;
+                    // whatever stopped the program from being parsed all the way is something like a stray }, so skip it and keep going
+                    match tokens.get(index)
+                    {
+                        Some(token) =>
+                        {
+                            let message = ASTNode{text : format!("error: unexpected `{}`", token.text), line : token.line, position : token.position, isparent : false, children : Vec::new(), precedence : None};
+                            let skipped = ASTNode{text : token.text.clone(), line : token.line, position : token.position, isparent : false, children : Vec::new(), precedence : None};
+                            let error = ASTNode{text : "error".to_string(), line : token.line, position : token.position, isparent : true, children : vec!(message, skipped), precedence : None};
+                            match ast.as_mut()
+                            {
+                                Some(ast) => ast.children.push(error),
+                                None => ast = Some(ASTNode{text : "program".to_string(), line : token.line, position : token.position, isparent : true, children : vec!(error), precedence : None})
+                            }
+                            index += 1;
+                        }
+                        None => break
+                    }
+                }
+                ast.ok_or_else(|| minierr("internal error: failed to parse any part of program in recovery mode"))?
+            }
+        };
+        
+        self.parse_fix_associativity(&mut ast)?;
+        self.parse_tweak_ast(&mut ast)?;
+        self.parse_tweak_ast_pass_2(&mut ast)?;
+        
+        // error nodes left behind by backtracking are gone by now, so only the ones that made it into the AST get reported
+        fn collect_errors(ast : &ASTNode, diagnostics : &mut Vec<Diagnostic>)
+        {
+            if ast.isparent && ast.text == "error"
+            {
+                if let Some(message) = ast.children.first()
+                {
+                    diagnostics.push(Diagnostic{line : message.line, position : message.position, severity : Severity::Error, message : message.text.clone()});
+                }
+            }
+            for child in &ast.children
+            {
+                collect_errors(child, diagnostics);
+            }
+        }
+        collect_errors(&ast, &mut diagnostics);
+        diagnostics.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.position));
+        diagnostics.dedup();
+        
+        Ok((ast, diagnostics))
+    }
 }
@@ -0,0 +1,106 @@
+use std::collections::{HashMap, BTreeMap};
+use std::sync::Arc;
+
+use super::compiler::Code;
+use super::interpreter::types::{ObjSpec, Value};
+
+#[derive(Debug, Clone, Default)]
+pub (crate) struct StringTableData {
+    next_index : usize,
+    table : HashMap<String, usize>,
+    reverse : BTreeMap<usize, String>,
+}
+
+impl StringTableData {
+    fn new() -> StringTableData
+    {
+        StringTableData { next_index : 1, table : HashMap::new(), reverse : BTreeMap::new() }
+    }
+}
+
+/// Maps strings to the indexes that bytecode refers to them by.
+///
+/// Made of a shared, immutable part (the string table of whatever program was loaded) and a local part for strings that were interned later.
+#[derive(Debug, Clone)]
+pub (crate) struct StringTable {
+    shared : Arc<StringTableData>,
+    local : StringTableData,
+}
+
+impl StringTable {
+    pub (crate) fn new() -> StringTable
+    {
+        StringTable::from_shared(&Arc::new(StringTableData::new()))
+    }
+    pub (crate) fn from_shared(shared : &Arc<StringTableData>) -> StringTable
+    {
+        let local = StringTableData { next_index : shared.next_index, table : HashMap::new(), reverse : BTreeMap::new() };
+        StringTable { shared : Arc::clone(shared), local }
+    }
+    pub (crate) fn get_index(&mut self, string : &str) -> usize
+    {
+        if let Some(index) = self.shared.table.get(string)
+        {
+            return *index;
+        }
+        if let Some(index) = self.local.table.get(string)
+        {
+            return *index;
+        }
+        let index = self.local.next_index;
+        self.local.next_index += 1;
+        self.local.table.insert(string.to_string(), index);
+        self.local.reverse.insert(index, string.to_string());
+        index
+    }
+    /// Like get_index(), but doesn't intern strings that aren't in the table yet.
+    pub (crate) fn find_index(&self, string : &str) -> Option<usize>
+    {
+        self.shared.table.get(string).or_else(|| self.local.table.get(string)).cloned()
+    }
+    pub (crate) fn get_string(&self, index : usize) -> Option<&String>
+    {
+        if index < self.shared.next_index
+        {
+            self.shared.reverse.get(&index)
+        }
+        else
+        {
+            self.local.reverse.get(&index)
+        }
+    }
+    /// Merges the shared and local parts into a new immutable table.
+    pub (crate) fn freeze(&self) -> Arc<StringTableData>
+    {
+        if self.local.table.is_empty()
+        {
+            return Arc::clone(&self.shared);
+        }
+        let mut data = (*self.shared).clone();
+        data.table.extend(self.local.table.iter().map(|(k, v)| (k.clone(), *v)));
+        data.reverse.extend(self.local.reverse.iter().map(|(k, v)| (*k, v.clone())));
+        data.next_index = self.local.next_index;
+        Arc::new(data)
+    }
+}
+
+/// A compiled program: bytecode, object types, global functions, and the string table they were compiled against.
+///
+/// Programs are immutable and cheap to clone. The same program can be loaded into any number of interpreters with Interpreter::load_program(); each interpreter keeps its own instances and global variables.
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub (crate) code : Code,
+    pub (crate) strings : Arc<StringTableData>,
+    pub (crate) objects : Arc<BTreeMap<usize, ObjSpec>>,
+    pub (crate) functions : Arc<BTreeMap<usize, Value>>,
+    pub (crate) variables : Arc<Vec<usize>>, // names of globalvar variables
+    pub (crate) barevariables : Arc<Vec<usize>>, // names of bare global variables
+}
+
+impl Program {
+    /// Returns the compiled bytecode of the program's main body.
+    pub fn code(&self) -> &Code
+    {
+        &self.code
+    }
+}
@@ -54,21 +54,16 @@ impl RegexHolder {
     // let re = Regex::new("[あそ]").unwrap();
     // assert!(re.find_at(mystr, 0).unwrap().start() == 0);
     // assert!(re.find_at(mystr, 3).unwrap().start() == 3);
-    pub (crate) fn match_at(&mut self, regex_text : &str, text : &str, start : usize) -> Option<String>
+    // the regex is anchored to the start offset, so failing to match doesn't search through the rest of the text
+    pub (crate) fn match_at<'t>(&mut self, regex_text : &str, text : &'t str, start : usize) -> Option<&'t str>
     {
         if let Some(regex) = self.regexes.get(regex_text)
         {
             let regex = regex.as_ref().ok()?;
-            let my_match = regex.find_at(text, start)?;
-            
-            if my_match.start() == start
-            {
-                return Some(my_match.as_str().to_string());
-            }
-            return None;
+            return regex.find(text.get(start..)?).map(|my_match| my_match.as_str());
         }
-        let regex = Regex::new(regex_text);
+        let regex = Regex::new(&format!("^(?:{})", regex_text));
         self.regexes.insert(regex_text.to_string(), regex);
         self.match_at(regex_text, text, start)
     }
-}
\ No newline at end of file
+}
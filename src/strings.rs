@@ -10,62 +10,185 @@ pub (crate) fn slice(text : &str, start : i64, end : i64) -> String
     slice_any(&text.chars().collect::<Vec<char>>(), start, end).map(|chars| chars.iter().collect()).unwrap_or_else(|| "".to_string())
 }
 
-pub (crate) fn slice_any_to_end<T>(collection : &[T], start : i64) -> Option<&[T]>
-{
-    let u_start = if start < 0 {collection.len() - (-start as usize)} else {start as usize};
-    
-    collection.get(u_start..)
-}
-pub (crate) fn slice_to_end(text : &str, start : i64) -> String
-{
-    slice_any_to_end(&text.chars().collect::<Vec<char>>(), start).map(|chars| chars.iter().collect()).unwrap_or_else(|| "".to_string())
-}
-
-pub (crate) fn unescape(text: &str) -> String
+// supported escapes: \\ \" \n \r \t \0 \{ \} \xNN (ascii only, up to 7F) and \u{N} (one to six hex digits, any unicode scalar value)
+// the error is the offset (in codepoints) of the invalid escape sequence, and the sequence itself
+pub (crate) fn unescape(text: &str) -> Result<String, (usize, String)>
 {
     let mut ret = String::with_capacity(text.len());
-    let mut chars : Vec<_> = text.chars().rev().collect();
-    while let Some(c) = chars.pop()
+    let chars : Vec<char> = text.chars().collect();
+    let mut i = 0;
+    while let Some(&c) = chars.get(i)
     {
+        i += 1;
         if c != '\\'
         {
             ret.push(c);
+            continue;
         }
-        else if let Some(c2) = chars.pop()
+        let start = i-1;
+        let c2 = chars.get(i).copied();
+        i += 1;
+        let hex_digits = |digits : &[char]| -> Option<u32>
+        {
+            if digits.is_empty() || !digits.iter().all(|c| c.is_ascii_hexdigit())
+            {
+                return None;
+            }
+            u32::from_str_radix(&digits.iter().collect::<String>(), 16).ok()
+        };
+        let escaped = match c2
         {
-            match c2
+            Some('\\') => Some('\\'),
+            Some('n') => Some('\n'),
+            Some('r') => Some('\r'),
+            Some('t') => Some('\t'),
+            Some('0') => Some('\0'),
+            Some('"') => Some('"'),
+            Some('{') => Some('{'),
+            Some('}') => Some('}'),
+            Some('x') =>
+            {
+                let digits = chars.get(i..i+2).unwrap_or(&[]);
+                i += digits.len();
+                hex_digits(digits).filter(|num| *num <= 0x7F).and_then(std::char::from_u32)
+            }
+            Some('u') if chars.get(i) == Some(&'{') =>
             {
-                '\\' => ret.push(c),
-                'n' => ret.push('\n'),
-                'r' => ret.push('\r'),
-                't' => ret.push('\t'),
-                '"' => ret.push('"'),
-                _ => ret.extend(&[c, c2])
+                match chars[i..].iter().take(8).position(|c| *c == '}')
+                {
+                    Some(end) =>
+                    {
+                        let digits = &chars[i+1..i+end];
+                        i += end+1;
+                        hex_digits(digits).filter(|_| digits.len() <= 6).and_then(std::char::from_u32)
+                    }
+                    None => None
+                }
             }
+            _ => None
+        };
+        match escaped
+        {
+            Some(escaped) => ret.push(escaped),
+            None => return Err((start, chars[start..i.min(chars.len())].iter().collect()))
         }
     }
-    ret
+    Ok(ret)
 }
 
+// the value of a string literal token: "text" with escapes, r"text" without them, or a """text block""" with escapes
+// text blocks drop the line break after the opening quotes, the line the closing quotes are on if it's blank, and the indentation common to every line that isn't blank
+// invalid escapes are reported as an offset into the token, see unescape()
+pub (crate) fn string_literal_value(text : &str) -> Result<String, (usize, String)>
+{
+    if text.starts_with("\"\"\"") && text.len() >= 6
+    {
+        let inner = &text[3..text.len()-3];
+        // check the escapes before removing indentation, so that the offsets of invalid ones are right
+        unescape(inner).map_err(|(offset, sequence)| (offset+3, sequence))?;
+        let inner = inner.strip_prefix("\r\n").or_else(|| inner.strip_prefix('\n')).unwrap_or(inner);
+        let mut lines : Vec<&str> = inner.split('\n').map(|line| line.trim_end_matches('\r')).collect();
+        if lines.len() > 1 && lines.last().map(|line| line.trim().is_empty()).unwrap_or(false)
+        {
+            lines.pop();
+        }
+        let indentation = lines.iter().filter(|line| !line.trim().is_empty()).map(|line| line.len() - line.trim_start_matches(&[' ', '\t'][..]).len()).min().unwrap_or(0);
+        let lines : Vec<&str> = lines.iter().map(|line| line.get(indentation..).unwrap_or("")).collect();
+        unescape(&lines.join("\n"))
+    }
+    else if text.starts_with('r')
+    {
+        Ok(slice(text, 2, -1))
+    }
+    else
+    {
+        unescape(&slice(text, 1, -1)).map_err(|(offset, sequence)| (offset+1, sequence))
+    }
+}
+
+// the inverse of unescape(), for showing strings as literals; other control characters are written as \xNN or \u{N}
 pub (crate) fn escape(text: &str) -> String
 {
     let mut ret = String::with_capacity(text.len());
-    let mut chars : Vec<_> = text.chars().rev().collect();
-    while let Some(c) = chars.pop()
+    for c in text.chars()
     {
         match c
         {
-            '\\' => ret.extend(&['\\', '\\']),
-            '\n' => ret.extend(&['\\', 'n']),
-            '\r' => ret.extend(&['\\', 'r']),
-            '\t' => ret.extend(&['\\', 't']),
-            '\"' => ret.extend(&['\\', '"']),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => ret.push_str("\\r"),
+            '\t' => ret.push_str("\\t"),
+            '\0' => ret.push_str("\\0"),
+            '\"' => ret.push_str("\\\""),
+            c if c.is_control() && c.is_ascii() => ret.push_str(&format!("\\x{:02x}", c as u32)),
+            c if c.is_control() => ret.push_str(&format!("\\u{{{:x}}}", c as u32)),
             _ => ret.push(c)
         }
     }
     ret
 }
 
+// format specifiers for interpolated strings, e.g. the ">8.2" in $"{x:>8.2}"
+// an optional alignment (< or >), then an optional width (zero-padded if it starts with 0), then an optional precision
+#[derive(Clone, Debug, Default, PartialEq)]
+pub (crate) struct FormatSpec {
+    pub (crate) align : Option<char>,
+    pub (crate) zero : bool,
+    pub (crate) width : usize,
+    pub (crate) precision : Option<usize>,
+}
+
+impl FormatSpec {
+    pub (crate) fn parse(text : &str) -> Option<FormatSpec>
+    {
+        let mut spec = FormatSpec::default();
+        let mut text = text;
+        if let Some(c) = text.chars().next().filter(|c| *c == '<' || *c == '>')
+        {
+            spec.align = Some(c);
+            text = &text[1..];
+        }
+        let (width, precision) = match text.find('.')
+        {
+            Some(dot) => (&text[..dot], Some(&text[dot+1..])),
+            None => (text, None)
+        };
+        if !width.is_empty()
+        {
+            spec.zero = width.starts_with('0');
+            spec.width = width.parse().ok()?;
+        }
+        if let Some(precision) = precision
+        {
+            spec.precision = Some(if precision.is_empty() { 0 } else { precision.parse().ok()? });
+        }
+        Some(spec)
+    }
+    // pads already-formatted text; numbers are right-aligned by default and everything else is left-aligned
+    pub (crate) fn pad(&self, text : String, is_number : bool) -> String
+    {
+        let len = text.chars().count();
+        if len >= self.width
+        {
+            return text;
+        }
+        let padding = self.width - len;
+        if self.zero && is_number
+        {
+            return match text.strip_prefix('-')
+            {
+                Some(digits) => format!("-{}{}", "0".repeat(padding), digits),
+                None => format!("{}{}", "0".repeat(padding), text)
+            };
+        }
+        match self.align.unwrap_or(if is_number { '>' } else { '<' })
+        {
+            '>' => format!("{}{}", " ".repeat(padding), text),
+            _ => format!("{}{}", text, " ".repeat(padding))
+        }
+    }
+}
+
 fn trim_at_null(mystr : &[u8]) -> &[u8]
 {
     let mut nullpos = 0usize;
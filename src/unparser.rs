@@ -0,0 +1,267 @@
+// turns ASTs back into program text, by matching each node's children against the forms of its grammar point
+// the AST still has every token as a leaf, except for list separators and the semicolons that end statements, which the forms say where to put back
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{ast::*, grammar::*, parser::*};
+
+// grammar points that get split into "X_head" nodes when tweaking the AST
+const HEAD_SOURCES : [&str; 3] = ["rhunexpr", "funccall", "lvrhunexpr"];
+
+// a token to write out, and whether it can't have whitespace on either side of it (parts of interpolated strings)
+struct Piece {
+    text : String,
+    glue_before : bool,
+    glue_after : bool,
+}
+
+enum Part {
+    Child(usize),
+    Separator(String),
+}
+
+struct Unparser<'a> {
+    parser : &'a Parser,
+    // grammar points that a node can be replaced by when expressions with a single child are collapsed
+    reach : HashMap<&'a str, HashSet<&'a str>>,
+}
+
+impl<'a> Unparser<'a> {
+    fn new(parser : &'a Parser) -> Unparser<'a>
+    {
+        // forms that parse as a single child, which can take the place of their parent
+        let mut single : HashMap<&str, Vec<&str>> = HashMap::new();
+        for (name, point) in &parser.nodetypemap
+        {
+            for form in &point.forms
+            {
+                if let Some(GrammarToken::Name(child)) = form.tokens.first()
+                {
+                    if form.tokens.len() == 1 || matches!(form.tokens.get(1), Some(GrammarToken::RestIsOptional))
+                    {
+                        single.entry(name.as_str()).or_default().push(child.as_str());
+                    }
+                }
+            }
+        }
+        let mut reach = HashMap::new();
+        for name in parser.nodetypemap.keys()
+        {
+            let mut found = HashSet::new();
+            let mut stack = vec!(name.as_str());
+            while let Some(next) = stack.pop()
+            {
+                if found.insert(next)
+                {
+                    stack.extend(single.get(next).into_iter().flatten());
+                }
+            }
+            reach.insert(name.as_str(), found);
+        }
+        Unparser{parser, reach}
+    }
+    // whether a child node can be what a $name$ in a form parsed as
+    fn accepts(&self, expected : &str, parent : &ASTNode, child : &ASTNode) -> bool
+    {
+        if !child.isparent
+        {
+            return false;
+        }
+        let reach = match self.reach.get(expected)
+        {
+            Some(reach) => reach,
+            None => return false
+        };
+        reach.contains(child.text.as_str())
+        || (child.text.ends_with("_head") && HEAD_SOURCES.iter().any(|source| reach.contains(source)))
+        // left-associative binary expressions are rotated so that they contain themselves on the left
+        || (parent.precedence.is_some() && child.text == parent.text)
+    }
+    fn accepts_leaf(&self, token : &GrammarToken, child : &ASTNode) -> Result<bool, String>
+    {
+        if child.isparent
+        {
+            return Ok(false);
+        }
+        match token
+        {
+            GrammarToken::Plain(text) => Ok(child.text == *text),
+            GrammarToken::Regex(regex) => self.parser.internal_regexes.is_exact_immut(regex, &child.text),
+            _ => Ok(false)
+        }
+    }
+    // matches the children of a node against the tokens of a form, starting at the given token and child, and lists where the children and separators go
+    fn match_form(&self, node : &ASTNode, tokens : &[GrammarToken], ti : usize, ci : usize) -> Result<Option<Vec<Part>>, String>
+    {
+        let children = &node.children;
+        let token = match tokens.get(ti)
+        {
+            Some(token) => token,
+            None => return Ok(if ci == children.len() { Some(Vec::new()) } else { None })
+        };
+        // prepends parts onto the rest of the match, if the rest matches
+        macro_rules! then { ($parts:expr, $ti:expr, $ci:expr) =>
+        {
+            self.match_form(node, tokens, $ti, $ci)?.map(|rest| { let mut parts : Vec<Part> = $parts; parts.extend(rest); parts })
+        } }
+        match token
+        {
+            GrammarToken::RestIsOptional =>
+            {
+                if ci == children.len()
+                {
+                    return Ok(Some(Vec::new()));
+                }
+                Ok(then!(Vec::new(), ti+1, ci))
+            }
+            GrammarToken::Plain(text) | GrammarToken::Regex(text) =>
+            {
+                if let Some(child) = children.get(ci)
+                {
+                    if self.accepts_leaf(token, child)?
+                    {
+                        return Ok(then!(vec!(Part::Child(ci)), ti+1, ci+1));
+                    }
+                }
+                // statements have their trailing semicolon stripped
+                if node.text == "statement" && text == ";" && ti+1 == tokens.len() && ci == children.len()
+                {
+                    return Ok(Some(vec!(Part::Separator(text.clone()))));
+                }
+                Ok(None)
+            }
+            GrammarToken::Name(name) =>
+            {
+                match children.get(ci)
+                {
+                    Some(child) if self.accepts(name, node, child) => Ok(then!(vec!(Part::Child(ci)), ti+1, ci+1)),
+                    _ => Ok(None)
+                }
+            }
+            GrammarToken::OptionalName(name) =>
+            {
+                if children.get(ci).map(|child| self.accepts(name, node, child)).unwrap_or(false)
+                {
+                    if let Some(parts) = then!(vec!(Part::Child(ci)), ti+1, ci+1)
+                    {
+                        return Ok(Some(parts));
+                    }
+                }
+                Ok(then!(Vec::new(), ti+1, ci))
+            }
+            GrammarToken::OptionalNameList(name) | GrammarToken::SpecialNameList{text : name, ..} | GrammarToken::SeparatorNameList{text : name, ..} =>
+            {
+                let (minimum, separator) = match token
+                {
+                    GrammarToken::OptionalNameList(_) => (0, None),
+                    GrammarToken::SpecialNameList{..} => (1, None),
+                    GrammarToken::SeparatorNameList{separator, ..} => (1, Some(separator)),
+                    _ => return Err("internal error: unhandled list kind in unparser".to_string())
+                };
+                let mut count = 0;
+                while children.get(ci+count).map(|child| self.accepts(name, node, child)).unwrap_or(false)
+                {
+                    count += 1;
+                }
+                // lists are parsed greedily, but the rest of the form still has to match
+                while count >= minimum
+                {
+                    let mut parts = Vec::new();
+                    for i in 0..count
+                    {
+                        if i > 0
+                        {
+                            if let Some(separator) = separator
+                            {
+                                parts.push(Part::Separator(separator.clone()));
+                            }
+                        }
+                        parts.push(Part::Child(ci+i));
+                    }
+                    if let Some(parts) = then!(parts, ti+1, ci+count)
+                    {
+                        return Ok(Some(parts));
+                    }
+                    if count == 0
+                    {
+                        break;
+                    }
+                    count -= 1;
+                }
+                Ok(None)
+            }
+        }
+    }
+    fn unparse(&self, node : &ASTNode, parent : &str, pieces : &mut Vec<Piece>) -> Result<(), String>
+    {
+        if !node.isparent
+        {
+            let interp_piece = parent == "interptext" || parent == "interpend" || parent == "interpopen";
+            pieces.push(Piece{
+                text : node.text.clone(),
+                glue_before : interp_piece,
+                glue_after : interp_piece || parent == "interpstart" || (parent == "interpexpr" && node.text == "}")
+            });
+            return Ok(());
+        }
+        let parts = if node.text.ends_with("_head")
+        {
+            (0..node.children.len()).map(Part::Child).collect()
+        }
+        else
+        {
+            let point = self.parser.nodetypemap.get(&node.text).ok_or_else(|| format!("error: can't unparse AST node of unknown type `{}`", node.text))?;
+            let mut found = None;
+            for form in &point.forms
+            {
+                found = self.match_form(node, &form.tokens, 0, 0)?;
+                if found.is_some()
+                {
+                    break;
+                }
+            }
+            found.ok_or_else(|| format!("error: can't unparse AST node of type `{}`; its children don't match any of its grammar forms", node.text))?
+        };
+        for part in parts
+        {
+            match part
+            {
+                Part::Child(i) => self.unparse(&node.children[i], &node.text, pieces)?,
+                Part::Separator(text) => pieces.push(Piece{text, glue_before : false, glue_after : false})
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Parser {
+    /// Turns an AST from Parser::parse_program() (or a modified one) back into program text that parses to an equal AST, except for line and position information.
+    ///
+    /// Whole programs are written in the canonical style used by Parser::format(). Other nodes (e.g. a single expression) are written on one line.
+    ///
+    /// Returns Err if a node's children can't have been parsed from any form of its grammar point.
+    pub fn unparse(&mut self, ast : &ASTNode) -> Result<String, String>
+    {
+        let mut pieces = Vec::new();
+        Unparser::new(self).unparse(ast, "", &mut pieces)?;
+        
+        let mut text = String::new();
+        for (i, piece) in pieces.iter().enumerate()
+        {
+            if i > 0 && !piece.glue_before && !pieces[i-1].glue_after
+            {
+                text.push(' ');
+            }
+            text.push_str(&piece.text);
+        }
+        Ok(self.format(&text).unwrap_or(text))
+    }
+}
+
+impl ASTNode {
+    /// Turns an AST from the default grammar back into program text. See Parser::unparse().
+    pub fn to_source(&self) -> Result<String, String>
+    {
+        Parser::new_from_default()?.unparse(self)
+    }
+}